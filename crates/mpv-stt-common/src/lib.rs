@@ -1,5 +1,13 @@
 use thiserror::Error;
 
+mod pcm;
+mod transcript;
+
+pub use pcm::{PcmBuffer, rms_db};
+pub use transcript::{
+    SegmentSink, Transcript, TranscriptMetrics, TranscriptSegment, TranscriptionOptions,
+};
+
 #[derive(Error, Debug)]
 pub enum MpvSttError {
     #[error("IO error: {0}")]
@@ -26,12 +34,22 @@ pub enum MpvSttError {
     #[error("WAV error: {0}")]
     Wav(String),
 
+    #[error("Ogg Opus error: {0}")]
+    OggOpus(String),
+
     #[error("STT execution failed: {0}")]
     SttFailed(String),
 
     #[error("STT execution cancelled")]
     SttCancelled,
 
+    #[error("STT server busy, retry after {}s", .0.as_secs())]
+    SttBusy(std::time::Duration),
+
+    /// The server refused the request in a way a retry cannot fix, e.g. a revoked key.
+    #[error("STT request rejected: {0}")]
+    SttRejected(String),
+
     #[error("Invalid path: {0}")]
     InvalidPath(String),
 
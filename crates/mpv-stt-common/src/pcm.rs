@@ -0,0 +1,171 @@
+use crate::{MpvSttError, Result};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::io::Cursor;
+use std::path::Path;
+
+/// Interleaved 16-bit PCM audio held in memory.
+///
+/// This is the unit of audio passed between the extractor, STT backends and the server
+/// worker, so chunks never have to round-trip through a WAV file on disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PcmBuffer {
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl PcmBuffer {
+    pub fn new(samples: Vec<i16>, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            samples,
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Digital silence of the given length.
+    pub fn silence(duration_ms: u64, sample_rate: u32, channels: u16) -> Self {
+        let frames = duration_ms.saturating_mul(sample_rate as u64) / 1000;
+        let len = (frames as usize).saturating_mul(channels as usize);
+        Self::new(vec![0; len], sample_rate, channels)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Number of sample frames (samples per channel).
+    pub fn frames(&self) -> usize {
+        if self.channels == 0 {
+            return 0;
+        }
+        self.samples.len() / self.channels as usize
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        if self.sample_rate == 0 {
+            return 0;
+        }
+        (self.frames() as u64).saturating_mul(1000) / self.sample_rate as u64
+    }
+
+    /// Decode a 16-bit integer WAV held in memory.
+    pub fn from_wav_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_reader(WavReader::new(Cursor::new(data))?)
+    }
+
+    /// Read a 16-bit integer WAV file.
+    pub fn read_wav<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(WavReader::open(path)?)
+    }
+
+    fn from_reader<R: std::io::Read>(mut reader: WavReader<R>) -> Result<Self> {
+        let spec = reader.spec();
+        if spec.bits_per_sample != 16 || spec.sample_format != SampleFormat::Int {
+            return Err(MpvSttError::Wav(format!(
+                "unsupported sample format: {}-bit {:?}",
+                spec.bits_per_sample, spec.sample_format
+            )));
+        }
+        let samples = reader
+            .samples::<i16>()
+            .collect::<std::result::Result<Vec<i16>, _>>()?;
+        Ok(Self::new(samples, spec.sample_rate, spec.channels))
+    }
+
+    /// Encode as a 16-bit integer WAV in memory.
+    pub fn to_wav_bytes(&self) -> Result<Vec<u8>> {
+        let mut cursor = Cursor::new(Vec::with_capacity(44 + self.samples.len() * 2));
+        self.write_samples(WavWriter::new(&mut cursor, self.wav_spec())?)?;
+        Ok(cursor.into_inner())
+    }
+
+    /// Write a 16-bit integer WAV file.
+    pub fn write_wav<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.write_samples(WavWriter::create(path, self.wav_spec())?)
+    }
+
+    fn wav_spec(&self) -> WavSpec {
+        WavSpec {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        }
+    }
+
+    fn write_samples<W: std::io::Write + std::io::Seek>(
+        &self,
+        mut writer: WavWriter<W>,
+    ) -> Result<()> {
+        for sample in &self.samples {
+            writer.write_sample(*sample)?;
+        }
+        writer.finalize()?;
+        Ok(())
+    }
+}
+
+/// Loudness of `samples` in dBFS; `-inf` when empty.
+pub fn rms_db(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let energy = samples
+        .iter()
+        .map(|s| (*s as f64 / 32_768.0).powi(2))
+        .sum::<f64>()
+        / samples.len() as f64;
+    10.0 * (energy.max(f64::MIN_POSITIVE) as f32).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_bytes_roundtrip() {
+        let pcm = PcmBuffer::new(vec![0, 1, -1, i16::MAX, i16::MIN, 42], 16_000, 2);
+        let bytes = pcm.to_wav_bytes().unwrap();
+        assert!(bytes.starts_with(b"RIFF"));
+
+        let decoded = PcmBuffer::from_wav_bytes(&bytes).unwrap();
+        assert_eq!(decoded, pcm);
+    }
+
+    #[test]
+    fn test_duration_ms() {
+        let pcm = PcmBuffer::silence(1_500, 16_000, 1);
+        assert_eq!(pcm.frames(), 24_000);
+        assert_eq!(pcm.duration_ms(), 1_500);
+
+        let stereo = PcmBuffer::silence(250, 48_000, 2);
+        assert_eq!(stereo.samples.len(), 24_000);
+        assert_eq!(stereo.duration_ms(), 250);
+    }
+
+    #[test]
+    fn test_rms_db() {
+        assert!(rms_db(&[0; 160]) < -300.0);
+        assert!(rms_db(&[i16::MIN; 160]).abs() < 0.01);
+        assert!((rms_db(&[16_384, -16_384]) + 6.02).abs() < 0.01);
+        assert_eq!(rms_db(&[]), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_rejects_float_wav() {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 16_000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut writer = WavWriter::new(&mut cursor, spec).unwrap();
+            writer.write_sample(0.5f32).unwrap();
+            writer.finalize().unwrap();
+        }
+        assert!(PcmBuffer::from_wav_bytes(cursor.get_ref()).is_err());
+    }
+}
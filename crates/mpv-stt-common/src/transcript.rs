@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// One recognised span of speech, timed relative to the start of the transcribed audio.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+/// Where the time went while producing a [`Transcript`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptMetrics {
+    /// Wall-clock time of the whole backend call.
+    pub total_ms: u64,
+    /// Time spent running the model (reported by the server for remote backends).
+    pub inference_ms: u64,
+    /// Time spent waiting in a server queue; zero for local backends.
+    pub queue_ms: u64,
+}
+
+/// Typed result of a speech-to-text run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub segments: Vec<TranscriptSegment>,
+    /// Detected or configured language code, when the backend knows it.
+    pub language: Option<String>,
+    /// Length of the audio that was transcribed.
+    pub duration_ms: u64,
+    pub metrics: TranscriptMetrics,
+}
+
+/// Per-call overrides of a backend's configured behaviour.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionOptions {
+    /// Spoken language code; `None` keeps the configured language (which may be "auto").
+    pub language: Option<String>,
+    /// Text that primes the decoder, e.g. names or the preceding dialogue.
+    pub prompt: Option<String>,
+    /// Translate the speech to English instead of transcribing it.
+    pub translate: bool,
+    /// Decoding temperature; `None` keeps the backend's default schedule.
+    pub temperature: Option<f32>,
+    /// Split the transcript into one segment per word.
+    pub word_timestamps: bool,
+}
+
+/// Callback that receives each segment as soon as a backend has finalised it.
+#[derive(Clone)]
+pub struct SegmentSink(Arc<dyn Fn(&TranscriptSegment) + Send + Sync>);
+
+impl SegmentSink {
+    pub fn new(emit: impl Fn(&TranscriptSegment) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(emit))
+    }
+
+    pub fn emit(&self, segment: &TranscriptSegment) {
+        (self.0)(segment)
+    }
+
+    /// Sink that drops segments ending at or before the last one forwarded, so a retried
+    /// run does not repeat what was already delivered.
+    pub fn deduplicated(&self) -> Self {
+        let inner = self.clone();
+        let emitted_until = AtomicU64::new(0);
+        Self::new(move |segment| {
+            if segment.end_ms > emitted_until.load(Ordering::Relaxed) {
+                emitted_until.store(segment.end_ms, Ordering::Relaxed);
+                inner.emit(segment);
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for SegmentSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SegmentSink")
+    }
+}
+
+impl Transcript {
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Shift every segment by `offset_ms`, e.g. to place a chunk on the media timeline.
+    pub fn offset(&mut self, offset_ms: u64) {
+        for segment in &mut self.segments {
+            segment.start_ms = segment.start_ms.saturating_add(offset_ms);
+            segment.end_ms = segment.end_ms.saturating_add(offset_ms);
+        }
+    }
+
+    /// Segment texts joined by newlines.
+    pub fn text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_and_text() {
+        let mut transcript = Transcript {
+            segments: vec![
+                TranscriptSegment {
+                    start_ms: 0,
+                    end_ms: 1_200,
+                    text: "hello".to_string(),
+                },
+                TranscriptSegment {
+                    start_ms: 1_500,
+                    end_ms: 2_000,
+                    text: "world".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        transcript.offset(30_000);
+        assert_eq!(transcript.segments[0].start_ms, 30_000);
+        assert_eq!(transcript.segments[1].end_ms, 32_000);
+        assert_eq!(transcript.text(), "hello\nworld");
+    }
+
+    #[test]
+    fn test_deduplicated_sink_skips_replayed_segments() {
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = {
+            let received = Arc::clone(&received);
+            SegmentSink::new(move |segment| received.lock().unwrap().push(segment.end_ms))
+        }
+        .deduplicated();
+
+        let segment = |end_ms| TranscriptSegment {
+            start_ms: end_ms - 500,
+            end_ms,
+            text: String::new(),
+        };
+        for end_ms in [1_000, 2_000, 1_000, 2_000, 3_000] {
+            sink.emit(&segment(end_ms));
+        }
+        assert_eq!(*received.lock().unwrap(), vec![1_000, 2_000, 3_000]);
+    }
+}
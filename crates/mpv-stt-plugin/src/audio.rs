@@ -1,11 +1,15 @@
+use crate::preprocess::AudioPreprocessor;
 use ffmpeg::format::Sample;
 use ffmpeg::format::sample::Type as SampleType;
 use ffmpeg::util::mathematics::rescale;
 use ffmpeg::util::mathematics::rescale::Rescale;
 use ffmpeg_next as ffmpeg;
+use ffmpeg_next::ffi;
 use log::{debug, trace};
-use mpv_stt_common::{MpvSttError, Result};
+use mpv_stt_common::{MpvSttError, PcmBuffer, Result};
+use std::os::raw::{c_int, c_void};
 use std::path::Path;
+use std::ptr;
 use std::sync::{
     Arc, OnceLock,
     atomic::{AtomicU64, Ordering},
@@ -48,12 +52,136 @@ fn output_channel_layout(channels: u8) -> ffmpeg::channel_layout::ChannelLayout
     }
 }
 
+/// Read size of the AVIO context behind [`MemoryInput`].
+const AVIO_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A media file held in memory, demuxed by ffmpeg through a custom AVIO context so it
+/// never has to be written to disk first.
+struct MemoryInput<'a> {
+    // Fields drop in order: the demuxer is closed before the IO context it reads from.
+    context: ffmpeg::format::context::Input,
+    _io: AvioContext,
+    _reader: Box<MemoryReader<'a>>,
+}
+
+struct MemoryReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+/// Owns an AVIO context and its buffer.
+struct AvioContext(*mut ffi::AVIOContext);
+
+impl Drop for AvioContext {
+    fn drop(&mut self) {
+        // SAFETY: the context came from `avio_alloc_context` and nothing uses it any more;
+        // ffmpeg may have swapped its buffer, so the one it holds now is freed.
+        unsafe {
+            ffi::av_freep(ptr::addr_of_mut!((*self.0).buffer).cast());
+            ffi::avio_context_free(&mut self.0);
+        }
+    }
+}
+
+impl<'a> MemoryInput<'a> {
+    fn open(data: &'a [u8]) -> Result<Self> {
+        let out_of_memory = || ffmpeg_err("open input failed", "out of memory");
+        let mut reader = Box::new(MemoryReader { data, position: 0 });
+        // SAFETY: `reader` is boxed, so the pointer handed to the callbacks stays valid for
+        // as long as the returned input, which owns it and closes the demuxer first.
+        unsafe {
+            let buffer = ffi::av_malloc(AVIO_BUFFER_SIZE).cast::<u8>();
+            if buffer.is_null() {
+                return Err(out_of_memory());
+            }
+            let io = ffi::avio_alloc_context(
+                buffer,
+                AVIO_BUFFER_SIZE as c_int,
+                0,
+                ptr::addr_of_mut!(*reader).cast(),
+                Some(read_memory),
+                None,
+                Some(seek_memory),
+            );
+            if io.is_null() {
+                ffi::av_free(buffer.cast());
+                return Err(out_of_memory());
+            }
+            let io = AvioContext(io);
+
+            let mut format = ffi::avformat_alloc_context();
+            if format.is_null() {
+                return Err(out_of_memory());
+            }
+            (*format).pb = io.0;
+            (*format).flags |= ffi::AVFMT_FLAG_CUSTOM_IO as c_int;
+            // Frees `format` when it fails.
+            let ret = ffi::avformat_open_input(
+                &mut format,
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            if ret < 0 {
+                return Err(ffmpeg_err("open input failed", ffmpeg::Error::from(ret)));
+            }
+            let ret = ffi::avformat_find_stream_info(format, ptr::null_mut());
+            if ret < 0 {
+                ffi::avformat_close_input(&mut format);
+                return Err(ffmpeg_err("probe input failed", ffmpeg::Error::from(ret)));
+            }
+            Ok(Self {
+                context: ffmpeg::format::context::Input::wrap(format),
+                _io: io,
+                _reader: reader,
+            })
+        }
+    }
+}
+
+/// `read_packet` callback of [`MemoryInput`].
+unsafe extern "C" fn read_memory(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
+    // SAFETY: `opaque` is the input's `MemoryReader` and `buf` holds `buf_size` bytes.
+    let reader = unsafe { &mut *opaque.cast::<MemoryReader>() };
+    let remaining = &reader.data[reader.position..];
+    if remaining.is_empty() {
+        return ffi::AVERROR_EOF;
+    }
+    let len = remaining.len().min(buf_size.max(0) as usize);
+    unsafe { ptr::copy_nonoverlapping(remaining.as_ptr(), buf, len) };
+    reader.position += len;
+    len as c_int
+}
+
+/// `seek` callback of [`MemoryInput`]; also answers `AVSEEK_SIZE` queries.
+unsafe extern "C" fn seek_memory(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    // SAFETY: `opaque` is the input's `MemoryReader`.
+    let reader = unsafe { &mut *opaque.cast::<MemoryReader>() };
+    let len = reader.data.len() as i64;
+    let whence = whence & !(ffi::AVSEEK_FORCE as c_int);
+    let base = match whence {
+        w if w == ffi::AVSEEK_SIZE as c_int => return len,
+        libc::SEEK_SET => 0,
+        libc::SEEK_CUR => reader.position as i64,
+        libc::SEEK_END => len,
+        _ => return -1,
+    };
+    match base.checked_add(offset) {
+        Some(position) if (0..=len).contains(&position) => {
+            reader.position = position as usize;
+            position
+        }
+        _ => -1,
+    }
+}
+
 pub struct AudioExtractor {
     output_sample_rate: u32,
     output_channels: u8,
     ffmpeg_timeout: Duration,
     ffprobe_timeout: Duration,
     cancel_generation: Arc<AtomicU64>,
+    preprocessor: Option<AudioPreprocessor>,
 }
 
 impl Default for AudioExtractor {
@@ -64,6 +192,7 @@ impl Default for AudioExtractor {
             ffmpeg_timeout: Duration::from_secs(30),
             ffprobe_timeout: Duration::from_secs(10),
             cancel_generation: Arc::new(AtomicU64::new(0)),
+            preprocessor: None,
         }
     }
 }
@@ -87,6 +216,12 @@ impl AudioExtractor {
         self
     }
 
+    /// Run `preprocessor` on every extracted segment; a disabled chain is dropped.
+    pub fn with_preprocessor(mut self, preprocessor: AudioPreprocessor) -> Self {
+        self.preprocessor = preprocessor.is_enabled().then_some(preprocessor);
+        self
+    }
+
     pub fn cancel_inflight(&self) {
         self.cancel_generation.fetch_add(1, Ordering::Relaxed);
     }
@@ -98,7 +233,7 @@ impl AudioExtractor {
         Ok(())
     }
 
-    /// Extract audio segment from media file using ffmpeg libraries
+    /// Extract audio segment from media file and write it as a WAV file.
     pub fn extract_audio_segment<P: AsRef<Path>>(
         &self,
         input_path: P,
@@ -106,6 +241,17 @@ impl AudioExtractor {
         start_ms: u64,
         duration_ms: u64,
     ) -> Result<()> {
+        let pcm = self.extract_pcm(input_path, start_ms, duration_ms)?;
+        pcm.write_wav(output_path)
+    }
+
+    /// Extract audio segment from media file into memory using ffmpeg libraries
+    pub fn extract_pcm<P: AsRef<Path>>(
+        &self,
+        input_path: P,
+        start_ms: u64,
+        duration_ms: u64,
+    ) -> Result<PcmBuffer> {
         ensure_ffmpeg()?;
         let start_time = Instant::now();
         let run_generation = self.cancel_generation.load(Ordering::Relaxed);
@@ -114,17 +260,12 @@ impl AudioExtractor {
             .as_ref()
             .to_str()
             .ok_or_else(|| MpvSttError::InvalidPath("Invalid input path".to_string()))?;
-        let output_str = output_path
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| MpvSttError::InvalidPath("Invalid output path".to_string()))?;
 
         trace!(
-            "Extracting audio: {}ms-{}ms from {} -> {}",
+            "Extracting audio: {}ms-{}ms from {}",
             start_ms,
             start_ms + duration_ms,
-            input_str,
-            output_str
+            input_str
         );
 
         let cancel_generation = Arc::clone(&self.cancel_generation);
@@ -139,7 +280,18 @@ impl AudioExtractor {
 
         check_timeout(start_time, self.ffmpeg_timeout, "ffmpeg")?;
         self.check_cancel(run_generation)?;
+        self.decode_input(&mut ictx, start_ms, duration_ms, start_time, run_generation)
+    }
 
+    /// Decode the best audio stream of an opened input, resampled to the output format.
+    fn decode_input(
+        &self,
+        ictx: &mut ffmpeg::format::context::Input,
+        start_ms: u64,
+        duration_ms: u64,
+        start_time: Instant,
+        run_generation: u64,
+    ) -> Result<PcmBuffer> {
         let mut seeked = false;
         if start_ms > 0 {
             let position = (start_ms as i64).rescale((1, 1000), rescale::TIME_BASE);
@@ -178,14 +330,6 @@ impl AudioExtractor {
         )
         .map_err(|e| ffmpeg_err("resampler init failed", e))?;
 
-        let spec = hound::WavSpec {
-            channels: self.output_channels as u16,
-            sample_rate: self.output_sample_rate,
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
-        };
-        let mut writer = hound::WavWriter::create(output_path, spec)?;
-
         let start_frames = if seeked {
             0
         } else {
@@ -199,6 +343,9 @@ impl AudioExtractor {
 
         let mut skipped_frames = 0u64;
         let mut written_frames = 0u64;
+        let mut pcm: Vec<i16> = Vec::with_capacity(
+            (target_frames as usize).saturating_mul(self.output_channels as usize),
+        );
 
         let mut decoded = ffmpeg::frame::Audio::empty();
 
@@ -249,7 +396,7 @@ impl AudioExtractor {
 
                     let base = frame_idx * channels;
                     for ch in 0..channels {
-                        writer.write_sample(samples[base + ch])?;
+                        pcm.push(samples[base + ch]);
                     }
                     written_frames += 1;
                 }
@@ -303,7 +450,7 @@ impl AudioExtractor {
 
                     let base = frame_idx * channels;
                     for ch in 0..channels {
-                        writer.write_sample(samples[base + ch])?;
+                        pcm.push(samples[base + ch]);
                     }
                     written_frames += 1;
                 }
@@ -315,15 +462,38 @@ impl AudioExtractor {
         }
 
         self.check_cancel(run_generation)?;
-        writer.finalize()?;
         if target_frames > 0 && written_frames == 0 {
             return Err(MpvSttError::AudioExtractionFailed(
                 "no audio samples decoded".to_string(),
             ));
         }
 
+        if let Some(preprocessor) = self.preprocessor.as_ref() {
+            trace!("Preprocessing {} extracted samples", pcm.len());
+            preprocessor.process(
+                &mut pcm,
+                self.output_sample_rate,
+                self.output_channels as u16,
+            );
+        }
+
         debug!("Audio extraction completed successfully");
-        Ok(())
+        Ok(PcmBuffer::new(
+            pcm,
+            self.output_sample_rate,
+            self.output_channels as u16,
+        ))
+    }
+
+    /// Decode a whole media file held in memory (any container and codec ffmpeg
+    /// understands) and resample it to the configured output format.
+    pub fn decode_bytes(&self, data: &[u8]) -> Result<PcmBuffer> {
+        ensure_ffmpeg()?;
+        let start_time = Instant::now();
+        let run_generation = self.cancel_generation.load(Ordering::Relaxed);
+        trace!("Decoding {} bytes of media from memory", data.len());
+        let mut input = MemoryInput::open(data)?;
+        self.decode_input(&mut input.context, 0, 0, start_time, run_generation)
     }
 
     /// Check if audio file exists and is valid
@@ -53,6 +53,7 @@ pub struct Config {
     pub playback: PlaybackConfig,
     pub prefetch: PrefetchConfig,
     pub network: NetworkConfig,
+    pub audio: AudioConfig,
 }
 
 impl Default for Config {
@@ -65,6 +66,7 @@ impl Default for Config {
             playback: PlaybackConfig::default(),
             prefetch: PrefetchConfig::default(),
             network: NetworkConfig::default(),
+            audio: AudioConfig::default(),
         }
     }
 }
@@ -73,6 +75,7 @@ impl Default for Config {
 pub struct SttConfig {
     pub local_whisper: Option<SttLocalWhisperConfig>,
     pub remote_http: Option<SttRemoteHttpConfig>,
+    pub mock: Option<SttMockConfig>,
 }
 
 impl Default for SttConfig {
@@ -80,6 +83,7 @@ impl Default for SttConfig {
         Self {
             local_whisper: Some(SttLocalWhisperConfig::default()),
             remote_http: Some(SttRemoteHttpConfig::default()),
+            mock: Some(SttMockConfig::default()),
         }
     }
 }
@@ -114,9 +118,53 @@ pub struct SttRemoteHttpConfig {
     pub max_retry: usize,
     /// Enable Opus compression to reduce network payload size.
     pub use_opus: bool,
+    /// How Opus uploads are packaged; `framed` is for servers that predate Ogg support.
+    pub opus_container: OpusContainer,
     pub enable_encryption: bool,
     pub encryption_key: String,
     pub auth_secret: String,
+    pub transport: RemoteTransport,
+    /// Spoken language sent with each request; empty keeps the server's setting.
+    pub language: String,
+    /// Ask the server to translate to English.
+    pub translate: bool,
+    /// Initial prompt sent with each request, e.g. character names.
+    pub prompt: String,
+    /// Decoding temperature; unset keeps the server's default.
+    pub temperature: Option<f32>,
+    /// Request one subtitle per word.
+    pub word_timestamps: bool,
+    /// PEM CA certificate(s) trusted for an `https://` server instead of the built-in roots.
+    pub tls_ca_path: String,
+    /// SHA-256 fingerprint (hex) of the server certificate; when set, only that certificate
+    /// is accepted, which also works for self-signed ones.
+    pub tls_cert_sha256: String,
+    /// PEM client certificate for servers that require one (mTLS).
+    pub tls_client_cert_path: String,
+    /// PEM private key of `tls_client_cert_path`.
+    pub tls_client_key_path: String,
+}
+
+/// How audio reaches the remote server.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteTransport {
+    /// One `POST /transcribe` per chunk.
+    #[default]
+    Http,
+    /// A persistent `/live` session; each chunk is streamed and then flushed.
+    WebSocket,
+}
+
+/// Container of Opus-compressed `/transcribe` uploads.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OpusContainer {
+    /// Standard Ogg Opus file (`x-compression: ogg-opus`).
+    #[default]
+    Ogg,
+    /// Length-prefixed raw packets (`x-compression: opus`).
+    Framed,
 }
 
 impl Default for SttRemoteHttpConfig {
@@ -126,9 +174,48 @@ impl Default for SttRemoteHttpConfig {
             timeout_ms: 120_000,
             max_retry: 3,
             use_opus: true,
+            opus_container: OpusContainer::Ogg,
             enable_encryption: false,
             encryption_key: String::new(),
             auth_secret: String::new(),
+            transport: RemoteTransport::Http,
+            language: String::new(),
+            translate: false,
+            prompt: String::new(),
+            temperature: None,
+            word_timestamps: false,
+            tls_ca_path: String::new(),
+            tls_cert_sha256: String::new(),
+            tls_client_cert_path: String::new(),
+            tls_client_key_path: String::new(),
+        }
+    }
+}
+
+/// Settings for the `stt_mock` test backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SttMockConfig {
+    /// SRT file whose entries are returned for every chunk (timestamps relative to the chunk).
+    /// When empty, one segment is produced per run of loud audio instead.
+    pub fixture_path: String,
+    /// Artificial per-call latency; cancellation interrupts it.
+    pub delay_ms: u64,
+    /// Fail every Nth call with an STT error (0 = never).
+    pub fail_every: u32,
+    /// Frames louder than this (dBFS) count as speech when no fixture is set.
+    pub energy_threshold_db: f32,
+    /// Language reported in transcripts.
+    pub language: String,
+}
+
+impl Default for SttMockConfig {
+    fn default() -> Self {
+        Self {
+            fixture_path: String::new(),
+            delay_ms: 0,
+            fail_every: 0,
+            energy_threshold_db: -40.0,
+            language: "en".to_string(),
         }
     }
 }
@@ -227,6 +314,37 @@ impl Default for NetworkConfig {
     }
 }
 
+/// Optional preprocessing applied to extracted audio before transcription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioConfig {
+    /// Normalize loudness towards `target_loudness_db` (RMS of non-silent audio, dBFS).
+    pub normalize: bool,
+    pub target_loudness_db: f32,
+    /// Upper bound for the gain normalization may apply, in dB.
+    pub max_gain_db: f32,
+    /// High-pass cutoff in Hz to remove rumble and hum; 0 disables the filter.
+    pub highpass_hz: f32,
+    /// Boost (or cut, if negative) around the 1-4 kHz dialogue band, in dB; 0 disables it.
+    pub dialogue_boost_db: f32,
+    /// Attenuate stretches quieter than `noise_gate_db` (dBFS).
+    pub noise_gate: bool,
+    pub noise_gate_db: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            normalize: false,
+            target_loudness_db: -20.0,
+            max_gain_db: 20.0,
+            highpass_hz: 0.0,
+            dialogue_boost_db: 0.0,
+            noise_gate: false,
+            noise_gate_db: -50.0,
+        }
+    }
+}
+
 impl Config {
     pub fn default_config_path() -> Option<PathBuf> {
         let base = BaseDirs::new()?;
@@ -1,10 +1,22 @@
 use crate::audio::AudioExtractor;
 #[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
 use crate::stt::LocalModelConfig;
-#[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
+#[cfg(feature = "stt_mock")]
+use crate::stt::MockSttConfig;
+#[cfg(any(
+    feature = "stt_local_cpu",
+    feature = "stt_local_cuda",
+    feature = "stt_mock"
+))]
 use crate::stt::{SttBackend, SttRunner};
 use crate::translate::{Translator, TranslatorConfig};
 use log::{debug, error};
+#[cfg(any(
+    feature = "stt_local_cpu",
+    feature = "stt_local_cuda",
+    feature = "stt_mock"
+))]
+use mpv_stt_common::PcmBuffer;
 use mpv_stt_srt;
 use parking_lot::Mutex;
 use std::ffi::{CStr, CString};
@@ -13,7 +25,11 @@ use std::sync::OnceLock;
 
 // Global state for configuration
 static AUDIO_EXTRACTOR: OnceLock<Mutex<AudioExtractor>> = OnceLock::new();
-#[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
+#[cfg(any(
+    feature = "stt_local_cpu",
+    feature = "stt_local_cuda",
+    feature = "stt_mock"
+))]
 static WHISPER_RUNNER: OnceLock<Mutex<Option<SttRunner>>> = OnceLock::new();
 static TRANSLATOR: OnceLock<Mutex<Option<Translator>>> = OnceLock::new();
 
@@ -21,7 +37,11 @@ fn audio_extractor() -> &'static Mutex<AudioExtractor> {
     AUDIO_EXTRACTOR.get_or_init(|| Mutex::new(AudioExtractor::default()))
 }
 
-#[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
+#[cfg(any(
+    feature = "stt_local_cpu",
+    feature = "stt_local_cuda",
+    feature = "stt_mock"
+))]
 fn whisper_runner() -> &'static Mutex<Option<SttRunner>> {
     WHISPER_RUNNER.get_or_init(|| Mutex::new(None))
 }
@@ -78,6 +98,30 @@ pub extern "C" fn mpv_stt_plugin_rs_stt_init(
     }
 }
 
+/// Initialize the mock speech-to-text backend (test builds only).
+///
+/// `fixture_path` may be null or empty to derive segments from audio energy.
+#[cfg(feature = "stt_mock")]
+#[unsafe(no_mangle)]
+pub extern "C" fn mpv_stt_plugin_rs_stt_init_mock(
+    fixture_path: *const c_char,
+    delay_ms: u64,
+    fail_every: u32,
+) -> i32 {
+    unsafe {
+        let config = MockSttConfig {
+            fixture_path: c_str_to_string(fixture_path).unwrap_or_default(),
+            delay_ms,
+            fail_every,
+            ..Default::default()
+        };
+
+        *whisper_runner().lock() = Some(SttRunner::new(config));
+        debug!("STT (mock) initialized via FFI");
+        0
+    }
+}
+
 /// Initialize Translator configuration (builtin Google Translate only)
 #[unsafe(no_mangle)]
 pub extern "C" fn translator_init(from_lang: *const c_char, to_lang: *const c_char) -> i32 {
@@ -124,7 +168,11 @@ pub extern "C" fn extract_audio(
 }
 
 /// Run Whisper transcription
-#[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
+#[cfg(any(
+    feature = "stt_local_cpu",
+    feature = "stt_local_cuda",
+    feature = "stt_mock"
+))]
 #[unsafe(no_mangle)]
 pub extern "C" fn stt_transcribe(
     audio_path: *const c_char,
@@ -151,7 +199,9 @@ pub extern "C" fn stt_transcribe(
             }
         };
 
-        match runner.transcribe(&audio, &output, duration_ms) {
+        let result = PcmBuffer::read_wav(&audio)
+            .and_then(|pcm| runner.transcribe_to_file(&pcm, output.as_str(), duration_ms));
+        match result {
             Ok(_) => 0,
             Err(e) => {
                 error!("STT transcription error: {}", e);
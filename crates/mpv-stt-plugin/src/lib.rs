@@ -2,6 +2,7 @@ pub mod audio;
 pub mod config;
 pub mod ffi;
 pub mod plugin;
+pub mod preprocess;
 pub mod process;
 pub mod stt;
 pub mod subtitle_manager;
@@ -12,10 +13,15 @@ pub use config::{Config, InferenceDevice};
 pub use mpv_stt_common::{MpvSttError, Result};
 pub use mpv_stt_crypto::{AuthToken, EncryptionKey};
 pub use mpv_stt_srt::{SrtFile, SubtitleEntry};
+pub use preprocess::AudioPreprocessor;
 #[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
 pub use stt::LocalModelConfig;
+#[cfg(feature = "stt_mock")]
+pub use stt::MockSttConfig;
 #[cfg(feature = "stt_remote_http")]
 pub use stt::RemoteSttConfig;
-pub use stt::{ActiveBackend as SttActiveBackend, SttBackend, SttRunner};
+pub use stt::{
+    ActiveBackend as SttActiveBackend, SttBackend, SttCancelHandle, SttRunner, SttRunnerConfig,
+};
 pub use subtitle_manager::SubtitleManager;
 pub use translate::{Translator, TranslatorConfig};
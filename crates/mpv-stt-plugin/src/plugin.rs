@@ -13,19 +13,22 @@ use std::ffi::CString;
 
 use crate::audio::AudioExtractor;
 use crate::config::Config;
+use crate::preprocess::AudioPreprocessor;
 #[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
 use crate::stt::LocalModelConfig;
+#[cfg(feature = "stt_mock")]
+use crate::stt::MockSttConfig;
 #[cfg(feature = "stt_remote_http")]
 use crate::stt::RemoteSttConfig;
 use crate::stt::{SttBackend, SttRunner};
 use crate::subtitle_manager::SubtitleManager;
 use crate::translate::{AsyncTranslationQueue, TranslationTask, TranslatorConfig};
-use mpv_stt_common::MpvSttError;
+use mpv_stt_common::{MpvSttError, PcmBuffer};
+use mpv_stt_protocol::JobPriority;
 use mpv_stt_srt::{self, SrtFile};
 
 struct TempPaths {
     _dir: TempDir,
-    tmp_wav: PathBuf,
     tmp_sub: PathBuf,
     tmp_cache: PathBuf,
 }
@@ -38,25 +41,15 @@ impl TempPaths {
             .expect("failed to create temp dir");
 
         Self {
-            tmp_wav: dir.path().join("audio.wav"),
-            // `tmp_sub` is a prefix; intermediate files are derived via `format!("{}_append...", tmp_sub.display())`
-            // and the main subtitle file is `tmp_sub.with_extension("srt")`.
+            // The main subtitle file is `tmp_sub.with_extension("srt")`.
             tmp_sub: dir.path().join("subs"),
             tmp_cache: dir.path().join("cache.mkv"),
             _dir: dir,
         }
     }
 
-    fn cleanup_intermediate_subs(&self) {
-        let _ = std::fs::remove_file(format!("{}_append.srt", self.tmp_sub.display()));
-        let _ = std::fs::remove_file(format!("{}_append_offset.srt", self.tmp_sub.display()));
-        let _ = std::fs::remove_file(format!("{}_append_offset_bi.srt", self.tmp_sub.display()));
-    }
-
     fn cleanup(&self) {
-        let _ = std::fs::remove_file(&self.tmp_wav);
         let _ = std::fs::remove_file(self.tmp_sub.with_extension("srt"));
-        self.cleanup_intermediate_subs();
         let _ = std::fs::remove_file(&self.tmp_cache);
     }
 }
@@ -90,6 +83,39 @@ enum ProcessingMode {
     },
 }
 
+/// The parts of the mpv client API the plugin uses; tests drive [`PluginState`] with a
+/// scripted player instead of a running mpv.
+trait Player {
+    /// Run an mpv command; false if mpv rejected it.
+    fn command(&mut self, args: &[&str]) -> bool;
+    fn get_f64(&mut self, name: &str) -> Option<f64>;
+    fn get_string(&mut self, name: &str) -> Option<String>;
+    fn set_flag(&mut self, name: &str, value: bool);
+    fn set_int(&mut self, name: &str, value: i64);
+}
+
+impl Player for Handle {
+    fn command(&mut self, args: &[&str]) -> bool {
+        Handle::command(self, args).is_ok()
+    }
+
+    fn get_f64(&mut self, name: &str) -> Option<f64> {
+        self.get_property::<f64>(name).ok()
+    }
+
+    fn get_string(&mut self, name: &str) -> Option<String> {
+        self.get_property::<String>(name).ok()
+    }
+
+    fn set_flag(&mut self, name: &str, value: bool) {
+        let _ = self.set_property(name, value);
+    }
+
+    fn set_int(&mut self, name: &str, value: i64) {
+        let _ = self.set_property(name, value);
+    }
+}
+
 struct PluginState {
     config: Config,
     paths: TempPaths,
@@ -118,7 +144,8 @@ impl PluginState {
         let chunk_dur = config.chunk.local_ms;
         let audio_extractor = AudioExtractor::default()
             .with_ffmpeg_timeout(config.timeout.ffmpeg_ms)
-            .with_ffprobe_timeout(config.timeout.ffprobe_ms);
+            .with_ffprobe_timeout(config.timeout.ffprobe_ms)
+            .with_preprocessor(AudioPreprocessor::new(config.audio.clone()));
 
         // Initialize speech-to-text backend (selected at compile time)
         #[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
@@ -149,13 +176,34 @@ impl PluginState {
                 timeout_ms: cfg.timeout_ms,
                 max_retry: cfg.max_retry,
                 use_opus: cfg.use_opus,
+                opus_container: cfg.opus_container,
                 enable_encryption: cfg.enable_encryption,
                 encryption_key: cfg.encryption_key.clone(),
                 auth_secret: cfg.auth_secret.clone(),
+                transport: cfg.transport,
+                language: cfg.language.clone(),
+                translate: cfg.translate,
+                prompt: cfg.prompt.clone(),
+                temperature: cfg.temperature,
+                word_timestamps: cfg.word_timestamps,
+                tls_ca_path: cfg.tls_ca_path.clone(),
+                tls_cert_sha256: cfg.tls_cert_sha256.clone(),
+                tls_client_cert_path: cfg.tls_client_cert_path.clone(),
+                tls_client_key_path: cfg.tls_client_key_path.clone(),
             };
             SttRunner::new(remote_config).expect("Failed to create remote STT client")
         };
 
+        #[cfg(feature = "stt_mock")]
+        let stt_runner = {
+            let cfg: MockSttConfig = config
+                .stt
+                .mock
+                .clone()
+                .expect("Missing [stt.mock] config for mock backend");
+            SttRunner::new(cfg)
+        };
+
         // Initialize async translation queue (always enabled)
         let async_translation_queue = Some(AsyncTranslationQueue::new(
             Self::build_translator_config(&config),
@@ -271,7 +319,7 @@ impl PluginState {
         }
     }
 
-    fn toggle_stt(&mut self, client: &mut Handle) {
+    fn toggle_stt(&mut self, client: &mut dyn Player) {
         if self.running {
             info!("Disabling STT");
             self.running = false;
@@ -285,10 +333,10 @@ impl PluginState {
         }
     }
 
-    fn start_transcription(&mut self, client: &mut Handle) {
+    fn start_transcription(&mut self, client: &mut dyn Player) {
         debug!("Starting transcription");
         // Get current position
-        let time_pos: f64 = client.get_property("time-pos").unwrap_or(0.0);
+        let time_pos = client.get_f64("time-pos").unwrap_or(0.0);
         self.current_pos_ms = (time_pos * 1000.0) as u64;
         self.last_playback_pos_ms = Some(self.current_pos_ms);
         trace!("Current playback position: {}ms", self.current_pos_ms);
@@ -302,12 +350,12 @@ impl PluginState {
             let _ = client.command(&["show-text", "STT: Starting network stream transcription..."]);
 
             // Enable caching
-            let _ = client.set_property("cache", true);
+            client.set_flag("cache", true);
 
             // Set demuxer max bytes if configured (for better lookahead caching)
             if let Some(max_bytes) = self.config.network.demuxer_max_bytes {
                 debug!("Setting demuxer-max-bytes to {} bytes", max_bytes);
-                let _ = client.set_property("demuxer-max-bytes", max_bytes);
+                client.set_int("demuxer-max-bytes", max_bytes);
             }
 
             self.mode = Some(ProcessingMode::Network);
@@ -356,10 +404,10 @@ impl PluginState {
         } else {
             // Local file mode
             debug!("Detected local file, entering local mode");
-            let media_path: Result<String, _> = client.get_property("path");
-            let duration: Result<f64, _> = client.get_property("duration");
+            let media_path = client.get_string("path");
+            let duration = client.get_f64("duration");
 
-            if let (Ok(path), Ok(dur)) = (media_path, duration) {
+            if let (Some(path), Some(dur)) = (media_path, duration) {
                 let file_length_ms = (dur * 1000.0) as u64;
                 trace!("Media file: {}, duration: {}ms", path, file_length_ms);
 
@@ -411,7 +459,7 @@ impl PluginState {
     }
 
     /// Main processing loop - called on each event loop iteration
-    fn tick(&mut self, client: &mut Handle) {
+    fn tick(&mut self, client: &mut dyn Player) {
         if !self.running || self.shutting_down {
             return;
         }
@@ -432,7 +480,7 @@ impl PluginState {
         }
     }
 
-    fn tick_network(&mut self, client: &mut Handle) {
+    fn tick_network(&mut self, client: &mut dyn Player) {
         let subtitle_path = self
             .network_cache
             .as_ref()
@@ -448,7 +496,7 @@ impl PluginState {
         self.process_translation_results(client, subtitle_path.as_deref());
 
         // Get cache end time
-        let cache_end_sec: Option<f64> = client.get_property("demuxer-cache-time").ok();
+        let cache_end_sec = client.get_f64("demuxer-cache-time");
         if cache_end_sec.is_none() {
             trace!("Cache not ready yet");
             return; // Cache not ready yet
@@ -565,7 +613,7 @@ impl PluginState {
 
     fn tick_local(
         &mut self,
-        client: &mut Handle,
+        client: &mut dyn Player,
         media_path: &str,
         file_length_ms: u64,
         subtitle_path: &Path,
@@ -669,8 +717,8 @@ impl PluginState {
         }
     }
 
-    fn check_seek(&mut self, client: &mut Handle) -> bool {
-        let playback_pos: Option<f64> = client.get_property("time-pos").ok();
+    fn check_seek(&mut self, client: &mut dyn Player) -> bool {
+        let playback_pos = client.get_f64("time-pos");
         if let Some(pos) = playback_pos {
             let playback_pos_ms = (pos * 1000.0) as u64;
             let now = Instant::now();
@@ -763,7 +811,7 @@ impl PluginState {
     /// Process one chunk from network cache
     fn process_chunk(
         &mut self,
-        client: &mut Handle,
+        client: &mut dyn Player,
         chunk_ms: u64,
         subtitle_path: Option<&Path>,
     ) -> bool {
@@ -772,46 +820,47 @@ impl PluginState {
         let end_sec = (self.current_pos_ms + chunk_ms) as f64 / 1000.0;
         trace!("Dumping cache from {}s to {}s", start_sec, end_sec);
 
-        let dump_result = client.command(&[
+        let dumped = client.command(&[
             "dump-cache",
             &start_sec.to_string(),
             &end_sec.to_string(),
             self.paths.tmp_cache.to_str().unwrap(),
         ]);
 
-        if dump_result.is_err() {
+        if !dumped {
             error!("dump-cache failed");
             return false;
         }
 
         // Extract audio from cache
-        let wav_ms = chunk_ms;
-        if !self.create_wav(self.paths.tmp_cache.to_str().unwrap(), 0, wav_ms) {
+        let Some(pcm) = self.extract_pcm(self.paths.tmp_cache.to_str().unwrap(), 0, chunk_ms)
+        else {
             return false;
-        }
+        };
 
-        self.transcribe_and_update(client, subtitle_path, chunk_ms)
+        self.transcribe_and_update(client, &pcm, subtitle_path, chunk_ms)
     }
 
     /// Process one chunk from local file
     fn process_chunk_local(
         &mut self,
-        client: &mut Handle,
+        client: &mut dyn Player,
         media_path: &str,
         subtitle_path: &Path,
     ) -> bool {
         // Extract audio directly from local file
-        if !self.create_wav(media_path, self.current_pos_ms, self.chunk_dur) {
+        let Some(pcm) = self.extract_pcm(media_path, self.current_pos_ms, self.chunk_dur) else {
             return false;
-        }
+        };
 
-        self.transcribe_and_update(client, Some(subtitle_path), self.chunk_dur)
+        self.transcribe_and_update(client, &pcm, Some(subtitle_path), self.chunk_dur)
     }
 
     /// Common transcription and subtitle update logic
     fn transcribe_and_update(
         &mut self,
-        client: &mut Handle,
+        client: &mut dyn Player,
+        pcm: &PcmBuffer,
         subtitle_path: Option<&Path>,
         chunk_ms: u64,
     ) -> bool {
@@ -819,66 +868,55 @@ impl PluginState {
             return false;
         }
 
-        let tmp_sub_prefix = self.paths.tmp_sub.to_string_lossy().to_string();
-        let append_path = format!("{}_append", &tmp_sub_prefix);
         let main_srt = subtitle_path
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| self.paths.tmp_sub.with_extension("srt"));
 
+        // Only the chunk under the playhead is urgent; lookahead chunks may be preempted.
+        let priority = match self.last_playback_pos_ms {
+            Some(playback_pos_ms) if self.current_pos_ms > playback_pos_ms => JobPriority::Prefetch,
+            _ => JobPriority::Realtime,
+        };
+        self.stt_runner.set_priority(priority);
+
         trace!("Starting STT transcription for current chunk");
         // Run STT transcription
-        if let Err(e) = self.stt_runner.transcribe(
-            self.paths.tmp_wav.to_str().unwrap(),
-            append_path.as_str(),
-            chunk_ms,
-        ) {
-            if matches!(e, MpvSttError::SttCancelled) {
-                debug!("STT transcription cancelled");
-            } else {
-                error!("STT transcription failed: {}", e);
-                let msg = format!("STT failed: {e}");
-                let _ = client.command(&["show-text", &msg, "4000"]);
-                // Hard stop: backend failed, keep plugin idle as requested.
-                self.running = false;
-                self.cleanup(client);
+        let mut transcript = match self.stt_runner.transcribe(pcm, chunk_ms) {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                if matches!(e, MpvSttError::SttCancelled) {
+                    debug!("STT transcription cancelled");
+                } else {
+                    error!("STT transcription failed: {}", e);
+                    let msg = format!("STT failed: {e}");
+                    let _ = client.command(&["show-text", &msg, "4000"]);
+                    // Hard stop: backend failed, keep plugin idle as requested.
+                    self.running = false;
+                    self.cleanup(client);
+                }
+                return false;
             }
-            return false;
-        }
+        };
         self.show_device_notice(client);
         if self.check_seek(client) {
             debug!("Seek detected during transcription; dropping interim results");
-            self.paths.cleanup_intermediate_subs();
             return false;
         }
 
-        // Offset timestamps
-        let append_srt = format!("{}.srt", append_path);
-        let offset_srt = format!("{}_append_offset.srt", &tmp_sub_prefix);
-
-        if let Ok(meta) = std::fs::metadata(&append_srt) {
-            if meta.len() == 0 {
-                info!(
-                    "Chunk starting at {}ms produced no subtitles; skipping merge",
-                    self.current_pos_ms
-                );
-                self.mark_chunk_processed(self.current_pos_ms);
-                self.paths.cleanup_intermediate_subs();
-                return true;
-            }
+        if transcript.is_empty() {
+            info!(
+                "Chunk starting at {}ms produced no subtitles; skipping merge",
+                self.current_pos_ms
+            );
+            self.mark_chunk_processed(self.current_pos_ms);
+            return true;
         }
 
-        if let Err(e) =
-            mpv_stt_srt::offset_srt_file(&append_srt, &offset_srt, self.current_pos_ms as i64)
-        {
-            error!("SRT offset failed: {}", e);
-            return false;
-        }
+        // Offset timestamps
+        transcript.offset(self.current_pos_ms);
+        let srt_file = SrtFile::from(&transcript);
 
         // Add original subtitles first so recognition updates immediately
-        let srt_file = match SrtFile::parse(&offset_srt) {
-            Ok(srt) => srt,
-            Err(_) => return false,
-        };
         self.subtitle_manager.add_from_srt(&srt_file);
         self.mark_chunk_processed(self.current_pos_ms);
 
@@ -926,10 +964,6 @@ impl PluginState {
             );
         }
 
-        // Keep only the main subtitle file on disk during playback to reduce clutter.
-        // The `_append*` files are per-chunk intermediates and will be regenerated each chunk.
-        self.paths.cleanup_intermediate_subs();
-
         debug!(
             "Processed chunk at {}ms, total subs: {}",
             self.current_pos_ms,
@@ -938,7 +972,7 @@ impl PluginState {
         true
     }
 
-    fn show_device_notice(&mut self, client: &mut Handle) {
+    fn show_device_notice(&mut self, client: &mut dyn Player) {
         let Some(notice) = self.stt_runner.take_device_notice() else {
             return;
         };
@@ -959,7 +993,11 @@ impl PluginState {
     }
 
     /// Process completed translation results from async queue
-    fn process_translation_results(&mut self, client: &mut Handle, subtitle_path: Option<&Path>) {
+    fn process_translation_results(
+        &mut self,
+        client: &mut dyn Player,
+        subtitle_path: Option<&Path>,
+    ) {
         if let Some(ref queue) = self.async_translation_queue {
             let results = queue.try_recv_results();
             if !results.is_empty() {
@@ -988,7 +1026,7 @@ impl PluginState {
         mpv_stt_srt::Timestamp::convert_to_milliseconds(h, m, s, ms)
     }
 
-    fn save_subs(&mut self, client: &mut Handle, main_srt: &Path) -> bool {
+    fn save_subs(&mut self, client: &mut dyn Player, main_srt: &Path) -> bool {
         if let Err(e) = self.subtitle_manager.save_to_file(main_srt) {
             error!("Failed to save subtitles: {}", e);
             return false;
@@ -1000,28 +1038,25 @@ impl PluginState {
         true
     }
 
-    fn create_wav(&self, media_path: &str, start_ms: u64, duration_ms: u64) -> bool {
-        let result = self.audio_extractor.extract_audio_segment(
-            media_path,
-            self.paths.tmp_wav.to_str().unwrap(),
-            start_ms,
-            duration_ms,
-        );
+    fn extract_pcm(&self, media_path: &str, start_ms: u64, duration_ms: u64) -> Option<PcmBuffer> {
+        let result = self
+            .audio_extractor
+            .extract_pcm(media_path, start_ms, duration_ms);
 
         match result {
-            Ok(_) => true,
+            Ok(pcm) => Some(pcm),
             Err(MpvSttError::AudioExtractionCancelled) => {
                 debug!("Audio extraction cancelled");
-                false
+                None
             }
             Err(e) => {
                 error!("Audio extraction failed: {}", e);
-                false
+                None
             }
         }
     }
 
-    fn cleanup(&mut self, _client: &mut Handle) {
+    fn cleanup(&mut self, _client: &mut dyn Player) {
         debug!("Cleaning up temporary files and state");
 
         // Set shutting down flag to stop any ongoing processing
@@ -1066,13 +1101,13 @@ impl PluginState {
         self.processed_chunks.insert(start_ms);
     }
 
-    fn media_id_for_cache(client: &mut Handle) -> Option<String> {
-        if let Ok(id) = client.get_property::<String>("stream-open-filename") {
+    fn media_id_for_cache(client: &mut dyn Player) -> Option<String> {
+        if let Some(id) = client.get_string("stream-open-filename") {
             if !id.trim().is_empty() {
                 return Some(id);
             }
         }
-        if let Ok(id) = client.get_property::<String>("path") {
+        if let Some(id) = client.get_string("path") {
             if !id.trim().is_empty() {
                 return Some(id);
             }
@@ -1210,9 +1245,9 @@ impl PluginState {
     }
 
     /// Detect if current media is a network stream
-    fn detect_network_stream(&self, client: &mut Handle) -> bool {
+    fn detect_network_stream(&self, client: &mut dyn Player) -> bool {
         // Method 1: Check path/filename for http/https URLs
-        if let Ok(path) = client.get_property::<String>("path") {
+        if let Some(path) = client.get_string("path") {
             debug!("Checking path for network stream: {}", path);
             if path.starts_with("http://") || path.starts_with("https://") {
                 debug!("Detected network stream by URL prefix");
@@ -1221,7 +1256,7 @@ impl PluginState {
         }
 
         // Method 2: Check stream-open-filename
-        if let Ok(filename) = client.get_property::<String>("stream-open-filename") {
+        if let Some(filename) = client.get_string("stream-open-filename") {
             debug!("Checking stream-open-filename: {}", filename);
             if filename.starts_with("http://") || filename.starts_with("https://") {
                 debug!("Detected network stream by stream-open-filename");
@@ -1230,7 +1265,7 @@ impl PluginState {
         }
 
         // Method 3: Check demuxer-via-network property
-        if let Ok(via_network) = client.get_property::<String>("demuxer-via-network") {
+        if let Some(via_network) = client.get_string("demuxer-via-network") {
             debug!("demuxer-via-network: {}", via_network);
             if via_network == "yes" {
                 debug!("Detected network stream by demuxer-via-network");
@@ -1496,3 +1531,241 @@ fn init_logger() {
         .try_init();
     }
 }
+
+#[cfg(all(test, feature = "stt_mock"))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    /// Stands in for mpv playing a local file; playback only moves when the test moves it.
+    struct ScriptedPlayer {
+        path: String,
+        duration_ms: u64,
+        position_ms: Arc<AtomicU64>,
+        commands: Vec<String>,
+    }
+
+    impl ScriptedPlayer {
+        fn new(path: &Path, duration_ms: u64) -> Self {
+            Self {
+                path: path.to_string_lossy().into_owned(),
+                duration_ms,
+                position_ms: Arc::new(AtomicU64::new(0)),
+                commands: Vec::new(),
+            }
+        }
+
+        fn play_at(&self, position_ms: u64) {
+            self.position_ms.store(position_ms, Ordering::Relaxed);
+        }
+
+        fn showed(&self, text: &str) -> bool {
+            self.commands
+                .iter()
+                .any(|command| command.starts_with("show-text") && command.contains(text))
+        }
+    }
+
+    impl Player for ScriptedPlayer {
+        fn command(&mut self, args: &[&str]) -> bool {
+            self.commands.push(args.join(" "));
+            true
+        }
+
+        fn get_f64(&mut self, name: &str) -> Option<f64> {
+            match name {
+                "time-pos" => Some(self.position_ms.load(Ordering::Relaxed) as f64 / 1000.0),
+                "duration" => Some(self.duration_ms as f64 / 1000.0),
+                _ => None,
+            }
+        }
+
+        fn get_string(&mut self, name: &str) -> Option<String> {
+            (name == "path").then(|| self.path.clone())
+        }
+
+        fn set_flag(&mut self, _name: &str, _value: bool) {}
+
+        fn set_int(&mut self, _name: &str, _value: i64) {}
+    }
+
+    /// A `ms` long 500 Hz tone, which the mock backend hears as one utterance per chunk.
+    fn tone_file(dir: &Path, ms: u64) -> PathBuf {
+        let samples = (0..ms * 16)
+            .map(|i| if i % 32 < 16 { 8_000 } else { -8_000 })
+            .collect();
+        let path = dir.join("talk.wav");
+        let wav = PcmBuffer::new(samples, 16_000, 1).to_wav_bytes().unwrap();
+        fs::write(&path, wav).unwrap();
+        path
+    }
+
+    /// A running plugin with 10 s chunks and two chunks of look-ahead.
+    fn test_state(mock: MockSttConfig) -> PluginState {
+        let mut config = Config::default();
+        config.chunk.local_ms = 10_000;
+        config.prefetch.lookahead_chunks = 2;
+        config.playback.show_progress = false;
+        config.stt.mock = Some(mock);
+        let mut state = PluginState::new(config);
+        // Translation would call out to an online service.
+        if let Some(mut queue) = state.async_translation_queue.take() {
+            queue.force_shutdown();
+        }
+        state.running = true;
+        state
+    }
+
+    fn chunk_starts(state: &PluginState) -> Vec<u64> {
+        let mut starts: Vec<u64> = state.processed_chunks.iter().copied().collect();
+        starts.sort_unstable();
+        starts
+    }
+
+    fn subtitle_starts(state: &PluginState) -> Vec<u32> {
+        state
+            .subtitle_manager
+            .entries_in_range(0, u32::MAX)
+            .into_iter()
+            .map(|(start_ms, _)| start_ms)
+            .collect()
+    }
+
+    #[test]
+    fn test_local_chunks_run_ahead_of_playback_up_to_the_lookahead() {
+        let dir = tempfile::tempdir().unwrap();
+        let media = tone_file(dir.path(), 25_000);
+        let mut player = ScriptedPlayer::new(&media, 25_000);
+        let mut state = test_state(MockSttConfig::default());
+
+        state.start_transcription(&mut player);
+        state.tick(&mut player);
+        state.tick(&mut player);
+        assert_eq!(chunk_starts(&state), vec![0, 10_000]);
+        // The last chunk would end more than two chunks past the playhead.
+        state.tick(&mut player);
+        assert_eq!(chunk_starts(&state), vec![0, 10_000]);
+
+        player.play_at(6_000);
+        state.tick(&mut player);
+        assert_eq!(chunk_starts(&state), vec![0, 10_000, 20_000]);
+        assert_eq!(subtitle_starts(&state), vec![0, 10_000, 20_000]);
+
+        state.tick(&mut player);
+        assert!(!state.running);
+        assert!(player.showed("STT: Saved subtitles"));
+        let srt = SrtFile::parse(dir.path().join("talk.srt")).unwrap();
+        let starts: Vec<u64> = srt.segments().iter().map(|s| s.start_ms).collect();
+        assert_eq!(starts, vec![0, 10_000, 20_000]);
+    }
+
+    #[test]
+    fn test_seeks_move_the_cursor_and_keep_finished_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let media = tone_file(dir.path(), 60_000);
+        let mut player = ScriptedPlayer::new(&media, 60_000);
+        let mut state = test_state(MockSttConfig::default());
+
+        state.start_transcription(&mut player);
+        state.tick(&mut player);
+        state.tick(&mut player);
+        assert_eq!(chunk_starts(&state), vec![0, 10_000]);
+
+        player.play_at(45_000);
+        state.tick(&mut player);
+        assert_eq!(state.current_pos_ms, 40_000);
+        assert!(player.showed("STT: Jumped to 00:00:40.000"));
+        state.tick(&mut player);
+        state.tick(&mut player);
+        assert_eq!(chunk_starts(&state), vec![0, 10_000, 40_000, 50_000]);
+
+        // Seeking back resumes at the first chunk not transcribed yet.
+        player.play_at(12_000);
+        state.tick(&mut player);
+        assert_eq!(state.current_pos_ms, 10_000);
+        assert!(player.showed("STT: Seeked back to 00:00:10.000"));
+        state.tick(&mut player);
+        state.tick(&mut player);
+        assert_eq!(
+            chunk_starts(&state),
+            vec![0, 10_000, 20_000, 40_000, 50_000]
+        );
+        assert_eq!(
+            subtitle_starts(&state),
+            vec![0, 10_000, 20_000, 40_000, 50_000]
+        );
+    }
+
+    #[test]
+    fn test_seek_during_transcription_drops_the_stale_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let media = tone_file(dir.path(), 60_000);
+        let mut player = ScriptedPlayer::new(&media, 60_000);
+        let mut state = test_state(MockSttConfig {
+            delay_ms: 1_000,
+            ..Default::default()
+        });
+
+        let position_ms = Arc::clone(&player.position_ms);
+        let seek = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            position_ms.store(30_000, Ordering::Relaxed);
+        });
+        state.start_transcription(&mut player);
+        seek.join().unwrap();
+
+        assert!(chunk_starts(&state).is_empty());
+        assert_eq!(state.subtitle_manager.len(), 0);
+        assert_eq!(state.current_pos_ms, 30_000);
+        state.tick(&mut player);
+        assert_eq!(chunk_starts(&state), vec![30_000]);
+    }
+
+    #[test]
+    fn test_cancelled_transcription_keeps_the_plugin_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let media = tone_file(dir.path(), 25_000);
+        let mut player = ScriptedPlayer::new(&media, 25_000);
+        let mut state = test_state(MockSttConfig {
+            delay_ms: 10_000,
+            ..Default::default()
+        });
+
+        let cancel = state.stt_runner.cancel_handle();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            cancel.cancel();
+        });
+        let start = Instant::now();
+        state.start_transcription(&mut player);
+        canceller.join().unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(state.running);
+        assert!(chunk_starts(&state).is_empty());
+        assert!(!player.showed("STT failed"));
+
+        state.toggle_stt(&mut player);
+        assert!(!state.running);
+        assert!(state.mode.is_none());
+        assert!(player.showed("STT: Off"));
+    }
+
+    #[test]
+    fn test_backend_failure_stops_the_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let media = tone_file(dir.path(), 25_000);
+        let mut player = ScriptedPlayer::new(&media, 25_000);
+        let mut state = test_state(MockSttConfig {
+            fail_every: 1,
+            ..Default::default()
+        });
+
+        state.start_transcription(&mut player);
+        assert!(!state.running);
+        assert!(player.showed("STT failed"));
+        assert!(chunk_starts(&state).is_empty());
+    }
+}
@@ -0,0 +1,326 @@
+use crate::config::AudioConfig;
+use log::trace;
+use std::f32::consts::PI;
+
+// Speech intelligibility lives roughly in 1-4 kHz; emphasise around the geometric centre.
+const DIALOGUE_CENTER_HZ: f32 = 2_000.0;
+const DIALOGUE_Q: f32 = 0.7;
+const HIGHPASS_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+const GATE_FRAME_MS: u32 = 10;
+const GATE_FLOOR_DB: f32 = -30.0;
+const GATE_ATTACK_MS: f32 = 5.0;
+const GATE_RELEASE_MS: f32 = 80.0;
+const GATE_HOLD_MS: u32 = 100;
+
+// Frames quieter than this are ignored when measuring loudness so silence does not drag
+// the average down and cause excessive gain.
+const LOUDNESS_SILENCE_DB: f32 = -60.0;
+const PEAK_CEILING: f32 = 0.99;
+
+/// Optional DSP chain applied to extracted PCM before it is handed to the STT backend.
+///
+/// Stages run in order: high-pass, dialogue-band emphasis, noise gate, loudness normalization.
+#[derive(Debug, Clone)]
+pub struct AudioPreprocessor {
+    config: AudioConfig,
+}
+
+impl AudioPreprocessor {
+    pub fn new(config: AudioConfig) -> Self {
+        Self { config }
+    }
+
+    /// Returns true when at least one stage is active.
+    pub fn is_enabled(&self) -> bool {
+        self.config.normalize
+            || self.config.highpass_hz > 0.0
+            || self.config.dialogue_boost_db != 0.0
+            || self.config.noise_gate
+    }
+
+    /// Process interleaved 16-bit PCM in place.
+    pub fn process(&self, samples: &mut [i16], sample_rate: u32, channels: u16) {
+        if !self.is_enabled() || samples.is_empty() || sample_rate == 0 || channels == 0 {
+            return;
+        }
+
+        let channels = channels as usize;
+        let rate = sample_rate as f32;
+        for ch in 0..channels {
+            let mut signal: Vec<f32> = samples
+                .iter()
+                .skip(ch)
+                .step_by(channels)
+                .map(|s| *s as f32 / 32_768.0)
+                .collect();
+
+            if self.config.highpass_hz > 0.0 && self.config.highpass_hz < rate / 2.0 {
+                Biquad::highpass(rate, self.config.highpass_hz, HIGHPASS_Q).run(&mut signal);
+            }
+            if self.config.dialogue_boost_db != 0.0 && DIALOGUE_CENTER_HZ < rate / 2.0 {
+                Biquad::peaking(
+                    rate,
+                    DIALOGUE_CENTER_HZ,
+                    DIALOGUE_Q,
+                    self.config.dialogue_boost_db,
+                )
+                .run(&mut signal);
+            }
+            if self.config.noise_gate {
+                noise_gate(&mut signal, sample_rate, self.config.noise_gate_db);
+            }
+            if self.config.normalize {
+                normalize_loudness(
+                    &mut signal,
+                    sample_rate,
+                    self.config.target_loudness_db,
+                    self.config.max_gain_db,
+                );
+            }
+
+            for (dst, src) in samples.iter_mut().skip(ch).step_by(channels).zip(signal) {
+                *dst = (src * 32_768.0)
+                    .round()
+                    .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            }
+        }
+    }
+}
+
+/// Direct form I biquad using the RBJ audio EQ cookbook coefficients.
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    fn highpass(sample_rate: f32, cutoff_hz: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * cutoff_hz / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    fn peaking(sample_rate: f32, center_hz: f32, q: f32, gain_db: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * center_hz / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    fn run(&self, signal: &mut [f32]) {
+        let (mut x1, mut x2, mut y1, mut y2) = (0f32, 0f32, 0f32, 0f32);
+        for sample in signal.iter_mut() {
+            let x0 = *sample;
+            let y0 = self.b0 * x0 + self.b1 * x1 + self.b2 * x2 - self.a1 * y1 - self.a2 * y2;
+            x2 = x1;
+            x1 = x0;
+            y2 = y1;
+            y1 = y0;
+            *sample = y0;
+        }
+    }
+}
+
+fn frame_len(sample_rate: u32) -> usize {
+    ((sample_rate * GATE_FRAME_MS / 1000) as usize).max(1)
+}
+
+fn rms_db(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let energy = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+    10.0 * energy.max(f32::MIN_POSITIVE).log10()
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+fn noise_gate(signal: &mut [f32], sample_rate: u32, threshold_db: f32) {
+    let frame = frame_len(sample_rate);
+    let hold_frames = (GATE_HOLD_MS / GATE_FRAME_MS) as usize;
+    let floor = db_to_gain(GATE_FLOOR_DB);
+    let rate = sample_rate as f32;
+    let attack = 1.0 - (-1000.0 / (GATE_ATTACK_MS * rate)).exp();
+    let release = 1.0 - (-1000.0 / (GATE_RELEASE_MS * rate)).exp();
+
+    // Decide per frame whether the gate is open, keeping it open for a short hold period so
+    // word endings are not clipped.
+    let mut targets = Vec::with_capacity(signal.len().div_ceil(frame));
+    let mut held = 0usize;
+    for chunk in signal.chunks(frame) {
+        if rms_db(chunk) >= threshold_db {
+            held = hold_frames;
+            targets.push(1.0);
+        } else if held > 0 {
+            held -= 1;
+            targets.push(1.0);
+        } else {
+            targets.push(floor);
+        }
+    }
+
+    let mut gain = targets.first().copied().unwrap_or(1.0);
+    let mut closed_frames = 0usize;
+    for (chunk, target) in signal.chunks_mut(frame).zip(targets) {
+        if target < 1.0 {
+            closed_frames += 1;
+        }
+        let coeff = if target > gain { attack } else { release };
+        for sample in chunk.iter_mut() {
+            gain += (target - gain) * coeff;
+            *sample *= gain;
+        }
+    }
+    trace!("Noise gate closed for {} frames", closed_frames);
+}
+
+fn normalize_loudness(signal: &mut [f32], sample_rate: u32, target_db: f32, max_gain_db: f32) {
+    let frame = frame_len(sample_rate);
+    let mut energy = 0f64;
+    let mut active = 0usize;
+    for chunk in signal.chunks(frame) {
+        if rms_db(chunk) > LOUDNESS_SILENCE_DB {
+            energy += chunk.iter().map(|s| (*s as f64) * (*s as f64)).sum::<f64>();
+            active += chunk.len();
+        }
+    }
+    if active == 0 {
+        trace!("Loudness normalization skipped: no active audio");
+        return;
+    }
+
+    let current_db = 10.0
+        * ((energy / active as f64) as f32)
+            .max(f32::MIN_POSITIVE)
+            .log10();
+    let max_gain_db = max_gain_db.max(0.0);
+    let mut gain = db_to_gain((target_db - current_db).clamp(-max_gain_db, max_gain_db));
+
+    let peak = signal.iter().fold(0f32, |acc, s| acc.max(s.abs()));
+    if peak > 0.0 && peak * gain > PEAK_CEILING {
+        gain = PEAK_CEILING / peak;
+    }
+
+    trace!(
+        "Loudness normalization: measured {:.1} dBFS, target {:.1} dBFS, gain {:.2}x",
+        current_db, target_db, gain
+    );
+    for sample in signal.iter_mut() {
+        *sample *= gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, amplitude: f32, sample_rate: u32, len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                ((2.0 * PI * freq * t).sin() * amplitude * 32_767.0) as i16
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[i16]) -> f32 {
+        let sum: f64 = samples.iter().map(|s| (*s as f64).powi(2)).sum();
+        (sum / samples.len() as f64).sqrt() as f32
+    }
+
+    #[test]
+    fn test_disabled_chain_is_noop() {
+        let preprocessor = AudioPreprocessor::new(AudioConfig::default());
+        assert!(!preprocessor.is_enabled());
+
+        let original = sine(440.0, 0.1, 16_000, 1_600);
+        let mut samples = original.clone();
+        preprocessor.process(&mut samples, 16_000, 1);
+        assert_eq!(samples, original);
+    }
+
+    #[test]
+    fn test_highpass_removes_dc_offset() {
+        let preprocessor = AudioPreprocessor::new(AudioConfig {
+            highpass_hz: 100.0,
+            ..Default::default()
+        });
+
+        let mut samples = vec![8_000i16; 16_000];
+        preprocessor.process(&mut samples, 16_000, 1);
+        assert!(rms(&samples[8_000..]) < 10.0);
+    }
+
+    #[test]
+    fn test_normalize_raises_quiet_audio() {
+        let preprocessor = AudioPreprocessor::new(AudioConfig {
+            normalize: true,
+            target_loudness_db: -20.0,
+            max_gain_db: 30.0,
+            ..Default::default()
+        });
+
+        let mut samples = sine(440.0, 0.01, 16_000, 16_000);
+        let before = rms(&samples);
+        preprocessor.process(&mut samples, 16_000, 1);
+        let after = rms(&samples);
+
+        // -20 dBFS RMS is ~3277 in i16 units.
+        assert!(after > before * 5.0);
+        assert!(
+            (after - 3_277.0).abs() < 400.0,
+            "rms after normalize: {after}"
+        );
+    }
+
+    #[test]
+    fn test_noise_gate_attenuates_quiet_section() {
+        let preprocessor = AudioPreprocessor::new(AudioConfig {
+            noise_gate: true,
+            noise_gate_db: -40.0,
+            ..Default::default()
+        });
+
+        let mut samples = sine(440.0, 0.3, 16_000, 8_000);
+        samples.extend(sine(440.0, 0.001, 16_000, 8_000));
+        let loud_before = rms(&samples[..8_000]);
+        let quiet_before = rms(&samples[14_000..]);
+
+        preprocessor.process(&mut samples, 16_000, 1);
+
+        assert!(rms(&samples[..8_000]) > loud_before * 0.9);
+        assert!(rms(&samples[14_000..]) < quiet_before * 0.1);
+    }
+}
@@ -1,17 +1,19 @@
-use super::{BackendKind, SttBackend, SttDeviceNotice};
+use super::{BackendKind, SttBackend, SttCancelHandle, SttDeviceNotice};
 use crate::config::InferenceDevice;
-use hound::{SampleFormat, WavReader};
 use log::{debug, info, trace, warn};
-use mpv_stt_common::{MpvSttError, Result};
-use mpv_stt_srt::{SrtFile, SubtitleEntry, Timestamp};
+use mpv_stt_common::{
+    MpvSttError, PcmBuffer, Result, SegmentSink, Transcript, TranscriptSegment,
+    TranscriptionOptions,
+};
 use std::ffi::c_void;
-use std::path::{Path, PathBuf};
 use std::sync::{
     Arc,
     atomic::{AtomicU64, Ordering},
 };
+use std::time::Instant;
 use whisper_rs::{
-    self, FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperError,
+    self, FullParams, SamplingStrategy, SegmentCallbackData, WhisperContext,
+    WhisperContextParameters, WhisperError,
 };
 
 const EXPECTED_SAMPLE_RATE: u32 = 16_000;
@@ -96,19 +98,37 @@ impl SttBackend for LocalWhisperBackend {
         return BackendKind::LocalModelCpu;
     }
 
-    fn transcribe<P: AsRef<Path>>(
+    fn transcribe_with_options(
+        &mut self,
+        audio: &PcmBuffer,
+        duration_ms: u64,
+        options: &TranscriptionOptions,
+    ) -> Result<Transcript> {
+        self.transcribe_impl(audio, duration_ms, options, None)
+    }
+
+    fn transcribe_streaming(
         &mut self,
-        audio_path: P,
-        output_prefix: P,
+        audio: &PcmBuffer,
         duration_ms: u64,
-    ) -> Result<()> {
-        self.transcribe_impl(audio_path, output_prefix, duration_ms)
+        options: &TranscriptionOptions,
+        sink: &SegmentSink,
+    ) -> Result<Transcript> {
+        // The CPU retry after a GPU failure would otherwise replay segments already sent.
+        self.transcribe_impl(audio, duration_ms, options, Some(&sink.deduplicated()))
     }
 
     fn cancel_inflight(&self) {
         self.cancel_generation.fetch_add(1, Ordering::Relaxed);
     }
 
+    fn cancel_handle(&self) -> SttCancelHandle {
+        let generation = Arc::clone(&self.cancel_generation);
+        SttCancelHandle::new(move || {
+            generation.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
     fn take_device_notice(&mut self) -> Option<SttDeviceNotice> {
         self.pending_device_notice.take()
     }
@@ -196,35 +216,27 @@ impl LocalWhisperBackend {
         Ok(())
     }
 
-    fn load_audio_samples<P: AsRef<Path>>(&self, audio_path: P) -> Result<Vec<f32>> {
-        let mut reader = WavReader::open(audio_path.as_ref())
-            .map_err(|e| MpvSttError::SttFailed(format!("Failed to read WAV: {}", e)))?;
-        let spec = reader.spec();
-
-        if spec.channels != EXPECTED_CHANNELS
-            || spec.sample_rate != EXPECTED_SAMPLE_RATE
-            || spec.bits_per_sample != 16
-            || spec.sample_format != SampleFormat::Int
-        {
+    fn load_audio_samples(&self, audio: &PcmBuffer) -> Result<Vec<f32>> {
+        if audio.channels != EXPECTED_CHANNELS || audio.sample_rate != EXPECTED_SAMPLE_RATE {
             return Err(MpvSttError::SttFailed(format!(
-                "Unexpected WAV format: channels={}, sample_rate={}, bits_per_sample={}, format={:?}",
-                spec.channels, spec.sample_rate, spec.bits_per_sample, spec.sample_format
+                "Unexpected audio format: channels={}, sample_rate={}",
+                audio.channels, audio.sample_rate
             )));
         }
 
-        let samples: Vec<i16> = reader
-            .samples::<i16>()
-            .collect::<std::result::Result<Vec<i16>, _>>()
-            .map_err(|e| MpvSttError::SttFailed(format!("Failed to read WAV samples: {}", e)))?;
-
-        let mut float_samples = vec![0f32; samples.len()];
-        whisper_rs::convert_integer_to_float_audio(&samples, &mut float_samples)
+        let mut float_samples = vec![0f32; audio.samples.len()];
+        whisper_rs::convert_integer_to_float_audio(&audio.samples, &mut float_samples)
             .map_err(|e| stt_error("Failed to convert audio", e))?;
 
         Ok(float_samples)
     }
 
-    fn build_params<'a>(&'a self, duration_ms: u64) -> FullParams<'a, 'a> {
+    fn build_params<'a>(
+        &'a self,
+        duration_ms: u64,
+        language: &'a str,
+        options: &TranscriptionOptions,
+    ) -> FullParams<'a, 'a> {
         let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 5 });
         params.set_n_threads(self.config.threads as i32);
         params.set_print_progress(false);
@@ -232,14 +244,27 @@ impl LocalWhisperBackend {
         params.set_print_timestamps(false);
         params.set_print_special(false);
         params.set_no_timestamps(false);
-        params.set_translate(false);
+        params.set_translate(options.translate);
+        if let Some(prompt) = options.prompt.as_deref().filter(|p| !p.trim().is_empty()) {
+            // whisper.cpp takes a C string; interior NULs would abort the conversion.
+            params.set_initial_prompt(&prompt.replace('\0', ""));
+        }
+        if let Some(temperature) = options.temperature {
+            params.set_temperature(temperature);
+        }
+        if options.word_timestamps {
+            // whisper.cpp's word mode: token timestamps, segments capped at one word.
+            params.set_token_timestamps(true);
+            params.set_max_len(1);
+            params.set_split_on_word(true);
+        }
 
-        if self.config.language.trim().eq_ignore_ascii_case("auto") {
+        if is_auto_language(language) {
             params.set_detect_language(true);
             params.set_language(None);
         } else {
             params.set_detect_language(false);
-            params.set_language(Some(self.config.language.as_str()));
+            params.set_language(Some(language));
         }
 
         let dur_i32 = i32::try_from(duration_ms).unwrap_or(i32::MAX);
@@ -272,41 +297,25 @@ impl LocalWhisperBackend {
         }
     }
 
-    fn write_srt<P: AsRef<Path>>(&self, output_prefix: P, segments: &[SegmentData]) -> Result<()> {
-        let mut srt_file = SrtFile::new();
-        for (idx, segment) in segments.iter().enumerate() {
-            srt_file.append_entry(SubtitleEntry {
-                index: (idx + 1) as u32,
-                start_time: Timestamp::from_milliseconds(segment.start_ms),
-                end_time: Timestamp::from_milliseconds(segment.end_ms),
-                text: segment.text.clone(),
-            });
-        }
-
-        let output_path = PathBuf::from(output_prefix.as_ref()).with_extension("srt");
-        srt_file.save(&output_path)?;
-        Ok(())
-    }
-
-    fn transcribe_impl<P: AsRef<Path>>(
+    fn transcribe_impl(
         &mut self,
-        audio_path: P,
-        output_prefix: P,
+        pcm: &PcmBuffer,
         duration_ms: u64,
-    ) -> Result<()> {
+        options: &TranscriptionOptions,
+        sink: Option<&SegmentSink>,
+    ) -> Result<Transcript> {
+        let start = Instant::now();
         self.ensure_context()?;
 
-        let audio_str = audio_path
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| MpvSttError::InvalidPath("Invalid audio path".to_string()))?;
-
         trace!(
-            "Running local model STT on {} (duration: {}ms, language: {})",
-            audio_str, duration_ms, self.config.language
+            "Running local model STT on {} samples (duration: {}ms, language: {}, translate: {})",
+            pcm.samples.len(),
+            duration_ms,
+            options.language.as_deref().unwrap_or(&self.config.language),
+            options.translate
         );
 
-        let audio = self.load_audio_samples(&audio_path)?;
+        let audio = self.load_audio_samples(pcm)?;
         if audio.is_empty() {
             return Err(MpvSttError::SttFailed("Audio buffer is empty".to_string()));
         }
@@ -325,8 +334,10 @@ impl LocalWhisperBackend {
             );
         }
 
-        let segments = match self.run_inference(&audio, effective_duration_ms) {
-            Ok(segments) => Ok(segments),
+        let infer_start = Instant::now();
+        let mut transcript = match self.run_inference(&audio, effective_duration_ms, options, sink)
+        {
+            Ok(transcript) => Ok(transcript),
             Err(err) => {
                 if matches!(err, MpvSttError::SttCancelled) {
                     return Err(err);
@@ -343,21 +354,29 @@ impl LocalWhisperBackend {
                         InferenceDevice::CPU,
                         "fallback after gpu inference failure",
                     )?;
-                    self.run_inference(&audio, effective_duration_ms)
+                    self.run_inference(&audio, effective_duration_ms, options, sink)
                 } else {
                     Err(err)
                 }
             }
         }?;
-        self.write_srt(output_prefix, &segments)?;
+        transcript.duration_ms = effective_duration_ms;
+        transcript.metrics.inference_ms = infer_start.elapsed().as_millis() as u64;
+        transcript.metrics.total_ms = start.elapsed().as_millis() as u64;
 
         debug!("Local model STT completed successfully");
-        Ok(())
+        Ok(transcript)
     }
 }
 
 impl LocalWhisperBackend {
-    fn run_inference(&self, audio: &[f32], duration_ms: u64) -> Result<Vec<SegmentData>> {
+    fn run_inference(
+        &self,
+        audio: &[f32],
+        duration_ms: u64,
+        options: &TranscriptionOptions,
+        sink: Option<&SegmentSink>,
+    ) -> Result<Transcript> {
         let run_generation = self.cancel_generation.load(Ordering::Relaxed);
         let ctx = self
             .ctx
@@ -367,11 +386,16 @@ impl LocalWhisperBackend {
             .create_state()
             .map_err(|e| stt_error("Failed to create state", e))?;
 
-        let mut params = self.build_params(duration_ms);
+        let language = options
+            .language
+            .as_deref()
+            .unwrap_or(&self.config.language)
+            .trim();
+        let mut params = self.build_params(duration_ms, language, options);
         // whisper.cpp 在 detect_language 模式下偶现“检测到语言但无分段”的情况；
         // 为避免空结果，先单独做语言检测，再用检测到的语言跑一次完整转录。
         let mut detected_lang: Option<String> = None;
-        if self.config.language.trim().eq_ignore_ascii_case("auto") {
+        if is_auto_language(language) {
             match self.detect_language(&mut state, audio) {
                 Ok(lang) => {
                     detected_lang = Some(lang);
@@ -390,6 +414,16 @@ impl LocalWhisperBackend {
             info!("Detected language: {}", lang);
         };
 
+        if let Some(sink) = sink.cloned() {
+            params.set_segment_callback_safe_lossy(move |data: SegmentCallbackData| {
+                if let Some(segment) =
+                    make_segment(data.start_timestamp, data.end_timestamp, &data.text)
+                {
+                    sink.emit(&segment);
+                }
+            });
+        }
+
         let abort_ctx = Box::new(AbortContext {
             generation: Arc::clone(&self.cancel_generation),
             run_generation,
@@ -412,7 +446,13 @@ impl LocalWhisperBackend {
             return Err(MpvSttError::SttCancelled);
         }
 
-        collect_segments(&state)
+        let language =
+            detected_lang.or_else(|| (!is_auto_language(language)).then(|| language.to_string()));
+        Ok(Transcript {
+            segments: collect_segments(&state)?,
+            language,
+            ..Default::default()
+        })
     }
 }
 
@@ -433,6 +473,10 @@ impl Drop for AbortGuard {
     }
 }
 
+fn is_auto_language(language: &str) -> bool {
+    language.is_empty() || language.eq_ignore_ascii_case("auto")
+}
+
 unsafe extern "C" fn whisper_abort_callback(user_data: *mut c_void) -> bool {
     if user_data.is_null() {
         return false;
@@ -441,37 +485,36 @@ unsafe extern "C" fn whisper_abort_callback(user_data: *mut c_void) -> bool {
     ctx.generation.load(Ordering::Relaxed) != ctx.run_generation
 }
 
-struct SegmentData {
-    start_ms: u32,
-    end_ms: u32,
-    text: String,
-}
-
-fn collect_segments(state: &whisper_rs::WhisperState) -> Result<Vec<SegmentData>> {
+fn collect_segments(state: &whisper_rs::WhisperState) -> Result<Vec<TranscriptSegment>> {
     let mut segments = Vec::new();
     for segment in state.as_iter() {
-        let start_ms = timestamp_to_millis(segment.start_timestamp());
-        let end_ms = timestamp_to_millis(segment.end_timestamp());
-        if end_ms <= start_ms {
-            continue;
-        }
         let text = segment
             .to_str_lossy()
-            .map_err(|e| stt_error("Failed to read segment text", e))?
-            .trim()
-            .to_string();
-        if text.is_empty() {
-            continue;
-        }
-        segments.push(SegmentData {
-            start_ms,
-            end_ms,
-            text,
-        });
+            .map_err(|e| stt_error("Failed to read segment text", e))?;
+        segments.extend(make_segment(
+            segment.start_timestamp(),
+            segment.end_timestamp(),
+            &text,
+        ));
     }
     Ok(segments)
 }
 
+/// Segment from whisper's centisecond timestamps; `None` for empty or zero-length output.
+fn make_segment(start_cs: i64, end_cs: i64, text: &str) -> Option<TranscriptSegment> {
+    let start_ms = timestamp_to_millis(start_cs);
+    let end_ms = timestamp_to_millis(end_cs);
+    let text = text.trim();
+    if end_ms <= start_ms || text.is_empty() {
+        return None;
+    }
+    Some(TranscriptSegment {
+        start_ms: start_ms as u64,
+        end_ms: end_ms as u64,
+        text: text.to_string(),
+    })
+}
+
 fn timestamp_to_millis(timestamp_cs: i64) -> u32 {
     let millis = timestamp_cs.saturating_mul(10);
     if millis < 0 {
@@ -0,0 +1,400 @@
+use super::{BackendKind, SttBackend, SttCancelHandle, SttDeviceNotice};
+use log::{debug, trace};
+use mpv_stt_common::{
+    MpvSttError, PcmBuffer, Result, SegmentSink, Transcript, TranscriptMetrics, TranscriptSegment,
+    TranscriptionOptions, rms_db,
+};
+use mpv_stt_srt::SrtFile;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::{Duration, Instant};
+
+pub type MockSttConfig = crate::config::SttMockConfig;
+
+const FRAME_MS: u64 = 100;
+// Quiet gaps shorter than this are bridged so a single utterance is not split on every pause.
+const MAX_GAP_MS: u64 = 300;
+const DELAY_STEP: Duration = Duration::from_millis(10);
+
+/// Deterministic backend for tests: no model, scripted or energy-derived segments.
+pub struct MockBackend {
+    config: MockSttConfig,
+    fixture: Option<Vec<TranscriptSegment>>,
+    calls: u64,
+    cancel_generation: Arc<AtomicU64>,
+}
+
+impl MockBackend {
+    pub fn new(config: MockSttConfig) -> Self {
+        Self {
+            config,
+            fixture: None,
+            calls: 0,
+            cancel_generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Number of `transcribe` calls made so far, including failed ones.
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    fn fixture_segments(&mut self) -> Result<Option<&[TranscriptSegment]>> {
+        if self.config.fixture_path.trim().is_empty() {
+            return Ok(None);
+        }
+        if self.fixture.is_none() {
+            let srt = SrtFile::parse(&self.config.fixture_path)?;
+            debug!(
+                "Mock STT loaded {} fixture segments from {}",
+                srt.entries.len(),
+                self.config.fixture_path
+            );
+            self.fixture = Some(srt.segments());
+        }
+        Ok(self.fixture.as_deref())
+    }
+
+    fn wait_until(&self, deadline: Instant, run_generation: u64) -> Result<()> {
+        loop {
+            if self.cancel_generation.load(Ordering::Relaxed) != run_generation {
+                return Err(MpvSttError::SttCancelled);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            std::thread::sleep(DELAY_STEP.min(deadline - now));
+        }
+    }
+
+    fn transcribe_impl(
+        &mut self,
+        audio: &PcmBuffer,
+        duration_ms: u64,
+        options: &TranscriptionOptions,
+        sink: Option<&SegmentSink>,
+    ) -> Result<Transcript> {
+        let start = Instant::now();
+        let delay = Duration::from_millis(self.config.delay_ms);
+        let run_generation = self.cancel_generation.load(Ordering::Relaxed);
+        self.calls += 1;
+        let call = self.calls;
+
+        trace!(
+            "Mock STT call {}: {} samples (duration: {}ms)",
+            call,
+            audio.samples.len(),
+            duration_ms
+        );
+
+        if self.config.fail_every > 0 && call.is_multiple_of(self.config.fail_every as u64) {
+            self.wait_until(start + delay, run_generation)?;
+            return Err(MpvSttError::SttFailed(format!(
+                "mock failure injected on call {call}"
+            )));
+        }
+
+        let audio_ms = audio.duration_ms();
+        let duration_ms = if duration_ms > 0 {
+            duration_ms.min(audio_ms)
+        } else {
+            audio_ms
+        };
+
+        let segments = match self.fixture_segments()? {
+            Some(fixture) => fixture
+                .iter()
+                .filter(|segment| segment.start_ms < duration_ms)
+                .map(|segment| TranscriptSegment {
+                    end_ms: segment.end_ms.min(duration_ms),
+                    ..segment.clone()
+                })
+                .collect(),
+            None => energy_segments(audio, duration_ms, self.config.energy_threshold_db),
+        };
+        let segments = if options.word_timestamps {
+            segments.iter().flat_map(split_words).collect()
+        } else {
+            segments
+        };
+
+        // Spread the delay over the segments so streaming consumers see them arrive one by one.
+        if let Some(sink) = sink {
+            let step = delay / (segments.len() as u32 + 1);
+            for (idx, segment) in segments.iter().enumerate() {
+                self.wait_until(start + step * (idx as u32 + 1), run_generation)?;
+                sink.emit(segment);
+            }
+        }
+        self.wait_until(start + delay, run_generation)?;
+
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        Ok(Transcript {
+            segments,
+            language: Some(options.language.as_ref().unwrap_or(&self.config.language))
+                .filter(|lang| !lang.is_empty())
+                .cloned(),
+            duration_ms,
+            metrics: TranscriptMetrics {
+                total_ms: elapsed_ms,
+                inference_ms: elapsed_ms,
+                queue_ms: 0,
+            },
+        })
+    }
+}
+
+impl SttBackend for MockBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Mock
+    }
+
+    fn transcribe_with_options(
+        &mut self,
+        audio: &PcmBuffer,
+        duration_ms: u64,
+        options: &TranscriptionOptions,
+    ) -> Result<Transcript> {
+        self.transcribe_impl(audio, duration_ms, options, None)
+    }
+
+    fn transcribe_streaming(
+        &mut self,
+        audio: &PcmBuffer,
+        duration_ms: u64,
+        options: &TranscriptionOptions,
+        sink: &SegmentSink,
+    ) -> Result<Transcript> {
+        self.transcribe_impl(audio, duration_ms, options, Some(sink))
+    }
+
+    fn cancel_inflight(&self) {
+        self.cancel_generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn cancel_handle(&self) -> SttCancelHandle {
+        let generation = Arc::clone(&self.cancel_generation);
+        SttCancelHandle::new(move || {
+            generation.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    fn take_device_notice(&mut self) -> Option<SttDeviceNotice> {
+        None
+    }
+}
+
+/// One segment per run of frames louder than `threshold_db`, labelled by position.
+fn energy_segments(
+    audio: &PcmBuffer,
+    duration_ms: u64,
+    threshold_db: f32,
+) -> Vec<TranscriptSegment> {
+    let channels = audio.channels.max(1) as usize;
+    let frame_len = (audio.sample_rate as u64 * FRAME_MS / 1000) as usize * channels;
+    if frame_len == 0 {
+        return Vec::new();
+    }
+
+    let mut spans: Vec<(u64, u64)> = Vec::new();
+    for (idx, frame) in audio.samples.chunks(frame_len).enumerate() {
+        let start_ms = idx as u64 * FRAME_MS;
+        if start_ms >= duration_ms {
+            break;
+        }
+        if rms_db(frame) < threshold_db {
+            continue;
+        }
+        let end_ms = (start_ms + FRAME_MS).min(duration_ms);
+        match spans.last_mut() {
+            Some((_, last_end)) if start_ms.saturating_sub(*last_end) <= MAX_GAP_MS => {
+                *last_end = end_ms;
+            }
+            _ => spans.push((start_ms, end_ms)),
+        }
+    }
+
+    spans
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (start_ms, end_ms))| TranscriptSegment {
+            start_ms,
+            end_ms,
+            text: format!("mock speech {}", idx + 1),
+        })
+        .collect()
+}
+
+/// `segment` cut into one segment per word, sharing its time span evenly.
+fn split_words(segment: &TranscriptSegment) -> Vec<TranscriptSegment> {
+    let words: Vec<&str> = segment.text.split_whitespace().collect();
+    let span = segment.end_ms.saturating_sub(segment.start_ms);
+    let count = words.len().max(1) as u64;
+    words
+        .iter()
+        .enumerate()
+        .map(|(idx, word)| TranscriptSegment {
+            start_ms: segment.start_ms + span * idx as u64 / count,
+            end_ms: segment.start_ms + span * (idx as u64 + 1) / count,
+            text: word.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(ms: u64, amplitude: i16) -> Vec<i16> {
+        (0..ms * 16)
+            .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+            .collect()
+    }
+
+    fn speech_and_silence() -> PcmBuffer {
+        let mut samples = tone(500, 8_000);
+        samples.extend(tone(1_000, 0));
+        samples.extend(tone(400, 8_000));
+        PcmBuffer::new(samples, 16_000, 1)
+    }
+
+    #[test]
+    fn test_energy_segments() {
+        let mut backend = MockBackend::new(MockSttConfig::default());
+        let transcript = backend.transcribe(&speech_and_silence(), 1_900).unwrap();
+
+        let spans: Vec<_> = transcript
+            .segments
+            .iter()
+            .map(|s| (s.start_ms, s.end_ms, s.text.as_str()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(0, 500, "mock speech 1"), (1_500, 1_900, "mock speech 2")]
+        );
+        assert_eq!(transcript.duration_ms, 1_900);
+    }
+
+    #[test]
+    fn test_word_timestamps_split_segments() {
+        let mut backend = MockBackend::new(MockSttConfig::default());
+        let options = TranscriptionOptions {
+            word_timestamps: true,
+            ..Default::default()
+        };
+        let transcript = backend
+            .transcribe_with_options(&speech_and_silence(), 1_900, &options)
+            .unwrap();
+
+        let words: Vec<_> = transcript
+            .segments
+            .iter()
+            .take(3)
+            .map(|s| (s.start_ms, s.end_ms, s.text.as_str()))
+            .collect();
+        assert_eq!(
+            words,
+            vec![(0, 166, "mock"), (166, 333, "speech"), (333, 500, "1")]
+        );
+        assert_eq!(transcript.segments.len(), 6);
+    }
+
+    #[test]
+    fn test_streaming_emits_segments_before_returning() {
+        let mut backend = MockBackend::new(MockSttConfig {
+            delay_ms: 300,
+            ..Default::default()
+        });
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = {
+            let received = Arc::clone(&received);
+            SegmentSink::new(move |segment| {
+                received
+                    .lock()
+                    .unwrap()
+                    .push((segment.clone(), Instant::now()))
+            })
+        };
+
+        let transcript = backend
+            .transcribe_streaming(
+                &speech_and_silence(),
+                1_900,
+                &TranscriptionOptions::default(),
+                &sink,
+            )
+            .unwrap();
+        let returned_at = Instant::now();
+
+        let received = received.lock().unwrap();
+        let segments: Vec<_> = received
+            .iter()
+            .map(|(segment, _)| segment.clone())
+            .collect();
+        assert_eq!(segments, transcript.segments);
+        assert!(returned_at - received[0].1 >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_fixture_segments_are_clipped_to_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = dir.path().join("fixture.srt");
+        std::fs::write(
+            &fixture,
+            "1\n00:00:00,200 --> 00:00:01,000\nfirst\n\n2\n00:00:01,500 --> 00:00:03,000\nsecond\n\n3\n00:00:05,000 --> 00:00:06,000\nlate\n",
+        )
+        .unwrap();
+
+        let mut backend = MockBackend::new(MockSttConfig {
+            fixture_path: fixture.to_string_lossy().into_owned(),
+            ..Default::default()
+        });
+        let transcript = backend
+            .transcribe(&PcmBuffer::silence(2_000, 16_000, 1), 2_000)
+            .unwrap();
+
+        assert_eq!(transcript.segments.len(), 2);
+        assert_eq!(transcript.segments[1].text, "second");
+        assert_eq!(transcript.segments[1].end_ms, 2_000);
+    }
+
+    #[test]
+    fn test_failure_injection() {
+        let mut backend = MockBackend::new(MockSttConfig {
+            fail_every: 2,
+            ..Default::default()
+        });
+        let audio = PcmBuffer::silence(100, 16_000, 1);
+
+        assert!(backend.transcribe(&audio, 100).is_ok());
+        assert!(matches!(
+            backend.transcribe(&audio, 100),
+            Err(MpvSttError::SttFailed(_))
+        ));
+        assert!(backend.transcribe(&audio, 100).is_ok());
+        assert_eq!(backend.calls(), 3);
+    }
+
+    #[test]
+    fn test_cancel_interrupts_delay() {
+        let mut backend = MockBackend::new(MockSttConfig {
+            delay_ms: 10_000,
+            ..Default::default()
+        });
+        let generation = Arc::clone(&backend.cancel_generation);
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            generation.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let start = Instant::now();
+        let result = backend.transcribe(&PcmBuffer::silence(100, 16_000, 1), 100);
+        canceller.join().unwrap();
+
+        assert!(matches!(result, Err(MpvSttError::SttCancelled)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
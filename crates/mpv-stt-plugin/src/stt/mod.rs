@@ -1,6 +1,9 @@
 use crate::config::InferenceDevice;
-use mpv_stt_common::Result;
+use mpv_stt_common::{PcmBuffer, Result, SegmentSink, Transcript, TranscriptionOptions};
+use mpv_stt_protocol::JobPriority;
+use mpv_stt_srt::SrtFile;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Enumerates available speech-to-text backends.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -8,6 +11,7 @@ pub enum BackendKind {
     LocalModelCpu,
     LocalModelCuda,
     RemoteHttp,
+    Mock,
 }
 
 impl std::fmt::Display for BackendKind {
@@ -16,6 +20,7 @@ impl std::fmt::Display for BackendKind {
             BackendKind::LocalModelCpu => write!(f, "local-model-cpu"),
             BackendKind::LocalModelCuda => write!(f, "local-model-cuda"),
             BackendKind::RemoteHttp => write!(f, "remote-http"),
+            BackendKind::Mock => write!(f, "mock"),
         }
     }
 }
@@ -24,20 +29,80 @@ impl std::fmt::Display for BackendKind {
 pub trait SttBackend: Send {
     fn kind(&self) -> BackendKind;
 
-    fn transcribe<P: AsRef<Path>>(
+    /// Transcribe 16 kHz mono PCM; segment timestamps are relative to the start of `audio`.
+    fn transcribe(&mut self, audio: &PcmBuffer, duration_ms: u64) -> Result<Transcript> {
+        self.transcribe_with_options(audio, duration_ms, &TranscriptionOptions::default())
+    }
+
+    /// [`transcribe`](Self::transcribe) with per-call language, prompt and task overrides.
+    fn transcribe_with_options(
+        &mut self,
+        audio: &PcmBuffer,
+        duration_ms: u64,
+        options: &TranscriptionOptions,
+    ) -> Result<Transcript>;
+
+    /// [`transcribe_with_options`](Self::transcribe_with_options) that also hands each segment to
+    /// `sink` once it is final. The default emits everything after the run completes.
+    fn transcribe_streaming(
+        &mut self,
+        audio: &PcmBuffer,
+        duration_ms: u64,
+        options: &TranscriptionOptions,
+        sink: &SegmentSink,
+    ) -> Result<Transcript> {
+        let transcript = self.transcribe_with_options(audio, duration_ms, options)?;
+        for segment in &transcript.segments {
+            sink.emit(segment);
+        }
+        Ok(transcript)
+    }
+
+    /// File output adapter: transcribe and also write the result to `<output_prefix>.srt`.
+    fn transcribe_to_file<P: AsRef<Path>>(
         &mut self,
-        audio_path: P,
+        audio: &PcmBuffer,
         output_prefix: P,
         duration_ms: u64,
-    ) -> Result<()>;
+    ) -> Result<Transcript> {
+        let transcript = self.transcribe(audio, duration_ms)?;
+        SrtFile::from(&transcript).save(output_prefix.as_ref().with_extension("srt"))?;
+        Ok(transcript)
+    }
 
     /// Request cancellation of in-flight work.
     fn cancel_inflight(&self);
 
+    /// Handle that cancels in-flight work from another thread while `transcribe` runs.
+    fn cancel_handle(&self) -> SttCancelHandle;
+
+    /// Scheduling class for subsequent requests; only remote backends act on it.
+    fn set_priority(&mut self, _priority: JobPriority) {}
+
     /// Optional notice about the effective device used (for UI).
     fn take_device_notice(&mut self) -> Option<SttDeviceNotice>;
 }
 
+/// Cloneable trigger equivalent to [`SttBackend::cancel_inflight`].
+#[derive(Clone)]
+pub struct SttCancelHandle(Arc<dyn Fn() + Send + Sync>);
+
+impl SttCancelHandle {
+    pub fn new(cancel: impl Fn() + Send + Sync + 'static) -> Self {
+        Self(Arc::new(cancel))
+    }
+
+    pub fn cancel(&self) {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for SttCancelHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SttCancelHandle")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SttDeviceNotice {
     pub requested: InferenceDevice,
@@ -50,16 +115,20 @@ pub struct SttDeviceNotice {
 #[cfg(not(any(
     feature = "stt_local_cpu",
     feature = "stt_local_cuda",
-    feature = "stt_remote_http"
+    feature = "stt_remote_http",
+    feature = "stt_mock"
 )))]
 compile_error!(
-    "No STT backend selected. Enable exactly one of: stt_local_cpu, stt_local_cuda, stt_remote_http"
+    "No STT backend selected. Enable exactly one of: stt_local_cpu, stt_local_cuda, stt_remote_http, stt_mock"
 );
 
 #[cfg(any(
     all(feature = "stt_local_cpu", feature = "stt_local_cuda"),
     all(feature = "stt_local_cpu", feature = "stt_remote_http"),
-    all(feature = "stt_local_cuda", feature = "stt_remote_http")
+    all(feature = "stt_local_cuda", feature = "stt_remote_http"),
+    all(feature = "stt_mock", feature = "stt_local_cpu"),
+    all(feature = "stt_mock", feature = "stt_local_cuda"),
+    all(feature = "stt_mock", feature = "stt_remote_http")
 ))]
 compile_error!("Cannot enable multiple STT backends simultaneously");
 
@@ -73,6 +142,14 @@ mod local_whisper;
 #[cfg(feature = "stt_remote_http")]
 mod remote_http;
 
+#[cfg(feature = "stt_remote_http")]
+mod remote_live;
+#[cfg(feature = "stt_remote_http")]
+mod remote_tls;
+
+#[cfg(feature = "stt_mock")]
+mod mock;
+
 // Active backend type alias
 #[cfg(feature = "stt_local_cpu")]
 pub use local_whisper::LocalWhisperBackend as ActiveBackend;
@@ -83,6 +160,9 @@ pub use local_whisper::LocalWhisperBackend as ActiveBackend;
 #[cfg(feature = "stt_remote_http")]
 pub use remote_http::RemoteHttpBackend as ActiveBackend;
 
+#[cfg(feature = "stt_mock")]
+pub use mock::MockBackend as ActiveBackend;
+
 // Config exports
 #[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
 pub use local_whisper::LocalModelConfig;
@@ -90,9 +170,25 @@ pub use local_whisper::LocalModelConfig;
 #[cfg(feature = "stt_remote_http")]
 pub use remote_http::RemoteSttConfig;
 
+#[cfg(feature = "stt_mock")]
+pub use mock::MockSttConfig;
+
 // Convenience type alias (ergonomic only)
 #[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
 pub type SttRunner = local_whisper::LocalWhisperBackend;
 
 #[cfg(feature = "stt_remote_http")]
 pub type SttRunner = remote_http::RemoteHttpBackend;
+
+#[cfg(feature = "stt_mock")]
+pub type SttRunner = mock::MockBackend;
+
+// Configuration accepted by `SttRunner::new` for the active backend.
+#[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
+pub type SttRunnerConfig = LocalModelConfig;
+
+#[cfg(feature = "stt_remote_http")]
+pub type SttRunnerConfig = RemoteSttConfig;
+
+#[cfg(feature = "stt_mock")]
+pub type SttRunnerConfig = MockSttConfig;
@@ -1,13 +1,26 @@
-use super::{BackendKind, SttBackend, SttDeviceNotice};
+use super::remote_live::LiveConnection;
+use super::remote_tls;
+use super::{BackendKind, SttBackend, SttCancelHandle, SttDeviceNotice};
 use log::{debug, trace};
-use mpv_stt_common::{MpvSttError, Result};
+use mpv_stt_common::{
+    MpvSttError, PcmBuffer, Result, SegmentSink, Transcript, TranscriptMetrics, TranscriptSegment,
+    TranscriptionOptions,
+};
 use mpv_stt_crypto::{AuthToken, EncryptionKey};
+use mpv_stt_protocol::{
+    GRANULE_RATE, JSON_CONTENT_TYPE, JobMetrics, JobPriority, NDJSON_CONTENT_TYPE, OggOpusWriter,
+    StreamEvent, TranscriptionResponse,
+};
 use mpv_stt_srt::SrtFile;
 use opusic_sys as opus;
-use reqwest::blocking::Client;
-use reqwest::header::{HeaderMap, HeaderValue};
-use std::path::{Path, PathBuf};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue, RETRY_AFTER};
 use libc;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader};
 use std::sync::{
     Arc,
     atomic::{AtomicU64, Ordering},
@@ -15,29 +28,111 @@ use std::sync::{
 use std::time::{Duration, Instant, SystemTime};
 
 pub type RemoteSttConfig = crate::config::SttRemoteHttpConfig;
+use crate::config::{OpusContainer, RemoteTransport};
 
 const HEADER_REQUEST_ID: &str = "x-request-id";
 const HEADER_DURATION_MS: &str = "x-duration-ms";
-const HEADER_AUTH_TOKEN: &str = "x-auth-token";
-const HEADER_COMPRESSION: &str = "x-compression";
+pub(super) const HEADER_AUTH_TOKEN: &str = "x-auth-token";
+const HEADER_CANCEL_TOKEN: &str = "x-cancel-token";
+pub(super) const HEADER_COMPRESSION: &str = "x-compression";
 const HEADER_ENCRYPTED: &str = "x-encrypted";
 const HEADER_QUEUE_MS: &str = "x-metric-queue-ms";
 const HEADER_INFER_MS: &str = "x-metric-infer-ms";
 const HEADER_WORKER_MS: &str = "x-metric-worker-ms";
+const HEADER_CACHE: &str = "x-metric-cache";
 const HEADER_BYTES_IN: &str = "x-bytes-in";
 const HEADER_BYTES_OUT: &str = "x-bytes-out";
+const HEADER_LANGUAGE: &str = "x-language";
+const HEADER_TASK: &str = "x-task";
+const HEADER_PROMPT: &str = "x-prompt";
+const HEADER_TEMPERATURE: &str = "x-temperature";
+const HEADER_WORD_TIMESTAMPS: &str = "x-word-timestamps";
+const HEADER_PRIORITY: &str = "x-priority";
+const HEADER_DEADLINE_MS: &str = "x-deadline-ms";
 
 // HTTP payloads are raw 16 kHz mono PCM WAV bytes; advertise them truthfully.
 const COMPRESSION_PCM: &str = "pcm";
 const COMPRESSION_OPUS: &str = "opus";
+const COMPRESSION_OGG_OPUS: &str = "ogg-opus";
+
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+// Longest server-suggested back-off worth waiting for; a longer one fails the chunk instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+const CANCEL_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Audio per WebSocket message in live transport; a multiple of the Opus frame size.
+const LIVE_MESSAGE_SAMPLES: usize = 16_000;
+
+/// Decoded server reply plus the server-reported details we surface in the transcript.
+struct RemoteReply {
+    segments: Vec<TranscriptSegment>,
+    language: Option<String>,
+    queue_ms: u64,
+    inference_ms: u64,
+}
 
 pub struct RemoteHttpBackend {
     config: RemoteSttConfig,
     server_url: String,
     cancel_generation: Arc<AtomicU64>,
+    /// Request id currently awaiting a server reply (0 when idle).
+    inflight_request: Arc<AtomicU64>,
     encryption_key: Option<EncryptionKey>,
     auth_token: AuthToken,
+    /// Sent with every request so that, on a server without API keys, only this player
+    /// can cancel its jobs.
+    cancel_token: String,
+    client: Client,
+    /// Shared with `/live` sessions so both transports verify the server the same way.
+    tls: Arc<rustls::ClientConfig>,
+    /// Open `/live` session when `transport = "websocket"`; reopened after errors.
+    live: Option<LiveConnection>,
+    /// Scheduling class sent with the next `/transcribe` request.
+    priority: JobPriority,
+}
+
+/// Stops the local wait and asks the server to drop the job; usable from any thread.
+#[derive(Clone)]
+struct RemoteCanceller {
+    generation: Arc<AtomicU64>,
+    inflight_request: Arc<AtomicU64>,
     client: Client,
+    server_url: String,
+    auth_token: String,
+    cancel_token: String,
+}
+
+impl RemoteCanceller {
+    fn cancel(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        let request_id = self.inflight_request.swap(0, Ordering::Relaxed);
+        if request_id == 0 {
+            return;
+        }
+
+        // Cancellation is triggered from the player thread on seek; never block it on the network.
+        let canceller = self.clone();
+        std::thread::spawn(move || {
+            let result = canceller
+                .client
+                .delete(format!(
+                    "{}/transcribe/{}",
+                    canceller.server_url, request_id
+                ))
+                .header(HEADER_AUTH_TOKEN, &canceller.auth_token)
+                .header(HEADER_CANCEL_TOKEN, &canceller.cancel_token)
+                .timeout(CANCEL_REQUEST_TIMEOUT)
+                .send();
+            match result {
+                Ok(response) => debug!(
+                    "Cancel of remote request {} answered {}",
+                    request_id,
+                    response.status()
+                ),
+                Err(e) => debug!("Cancel of remote request {} failed: {}", request_id, e),
+            }
+        });
+    }
 }
 
 impl RemoteHttpBackend {
@@ -52,6 +147,11 @@ impl RemoteHttpBackend {
         } else {
             None
         };
+        if encryption_key.is_some() && config.transport == RemoteTransport::WebSocket {
+            return Err(MpvSttError::SttFailed(
+                "Encryption is not supported with the websocket transport".to_string(),
+            ));
+        }
 
         let auth_token = if !config.auth_secret.is_empty() {
             AuthToken::from_secret(&config.auth_secret)
@@ -59,8 +159,10 @@ impl RemoteHttpBackend {
             AuthToken::from_secret("")
         };
 
+        let tls = remote_tls::client_config(&config)?;
         let client = Client::builder()
             .timeout(Duration::from_millis(config.timeout_ms))
+            .use_preconfigured_tls((*tls).clone())
             .build()
             .map_err(|e| MpvSttError::SttFailed(format!("HTTP client build failed: {}", e)))?;
 
@@ -70,56 +172,193 @@ impl RemoteHttpBackend {
             config,
             server_url,
             cancel_generation: Arc::new(AtomicU64::new(0)),
+            inflight_request: Arc::new(AtomicU64::new(0)),
             encryption_key,
             auth_token,
+            cancel_token: new_cancel_token(),
             client,
+            tls,
+            live: None,
+            priority: JobPriority::default(),
         })
     }
 
-    fn transcribe_impl<P: AsRef<Path>>(
+    fn transcribe_impl(
         &mut self,
-        audio_path: P,
-        output_prefix: P,
+        pcm: &PcmBuffer,
         duration_ms: u64,
-    ) -> Result<()> {
-        let audio_str = audio_path
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| MpvSttError::InvalidPath("Invalid audio path".to_string()))?;
-
+        options: &TranscriptionOptions,
+        sink: Option<&SegmentSink>,
+    ) -> Result<Transcript> {
+        let start = Instant::now();
         trace!(
-            "Remote HTTP STT: {} (duration: {}ms)",
-            audio_str, duration_ms
+            "Remote HTTP STT: {} samples (duration: {}ms)",
+            pcm.samples.len(),
+            duration_ms
         );
 
         let run_generation = self.cancel_generation.load(Ordering::Relaxed);
+        let options = option_headers(&self.request_options(options));
+
+        if self.config.transport == RemoteTransport::WebSocket {
+            let segments = self.transcribe_live(pcm, options, run_generation, sink)?;
+            debug!("Remote live STT completed successfully");
+            return Ok(Transcript {
+                segments,
+                language: None,
+                duration_ms: duration_ms.min(pcm.duration_ms()),
+                metrics: TranscriptMetrics {
+                    total_ms: start.elapsed().as_millis() as u64,
+                    ..Default::default()
+                },
+            });
+        }
 
-        let audio_data = self.compress_audio(&audio_path)?;
+        let audio_data = self.compress_audio(pcm)?;
         if audio_data.is_empty() {
             return Err(MpvSttError::SttFailed("Audio data is empty".to_string()));
         }
 
-        let request_id = self.generate_request_id();
-        let srt_data =
-            self.send_request_with_retry(request_id, &audio_data, duration_ms, run_generation)?;
+        let reply =
+            self.send_request_with_retry(&audio_data, duration_ms, &options, run_generation, sink)?;
 
         if self.cancel_generation.load(Ordering::Relaxed) != run_generation {
             return Err(MpvSttError::SttCancelled);
         }
 
-        if srt_data.iter().all(|b| b.is_ascii_whitespace()) {
-            debug!("Remote HTTP STT returned empty subtitles; skipping SRT parse");
-            let output_path = PathBuf::from(output_prefix.as_ref()).with_extension("srt");
-            SrtFile::new().save(&output_path)?;
-            return Ok(());
+        debug!("Remote HTTP STT completed successfully");
+        Ok(Transcript {
+            segments: reply.segments,
+            language: reply.language,
+            duration_ms: duration_ms.min(pcm.duration_ms()),
+            metrics: TranscriptMetrics {
+                total_ms: start.elapsed().as_millis() as u64,
+                inference_ms: reply.inference_ms,
+                queue_ms: reply.queue_ms,
+            },
+        })
+    }
+
+    fn transcribe_live(
+        &mut self,
+        pcm: &PcmBuffer,
+        options: Vec<(&'static str, String)>,
+        run_generation: u64,
+        sink: Option<&SegmentSink>,
+    ) -> Result<Vec<TranscriptSegment>> {
+        let audio = self.live_messages(pcm)?;
+        // Options are fixed per session; reopen it when they change.
+        if self
+            .live
+            .as_ref()
+            .is_some_and(|connection| connection.options() != options.as_slice())
+        {
+            self.live = None;
         }
+        let generation = Arc::clone(&self.cancel_generation);
+        let cancelled = move || generation.load(Ordering::Relaxed) != run_generation;
+        let mut last_error = None;
+
+        for attempt in 0..self.config.max_retry {
+            if cancelled() {
+                return Err(MpvSttError::SttCancelled);
+            }
 
-        let srt_file = SrtFile::parse_content(&String::from_utf8_lossy(&srt_data))?;
-        let output_path = PathBuf::from(output_prefix.as_ref()).with_extension("srt");
-        srt_file.save(&output_path)?;
+            let result = match self.live.take() {
+                Some(connection) => Ok(connection),
+                None => LiveConnection::connect(
+                    &self.server_url,
+                    &hex::encode(self.auth_token.as_bytes()),
+                    if self.config.use_opus {
+                        COMPRESSION_OPUS
+                    } else {
+                        COMPRESSION_PCM
+                    },
+                    options.clone(),
+                    Arc::clone(&self.tls),
+                    CANCEL_POLL,
+                ),
+            }
+            .and_then(|mut connection| {
+                let deadline = Instant::now() + Duration::from_millis(self.config.timeout_ms);
+                let segments = connection.transcribe(audio.clone(), deadline, &cancelled, sink)?;
+                Ok((connection, segments))
+            });
+
+            // Any failure leaves the session mid-chunk, so the connection is dropped.
+            match result {
+                Ok((connection, segments)) => {
+                    self.live = Some(connection);
+                    return Ok(segments);
+                }
+                Err(MpvSttError::SttCancelled) => return Err(MpvSttError::SttCancelled),
+                Err(e) => last_error = Some(e),
+            }
+            if attempt + 1 < self.config.max_retry {
+                debug!(
+                    "Live attempt {} failed, reconnecting in {}ms...",
+                    attempt + 1,
+                    RETRY_DELAY.as_millis()
+                );
+                self.sleep_unless_cancelled(RETRY_DELAY, run_generation)?;
+            }
+        }
 
-        debug!("Remote HTTP STT completed successfully");
-        Ok(())
+        Err(last_error.unwrap())
+    }
+
+    /// Per-call options, falling back to `[stt.remote_http]` for anything left unset.
+    fn request_options(&self, per_call: &TranscriptionOptions) -> TranscriptionOptions {
+        let configured = |value: &str| Some(value.to_string()).filter(|value| !value.is_empty());
+        TranscriptionOptions {
+            language: per_call
+                .language
+                .clone()
+                .or_else(|| configured(&self.config.language)),
+            prompt: per_call
+                .prompt
+                .clone()
+                .or_else(|| configured(&self.config.prompt)),
+            translate: per_call.translate || self.config.translate,
+            temperature: per_call.temperature.or(self.config.temperature),
+            word_timestamps: per_call.word_timestamps || self.config.word_timestamps,
+        }
+    }
+
+    /// The chunk as `/live` binary messages: raw s16le PCM or framed Opus.
+    fn live_messages(&self, audio: &PcmBuffer) -> Result<Vec<Vec<u8>>> {
+        check_audio_format(audio)?;
+        if audio.samples.is_empty() {
+            return Err(MpvSttError::SttFailed("Audio data is empty".to_string()));
+        }
+
+        if !self.config.use_opus {
+            return Ok(audio
+                .samples
+                .chunks(LIVE_MESSAGE_SAMPLES)
+                .map(|chunk| chunk.iter().flat_map(|s| s.to_le_bytes()).collect())
+                .collect());
+        }
+
+        // One encoder for the whole chunk; the server keeps one decoder per session.
+        let mut encoder = SimpleOpusEncoder::new()
+            .map_err(|e| MpvSttError::SttFailed(format!("Opus encoder init failed: {e}")))?;
+        audio
+            .samples
+            .chunks(LIVE_MESSAGE_SAMPLES)
+            .map(|chunk| encode_opus(&mut encoder, chunk))
+            .collect()
+    }
+
+    fn canceller(&self) -> RemoteCanceller {
+        RemoteCanceller {
+            generation: Arc::clone(&self.cancel_generation),
+            inflight_request: Arc::clone(&self.inflight_request),
+            client: self.client.clone(),
+            server_url: self.server_url.clone(),
+            auth_token: hex::encode(self.auth_token.as_bytes()),
+            cancel_token: self.cancel_token.clone(),
+        }
     }
 
     fn generate_request_id(&self) -> u64 {
@@ -129,13 +368,16 @@ impl RemoteHttpBackend {
             .as_nanos() as u64
     }
 
+    /// Send the request until it succeeds; each attempt gets a fresh request id, so a retry
+    /// is never refused as a duplicate of an attempt the server is still working on.
     fn send_request_with_retry(
         &self,
-        request_id: u64,
         audio: &[u8],
         duration_ms: u64,
+        options: &[(&'static str, String)],
         run_generation: u64,
-    ) -> Result<Vec<u8>> {
+        sink: Option<&SegmentSink>,
+    ) -> Result<RemoteReply> {
         let mut last_error = None;
 
         for attempt in 0..self.config.max_retry {
@@ -143,28 +385,75 @@ impl RemoteHttpBackend {
                 return Err(MpvSttError::SttCancelled);
             }
 
-            match self.send_request(request_id, audio, duration_ms, run_generation) {
+            let request_id = self.generate_request_id();
+            self.inflight_request.store(request_id, Ordering::Relaxed);
+            let result = self.send_request(
+                request_id,
+                audio,
+                duration_ms,
+                options,
+                run_generation,
+                sink,
+            );
+            let _ = self.inflight_request.compare_exchange(
+                request_id,
+                0,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+            let delay = match result {
                 Ok(result) => return Ok(result),
+                Err(MpvSttError::SttCancelled) => return Err(MpvSttError::SttCancelled),
+                Err(e @ MpvSttError::SttRejected(_)) => return Err(e),
+                // Waiting out e.g. a used-up daily quota would stall playback for hours.
+                Err(e @ MpvSttError::SttBusy(retry_after)) if retry_after > MAX_RETRY_AFTER => {
+                    return Err(e);
+                }
+                Err(MpvSttError::SttBusy(retry_after)) => {
+                    last_error = Some(MpvSttError::SttBusy(retry_after));
+                    retry_after
+                }
                 Err(e) => {
                     last_error = Some(e);
-                    if attempt + 1 < self.config.max_retry {
-                        debug!("HTTP request attempt {} failed, retrying...", attempt + 1);
-                        std::thread::sleep(Duration::from_millis(500));
-                    }
+                    RETRY_DELAY
                 }
+            };
+            if attempt + 1 < self.config.max_retry {
+                debug!(
+                    "HTTP request attempt {} failed, retrying in {}ms...",
+                    attempt + 1,
+                    delay.as_millis()
+                );
+                self.sleep_unless_cancelled(delay, run_generation)?;
             }
         }
 
         Err(last_error.unwrap())
     }
 
+    fn sleep_unless_cancelled(&self, delay: Duration, run_generation: u64) -> Result<()> {
+        let deadline = Instant::now() + delay;
+        loop {
+            if self.cancel_generation.load(Ordering::Relaxed) != run_generation {
+                return Err(MpvSttError::SttCancelled);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            std::thread::sleep(CANCEL_POLL.min(deadline - now));
+        }
+    }
+
     fn send_request(
         &self,
         request_id: u64,
         audio: &[u8],
         duration_ms: u64,
+        options: &[(&'static str, String)],
         run_generation: u64,
-    ) -> Result<Vec<u8>> {
+        sink: Option<&SegmentSink>,
+    ) -> Result<RemoteReply> {
         let mut payload = audio.to_vec();
         let encrypted = if let Some(key) = self.encryption_key.as_ref() {
             payload = key.encrypt(&payload)?;
@@ -190,18 +479,43 @@ impl RemoteHttpBackend {
             HeaderValue::from_str(&hex::encode(self.auth_token.as_bytes()))
                 .map_err(|e| MpvSttError::SttFailed(format!("Header error: {}", e)))?,
         );
-        let compression = if self.config.use_opus {
-            COMPRESSION_OPUS
-        } else {
-            COMPRESSION_PCM
+        headers.insert(
+            HEADER_CANCEL_TOKEN,
+            HeaderValue::from_str(&self.cancel_token)
+                .map_err(|e| MpvSttError::SttFailed(format!("Header error: {}", e)))?,
+        );
+        let compression = match (self.config.use_opus, self.config.opus_container) {
+            (false, _) => COMPRESSION_PCM,
+            (true, OpusContainer::Ogg) => COMPRESSION_OGG_OPUS,
+            (true, OpusContainer::Framed) => COMPRESSION_OPUS,
         };
+        headers.insert(HEADER_COMPRESSION, HeaderValue::from_static(compression));
+        headers.insert(
+            HEADER_PRIORITY,
+            HeaderValue::from_static(self.priority.as_str()),
+        );
+        // The HTTP client gives up after `timeout_ms`; let the server stop working then too.
         headers.insert(
-            HEADER_COMPRESSION,
-            HeaderValue::from_static(compression),
+            HEADER_DEADLINE_MS,
+            HeaderValue::from(self.config.timeout_ms.max(1)),
         );
+        for (name, value) in options {
+            headers.insert(
+                *name,
+                HeaderValue::from_str(value)
+                    .map_err(|e| MpvSttError::SttFailed(format!("Header error: {}", e)))?,
+            );
+        }
         if encrypted {
             headers.insert(HEADER_ENCRYPTED, HeaderValue::from_static("1"));
         }
+        // The server does not encrypt streamed replies; encrypted requests get one JSON body.
+        let accept = if sink.is_some() && !encrypted {
+            NDJSON_CONTENT_TYPE
+        } else {
+            JSON_CONTENT_TYPE
+        };
+        headers.insert(ACCEPT, HeaderValue::from_static(accept));
 
         let wall_start = Instant::now();
         let response = self
@@ -217,14 +531,38 @@ impl RemoteHttpBackend {
         }
 
         let status = response.status();
+        // A full queue (503) or the key's rate limit or quota (429).
+        if status == StatusCode::SERVICE_UNAVAILABLE || status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = parse_retry_after(response.headers()).unwrap_or(RETRY_DELAY);
+            debug!(
+                "Server busy for request {} ({}), retry after {}ms",
+                request_id,
+                status,
+                retry_after.as_millis()
+            );
+            return Err(MpvSttError::SttBusy(retry_after));
+        }
         if !status.is_success() {
             let text = response
                 .text()
                 .unwrap_or_else(|_| "unknown error".to_string());
-            return Err(MpvSttError::SttFailed(format!(
-                "Server error ({}): {}",
-                status, text
-            )));
+            let message = format!("Server error ({}): {}", status, text);
+            // Bad input or credentials fail the same way every time.
+            return Err(if status.is_client_error() {
+                MpvSttError::SttRejected(message)
+            } else {
+                MpvSttError::SttFailed(message)
+            });
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if let (true, Some(sink)) = (content_type.starts_with(NDJSON_CONTENT_TYPE), sink) {
+            return self.read_stream(request_id, response, sink, run_generation);
         }
 
         let response_headers = response.headers().clone();
@@ -240,19 +578,31 @@ impl RemoteHttpBackend {
             }
         }
 
+        let reply = if content_type.starts_with(JSON_CONTENT_TYPE) {
+            serde_json::from_slice::<TranscriptionResponse>(&data)
+                .map_err(|e| MpvSttError::SttFailed(format!("Invalid JSON reply: {}", e)))?
+        } else {
+            // Servers without JSON replies send SRT text and the metrics as headers.
+            srt_reply(&data, &response_headers)?
+        };
+
         let wall_ms = wall_start.elapsed().as_millis() as u64;
-        let server_queue_ms = parse_u64_header(&response_headers, HEADER_QUEUE_MS);
-        let server_infer_ms = parse_u64_header(&response_headers, HEADER_INFER_MS);
-        let server_worker_ms = parse_u64_header(&response_headers, HEADER_WORKER_MS);
+        let server_queue_ms = reply.metrics.queue_wait_ms;
+        let server_infer_ms = reply.metrics.inference_ms;
+        let server_worker_ms = reply.metrics.worker_total_ms;
         let server_bytes_in = parse_u64_header(&response_headers, HEADER_BYTES_IN);
         let server_bytes_out = parse_u64_header(&response_headers, HEADER_BYTES_OUT);
         let server_total_ms = server_queue_ms.saturating_add(server_worker_ms);
         let network_ms = wall_ms.saturating_sub(server_total_ms);
         let server_non_infer_ms = server_worker_ms.saturating_sub(server_infer_ms);
+        let server_cache = response_headers
+            .get(HEADER_CACHE)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("off");
 
         debug!(
             "Remote HTTP req {} duration_ms={} wall={}ms net≈{}ms srv_queue={}ms srv_worker={}ms \
-             srv_infer={}ms srv_non_infer={}ms bytes_out={}B bytes_in={}B srv_bytes_out={}B resp_raw={}B",
+             srv_infer={}ms srv_non_infer={}ms srv_cache={} bytes_out={}B bytes_in={}B srv_bytes_out={}B resp_raw={}B",
             request_id,
             duration_ms,
             wall_ms,
@@ -261,69 +611,174 @@ impl RemoteHttpBackend {
             server_worker_ms,
             server_infer_ms,
             server_non_infer_ms,
+            server_cache,
             payload_len,
             server_bytes_in,
             server_bytes_out,
             raw_resp_len
         );
 
-        Ok(data)
+        if let Some(sink) = sink {
+            for segment in &reply.segments {
+                sink.emit(segment);
+            }
+        }
+
+        Ok(RemoteReply {
+            segments: reply.segments,
+            language: reply.language.filter(|s| !s.is_empty()),
+            queue_ms: server_queue_ms,
+            inference_ms: server_infer_ms,
+        })
     }
 
-    fn compress_audio<P: AsRef<Path>>(&self, audio_path: P) -> Result<Vec<u8>> {
-        use hound::WavReader;
+    /// Read an NDJSON reply, handing each segment to `sink` as soon as it arrives.
+    fn read_stream(
+        &self,
+        request_id: u64,
+        response: Response,
+        sink: &SegmentSink,
+        run_generation: u64,
+    ) -> Result<RemoteReply> {
+        let mut segments = Vec::new();
+        for line in BufReader::new(response).lines() {
+            if self.cancel_generation.load(Ordering::Relaxed) != run_generation {
+                return Err(MpvSttError::SttCancelled);
+            }
+            let line = line
+                .map_err(|e| MpvSttError::SttFailed(format!("HTTP stream read failed: {}", e)))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: StreamEvent = serde_json::from_str(&line)
+                .map_err(|e| MpvSttError::SttFailed(format!("Invalid stream event: {}", e)))?;
+            match event {
+                StreamEvent::Segment(segment) => {
+                    sink.emit(&segment);
+                    segments.push(segment);
+                }
+                StreamEvent::Done {
+                    language, metrics, ..
+                } => {
+                    debug!(
+                        "Remote HTTP stream {} done: segments={} srv_queue={}ms srv_worker={}ms srv_infer={}ms srv_cache_hit={}",
+                        request_id,
+                        segments.len(),
+                        metrics.queue_wait_ms,
+                        metrics.worker_total_ms,
+                        metrics.inference_ms,
+                        metrics.cache_hit
+                    );
+                    return Ok(RemoteReply {
+                        segments,
+                        language,
+                        queue_ms: metrics.queue_wait_ms,
+                        inference_ms: metrics.inference_ms,
+                    });
+                }
+                StreamEvent::Error { code, .. } if code == "cancelled" => {
+                    return Err(MpvSttError::SttCancelled);
+                }
+                StreamEvent::Error { code, message } => {
+                    return Err(MpvSttError::SttFailed(format!(
+                        "Server error ({}): {}",
+                        code, message
+                    )));
+                }
+            }
+        }
+        Err(MpvSttError::SttFailed(
+            "Server stream ended without a summary".to_string(),
+        ))
+    }
 
-        let path_ref = audio_path.as_ref();
-        let mut reader = WavReader::open(path_ref)
-            .map_err(|e| MpvSttError::SttFailed(format!("Failed to read WAV: {}", e)))?;
+    fn compress_audio(&self, audio: &PcmBuffer) -> Result<Vec<u8>> {
+        check_audio_format(audio)?;
 
-        let spec = reader.spec();
-        if spec.channels != 1 || spec.sample_rate != 16000 || spec.bits_per_sample != 16 {
-            return Err(MpvSttError::SttFailed(format!(
-                "Unsupported WAV format: {}ch {}Hz {}-bit",
-                spec.channels, spec.sample_rate, spec.bits_per_sample
-            )));
+        if !self.config.use_opus {
+            return audio.to_wav_bytes();
         }
 
-        if !self.config.use_opus {
-            let bytes = std::fs::read(path_ref)
-                .map_err(|e| MpvSttError::SttFailed(format!("Failed to read WAV bytes: {}", e)))?;
-            return Ok(bytes);
+        if audio.samples.is_empty() {
+            return Err(MpvSttError::SttFailed("Audio data is empty".to_string()));
         }
 
-        // Encode to Opus (mono, 16 kHz, 20 ms frames; framing: [u32_le_len][packet]...)
         let mut encoder = SimpleOpusEncoder::new()
             .map_err(|e| MpvSttError::SttFailed(format!("Opus encoder init failed: {e}")))?;
+        match self.config.opus_container {
+            OpusContainer::Ogg => encode_ogg_opus(&mut encoder, &audio.samples),
+            OpusContainer::Framed => encode_opus(&mut encoder, &audio.samples),
+        }
+    }
+}
 
-        let frame_size = SimpleOpusEncoder::FRAME_SIZE as usize; // 20 ms @ 16 kHz
-        let mut pcm: Vec<i16> = reader
-            .samples::<i16>()
-            .collect::<std::result::Result<_, _>>()
-            .map_err(|e| MpvSttError::SttFailed(format!("Read WAV samples failed: {}", e)))?;
+fn check_audio_format(audio: &PcmBuffer) -> Result<()> {
+    if audio.channels != 1 || audio.sample_rate != 16000 {
+        return Err(MpvSttError::SttFailed(format!(
+            "Unsupported audio format: {}ch {}Hz",
+            audio.channels, audio.sample_rate
+        )));
+    }
+    Ok(())
+}
 
-        if pcm.is_empty() {
-            return Err(MpvSttError::SttFailed("Audio data is empty".to_string()));
-        }
+/// Encode to Opus (mono, 16 kHz, 20 ms frames; framing: [u32_le_len][packet]...)
+fn encode_opus(encoder: &mut SimpleOpusEncoder, samples: &[i16]) -> Result<Vec<u8>> {
+    let frame_size = SimpleOpusEncoder::FRAME_SIZE as usize; // 20 ms @ 16 kHz
+    let mut pcm = samples.to_vec();
 
-        // Pad last frame with zeros if not aligned.
-        let rem = pcm.len() % frame_size;
-        if rem != 0 {
-            pcm.extend(std::iter::repeat(0).take(frame_size - rem));
-        }
+    // Pad last frame with zeros if not aligned.
+    let rem = pcm.len() % frame_size;
+    if rem != 0 {
+        pcm.extend(std::iter::repeat_n(0, frame_size - rem));
+    }
 
-        let mut encoded = Vec::with_capacity(pcm.len() / 2);
-        let mut out_buf = vec![0u8; 4000]; // generous per-frame buffer
+    let mut encoded = Vec::with_capacity(pcm.len() / 2);
+    let mut out_buf = vec![0u8; 4000]; // generous per-frame buffer
 
-        for chunk in pcm.chunks(frame_size) {
-            let len = encoder
-                .encode(chunk, &mut out_buf)
-                .map_err(|e| MpvSttError::SttFailed(format!("Opus encode failed: {e}")))?;
-            encoded.extend_from_slice(&(len as u32).to_le_bytes());
-            encoded.extend_from_slice(&out_buf[..len]);
-        }
+    for chunk in pcm.chunks(frame_size) {
+        let len = encoder
+            .encode(chunk, &mut out_buf)
+            .map_err(|e| MpvSttError::SttFailed(format!("Opus encode failed: {e}")))?;
+        encoded.extend_from_slice(&(len as u32).to_le_bytes());
+        encoded.extend_from_slice(&out_buf[..len]);
+    }
+
+    Ok(encoded)
+}
 
-        Ok(encoded)
+/// Encode to a standard Ogg Opus file (mono, 16 kHz input, 20 ms frames).
+fn encode_ogg_opus(encoder: &mut SimpleOpusEncoder, samples: &[i16]) -> Result<Vec<u8>> {
+    let frame_size = SimpleOpusEncoder::FRAME_SIZE as usize;
+    let rate_ratio = GRANULE_RATE / SimpleOpusEncoder::SAMPLE_RATE as u32;
+    let lookahead = encoder
+        .lookahead()
+        .map_err(|e| MpvSttError::SttFailed(format!("Opus encoder query failed: {e}")))?;
+
+    // Feed the encoder delay's worth of extra silence so the tail survives pre-skip.
+    let mut pcm = samples.to_vec();
+    let padded_len = (pcm.len() + lookahead).div_ceil(frame_size) * frame_size;
+    pcm.resize(padded_len, 0);
+
+    let serial = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let mut writer = OggOpusWriter::new(
+        SimpleOpusEncoder::CHANNELS as u8,
+        SimpleOpusEncoder::SAMPLE_RATE as u32,
+        (lookahead as u32 * rate_ratio) as u16,
+        serial,
+    );
+    let mut out_buf = vec![0u8; 4000];
+    for chunk in pcm.chunks(frame_size) {
+        let len = encoder
+            .encode(chunk, &mut out_buf)
+            .map_err(|e| MpvSttError::SttFailed(format!("Opus encode failed: {e}")))?;
+        writer.push_packet(&out_buf[..len], (frame_size as u32 * rate_ratio) as u64);
     }
+
+    Ok(writer.finish(samples.len() as u64))
 }
 
 // Minimal safe wrapper around opusic-sys encoder.
@@ -353,6 +808,22 @@ impl SimpleOpusEncoder {
         Ok(Self { enc })
     }
 
+    /// Encoder delay in samples at `SAMPLE_RATE`; decoders skip this much output.
+    fn lookahead(&self) -> std::result::Result<usize, String> {
+        let mut lookahead: i32 = 0;
+        let ret = unsafe {
+            opus::opus_encoder_ctl(
+                self.enc,
+                opus::OPUS_GET_LOOKAHEAD_REQUEST,
+                &mut lookahead as *mut i32,
+            )
+        };
+        if ret != opus::OPUS_OK {
+            return Err(format!("OPUS_GET_LOOKAHEAD failed: {}", opus_error(ret)));
+        }
+        Ok(lookahead.max(0) as usize)
+    }
+
     fn encode(&mut self, pcm: &[i16], out: &mut [u8]) -> std::result::Result<usize, String> {
         if pcm.len() != Self::FRAME_SIZE as usize {
             return Err(format!(
@@ -394,6 +865,54 @@ fn opus_error(code: libc::c_int) -> String {
     }
 }
 
+/// Request headers for the server's per-request options; defaults are left out.
+fn option_headers(options: &TranscriptionOptions) -> Vec<(&'static str, String)> {
+    let mut headers = Vec::new();
+    if let Some(language) = &options.language {
+        headers.push((HEADER_LANGUAGE, language.clone()));
+    }
+    if options.translate {
+        headers.push((HEADER_TASK, "translate".to_string()));
+    }
+    if let Some(prompt) = &options.prompt {
+        // Header values must be visible ASCII.
+        let encoded = utf8_percent_encode(prompt, NON_ALPHANUMERIC).to_string();
+        headers.push((HEADER_PROMPT, encoded));
+    }
+    if let Some(temperature) = options.temperature {
+        headers.push((HEADER_TEMPERATURE, temperature.to_string()));
+    }
+    if options.word_timestamps {
+        headers.push((HEADER_WORD_TIMESTAMPS, "true".to_string()));
+    }
+    headers
+}
+
+/// A reply in the original format: SRT text, with language and timings in headers and no
+/// `duration_ms`.
+fn srt_reply(data: &[u8], headers: &HeaderMap) -> Result<TranscriptionResponse> {
+    let segments = if data.iter().all(|b| b.is_ascii_whitespace()) {
+        debug!("Remote HTTP STT returned empty subtitles; skipping SRT parse");
+        Vec::new()
+    } else {
+        SrtFile::parse_content(&String::from_utf8_lossy(data))?.segments()
+    };
+    Ok(TranscriptionResponse {
+        segments,
+        language: headers
+            .get(HEADER_LANGUAGE)
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string),
+        duration_ms: 0,
+        metrics: JobMetrics {
+            queue_wait_ms: parse_u64_header(headers, HEADER_QUEUE_MS),
+            inference_ms: parse_u64_header(headers, HEADER_INFER_MS),
+            worker_total_ms: parse_u64_header(headers, HEADER_WORKER_MS),
+            cache_hit: false,
+        },
+    })
+}
+
 fn parse_u64_header(headers: &HeaderMap, name: &str) -> u64 {
     headers
         .get(name)
@@ -402,6 +921,21 @@ fn parse_u64_header(headers: &HeaderMap, name: &str) -> u64 {
         .unwrap_or(0)
 }
 
+/// 128 random bits, hex-encoded; `RandomState` is seeded from the OS.
+fn new_cancel_token() -> String {
+    let random = || RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", random(), random())
+}
+
+/// `Retry-After` in its delay-seconds form; HTTP dates are not sent by our server.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
 fn normalize_server_url(raw: &str) -> String {
     if raw.starts_with("http://") || raw.starts_with("https://") {
         raw.to_string()
@@ -415,17 +949,40 @@ impl SttBackend for RemoteHttpBackend {
         BackendKind::RemoteHttp
     }
 
-    fn transcribe<P: AsRef<Path>>(
+    /// Per-call options override the ones from `[stt.remote_http]`.
+    fn transcribe_with_options(
+        &mut self,
+        audio: &PcmBuffer,
+        duration_ms: u64,
+        options: &TranscriptionOptions,
+    ) -> Result<Transcript> {
+        self.transcribe_impl(audio, duration_ms, options, None)
+    }
+
+    /// Asks the server for an NDJSON stream so segments arrive while inference runs.
+    fn transcribe_streaming(
         &mut self,
-        audio_path: P,
-        output_prefix: P,
+        audio: &PcmBuffer,
         duration_ms: u64,
-    ) -> Result<()> {
-        self.transcribe_impl(audio_path, output_prefix, duration_ms)
+        options: &TranscriptionOptions,
+        sink: &SegmentSink,
+    ) -> Result<Transcript> {
+        // A retry after a broken stream is a new request that starts over; do not repeat
+        // delivered segments.
+        self.transcribe_impl(audio, duration_ms, options, Some(&sink.deduplicated()))
     }
 
     fn cancel_inflight(&self) {
-        self.cancel_generation.fetch_add(1, Ordering::Relaxed);
+        self.canceller().cancel();
+    }
+
+    fn cancel_handle(&self) -> SttCancelHandle {
+        let canceller = self.canceller();
+        SttCancelHandle::new(move || canceller.cancel())
+    }
+
+    fn set_priority(&mut self, priority: JobPriority) {
+        self.priority = priority;
     }
 
     fn take_device_notice(&mut self) -> Option<SttDeviceNotice> {
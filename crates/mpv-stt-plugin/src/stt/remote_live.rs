@@ -0,0 +1,201 @@
+//! Blocking client for the server's `/live` WebSocket, used when
+//! `[stt.remote_http] transport = "websocket"`.
+//!
+//! One session is kept open across chunks. Each chunk is streamed as binary audio
+//! messages followed by a flush; the server's `flushed` reply marks the end of the
+//! chunk and tells us where the next one starts in stream time.
+
+use log::{debug, trace};
+use mpv_stt_common::{MpvSttError, Result, SegmentSink, TranscriptSegment};
+use mpv_stt_protocol::{LiveControl, LiveEvent};
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::HeaderValue;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Connector, Message, WebSocket};
+
+pub(super) struct LiveConnection {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    /// Stream time at which the next chunk starts, as reported by the last flush.
+    stream_ms: u64,
+    /// Option headers the session was opened with; they apply to the whole session.
+    options: Vec<(&'static str, String)>,
+}
+
+impl LiveConnection {
+    /// Open `/live`; reads time out every `poll` so the caller can notice cancellation.
+    pub(super) fn connect(
+        server_url: &str,
+        auth_token: &str,
+        compression: &'static str,
+        options: Vec<(&'static str, String)>,
+        tls: Arc<rustls::ClientConfig>,
+        poll: Duration,
+    ) -> Result<Self> {
+        let url = format!("{}/live", websocket_url(server_url));
+        let mut request = url
+            .as_str()
+            .into_client_request()
+            .map_err(|e| MpvSttError::SttFailed(format!("Invalid live URL {}: {}", url, e)))?;
+        let headers = request.headers_mut();
+        headers.insert(
+            super::remote_http::HEADER_AUTH_TOKEN,
+            HeaderValue::from_str(auth_token)
+                .map_err(|e| MpvSttError::SttFailed(format!("Invalid auth token: {}", e)))?,
+        );
+        headers.insert(
+            super::remote_http::HEADER_COMPRESSION,
+            HeaderValue::from_static(compression),
+        );
+        for (name, value) in &options {
+            headers.insert(
+                *name,
+                HeaderValue::from_str(value).map_err(|e| {
+                    MpvSttError::SttFailed(format!("Invalid {} header: {}", name, e))
+                })?,
+            );
+        }
+
+        let uri = request.uri();
+        let host = uri.host().unwrap_or_default().trim_matches(['[', ']']);
+        let port = uri
+            .port_u16()
+            .unwrap_or(if uri.scheme_str() == Some("wss") {
+                443
+            } else {
+                80
+            });
+        let stream = TcpStream::connect((host, port))?;
+        let (socket, _) = tungstenite::client_tls_with_config(
+            request,
+            stream,
+            None,
+            Some(Connector::Rustls(tls)),
+        )
+        .map_err(|e| MpvSttError::SttFailed(format!("Live connect to {} failed: {}", url, e)))?;
+        // Only after the handshake, which does not tolerate read timeouts.
+        match socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(poll))?,
+            MaybeTlsStream::Rustls(stream) => stream.sock.set_read_timeout(Some(poll))?,
+            _ => {
+                return Err(MpvSttError::SttFailed(
+                    "Unsupported live stream type".to_string(),
+                ));
+            }
+        }
+        debug!("Live session opened at {}", url);
+
+        Ok(Self {
+            socket,
+            stream_ms: 0,
+            options,
+        })
+    }
+
+    pub(super) fn options(&self) -> &[(&'static str, String)] {
+        &self.options
+    }
+
+    /// Send one chunk's audio messages and flush; returns its final segments relative to
+    /// the chunk start. `cancelled` is polled while waiting for the server.
+    pub(super) fn transcribe(
+        &mut self,
+        audio: Vec<Vec<u8>>,
+        deadline: Instant,
+        cancelled: &dyn Fn() -> bool,
+        sink: Option<&SegmentSink>,
+    ) -> Result<Vec<TranscriptSegment>> {
+        let chunk_start_ms = self.stream_ms;
+        for data in audio {
+            self.socket
+                .send(Message::Binary(data))
+                .map_err(live_error)?;
+        }
+        let flush = serde_json::to_string(&LiveControl::Flush)
+            .map_err(|e| MpvSttError::SttFailed(format!("Live flush encode failed: {}", e)))?;
+        self.socket.send(Message::Text(flush)).map_err(live_error)?;
+
+        let mut segments = Vec::new();
+        let mut failure = None;
+        loop {
+            if cancelled() {
+                return Err(MpvSttError::SttCancelled);
+            }
+            if Instant::now() >= deadline {
+                return Err(MpvSttError::SttFailed(
+                    "Live transcription timed out".to_string(),
+                ));
+            }
+
+            let text = match self.socket.read() {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Close(_)) => {
+                    return Err(MpvSttError::SttFailed(
+                        "Server closed the live session".to_string(),
+                    ));
+                }
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(live_error(e)),
+            };
+            let event: LiveEvent = serde_json::from_str(&text).map_err(|e| {
+                MpvSttError::SttFailed(format!("Invalid live event '{}': {}", text, e))
+            })?;
+
+            match event {
+                LiveEvent::Partial { segments } => {
+                    trace!("Live partial with {} segments", segments.len());
+                }
+                LiveEvent::Final { segments: finals } => {
+                    for mut segment in finals {
+                        segment.start_ms = segment.start_ms.saturating_sub(chunk_start_ms);
+                        segment.end_ms = segment.end_ms.saturating_sub(chunk_start_ms);
+                        if let Some(sink) = sink {
+                            sink.emit(&segment);
+                        }
+                        segments.push(segment);
+                    }
+                }
+                // Keep reading: the server still answers the flush after a failed window.
+                LiveEvent::Error { code, message } => {
+                    failure = Some(MpvSttError::SttFailed(format!(
+                        "Server error ({}): {}",
+                        code, message
+                    )));
+                }
+                LiveEvent::Flushed { until_ms } => {
+                    self.stream_ms = until_ms;
+                    return match failure {
+                        Some(e) => Err(e),
+                        None => Ok(segments),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl Drop for LiveConnection {
+    fn drop(&mut self) {
+        let _ = self.socket.close(None);
+    }
+}
+
+fn live_error(e: tungstenite::Error) -> MpvSttError {
+    MpvSttError::SttFailed(format!("Live session error: {}", e))
+}
+
+/// `http://host` → `ws://host`, `https://host` → `wss://host`.
+fn websocket_url(server_url: &str) -> String {
+    match server_url.strip_prefix("http") {
+        Some(rest) => format!("ws{}", rest),
+        None => server_url.to_string(),
+    }
+}
@@ -0,0 +1,188 @@
+//! TLS client setup shared by the HTTP and live transports.
+//!
+//! Servers are verified against the built-in web roots unless `tls_ca_path` pins a CA
+//! or `tls_cert_sha256` pins the exact server certificate (useful for self-signed
+//! ones). `tls_client_cert_path`/`tls_client_key_path` add a client certificate for
+//! servers that require one.
+
+use super::remote_http::RemoteSttConfig;
+use mpv_stt_common::{MpvSttError, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, ring, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+pub(super) fn client_config(config: &RemoteSttConfig) -> Result<Arc<ClientConfig>> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(tls_error)?;
+
+    let builder = match (
+        config.tls_ca_path.is_empty(),
+        config.tls_cert_sha256.is_empty(),
+    ) {
+        (true, true) => builder.with_root_certificates(RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        }),
+        (false, true) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(&config.tls_ca_path)? {
+                roots.add(cert).map_err(tls_error)?;
+            }
+            builder.with_root_certificates(roots)
+        }
+        (true, false) => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(FingerprintVerifier {
+                fingerprint: parse_fingerprint(&config.tls_cert_sha256)?,
+                provider,
+            })),
+        (false, false) => {
+            return Err(MpvSttError::SttFailed(
+                "Set either tls_ca_path or tls_cert_sha256, not both".to_string(),
+            ));
+        }
+    };
+
+    let tls = match (
+        config.tls_client_cert_path.is_empty(),
+        config.tls_client_key_path.is_empty(),
+    ) {
+        (true, true) => builder.with_no_client_auth(),
+        (false, false) => {
+            let key = PrivateKeyDer::from_pem_file(&config.tls_client_key_path).map_err(|e| {
+                MpvSttError::SttFailed(format!(
+                    "Reading client key {} failed: {}",
+                    config.tls_client_key_path, e
+                ))
+            })?;
+            builder
+                .with_client_auth_cert(load_certs(&config.tls_client_cert_path)?, key)
+                .map_err(tls_error)?
+        }
+        _ => {
+            return Err(MpvSttError::SttFailed(
+                "tls_client_cert_path and tls_client_key_path must be set together".to_string(),
+            ));
+        }
+    };
+    Ok(Arc::new(tls))
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let read_error = |e: rustls::pki_types::pem::Error| {
+        MpvSttError::SttFailed(format!("Reading {} failed: {}", path, e))
+    };
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(read_error)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(read_error)?;
+    if certs.is_empty() {
+        return Err(MpvSttError::SttFailed(format!(
+            "{} contains no certificates",
+            path
+        )));
+    }
+    Ok(certs)
+}
+
+/// Hex SHA-256, as printed by `openssl x509 -fingerprint -sha256` (colons optional).
+fn parse_fingerprint(raw: &str) -> Result<[u8; 32]> {
+    let digits: String = raw
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect();
+    hex::decode(&digits)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| {
+            MpvSttError::SttFailed(format!("Invalid tls_cert_sha256 fingerprint '{}'", raw))
+        })
+}
+
+fn tls_error(e: impl std::fmt::Display) -> MpvSttError {
+    MpvSttError::SttFailed(format!("TLS setup failed: {}", e))
+}
+
+/// Accepts exactly one server certificate; names and chains are not checked, but the
+/// handshake signatures still are, so the server must hold the matching key.
+#[derive(Debug)]
+struct FingerprintVerifier {
+    fingerprint: [u8; 32],
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        if Sha256::digest(end_entity.as_ref()).as_slice() == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fingerprint_accepts_openssl_format() {
+        let openssl = (0..32)
+            .map(|i| format!("{:02X}", i))
+            .collect::<Vec<_>>()
+            .join(":");
+        let expected: [u8; 32] = std::array::from_fn(|i| i as u8);
+        assert_eq!(parse_fingerprint(&openssl).unwrap(), expected);
+        assert_eq!(parse_fingerprint(&hex::encode(expected)).unwrap(), expected);
+        assert!(parse_fingerprint("ab:cd").is_err());
+        assert!(parse_fingerprint("not hex").is_err());
+    }
+}
@@ -1,8 +1,15 @@
-use mpv_stt_common::{MpvSttError, Result};
+use mpv_stt_common::{
+    MpvSttError, PcmBuffer, Result, SegmentSink, Transcript, TranscriptSegment,
+    TranscriptionOptions,
+};
 use mpv_stt_crypto::EncryptionKey;
 use serde::{Deserialize, Serialize};
 use std::time::Instant;
 
+mod ogg_opus;
+
+pub use ogg_opus::{GRANULE_RATE, OggOpusStream, OggOpusWriter};
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum CompressionFormat {
     Opus,
@@ -19,10 +26,6 @@ pub enum Message {
         auth_token: [u8; 32],
         compression: CompressionFormat,
     },
-    Cancel {
-        request_id: u64,
-        auth_token: [u8; 32],
-    },
     Result {
         request_id: u64,
         chunk_index: u32,
@@ -39,7 +42,6 @@ impl Message {
     pub fn request_id(&self) -> u64 {
         match self {
             Message::AudioChunk { request_id, .. }
-            | Message::Cancel { request_id, .. }
             | Message::Result { request_id, .. }
             | Message::Error { request_id, .. } => *request_id,
         }
@@ -47,9 +49,7 @@ impl Message {
 
     pub fn auth_token(&self) -> Option<&[u8; 32]> {
         match self {
-            Message::AudioChunk { auth_token, .. } | Message::Cancel { auth_token, .. } => {
-                Some(auth_token)
-            }
+            Message::AudioChunk { auth_token, .. } => Some(auth_token),
             _ => None,
         }
     }
@@ -77,26 +77,97 @@ impl Message {
     }
 }
 
+/// Scheduling class of a job; workers always take the highest class waiting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPriority {
+    /// Whole-file and archive work that runs when nothing else is waiting.
+    Batch,
+    /// Lookahead chunks; a running prefetch job gives way to a realtime one.
+    Prefetch,
+    /// Audio a viewer is about to see subtitles for.
+    #[default]
+    Realtime,
+}
+
+impl JobPriority {
+    /// Every class, highest first.
+    pub const ALL: [JobPriority; 3] = [
+        JobPriority::Realtime,
+        JobPriority::Prefetch,
+        JobPriority::Batch,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "realtime" => Some(Self::Realtime),
+            "prefetch" => Some(Self::Prefetch),
+            "batch" => Some(Self::Batch),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Realtime => "realtime",
+            Self::Prefetch => "prefetch",
+            Self::Batch => "batch",
+        }
+    }
+
+    /// Whether a running job of this class may be interrupted for a realtime one.
+    pub fn is_preemptible(self) -> bool {
+        self == Self::Prefetch
+    }
+}
+
 #[derive(Debug)]
 pub struct TranscriptionJob {
     pub request_id: u64,
-    pub audio_data: Vec<u8>,
+    /// Name of the hosted model that transcribes the job.
+    pub model: String,
+    /// Decoded 16 kHz mono PCM.
+    pub audio: PcmBuffer,
     pub duration_ms: u64,
+    pub options: TranscriptionOptions,
+    pub priority: JobPriority,
+    /// Receives segments while the job runs, for streamed responses.
+    pub on_segment: Option<SegmentSink>,
     /// Timestamp recorded when the request is accepted by the HTTP handler.
     pub enqueue_at: Instant,
+    /// The client gives up at this point; the job is dropped or aborted once it passes.
+    pub deadline: Option<Instant>,
 }
 
 #[derive(Debug)]
 pub enum JobResult {
     Success {
         request_id: u64,
-        srt_data: Vec<u8>,
+        transcript: Transcript,
         metrics: JobMetrics,
     },
     Error { request_id: u64, message: String },
+    /// The job was cancelled before it produced a result.
+    Cancelled { request_id: u64 },
+    /// The job's deadline passed while it was queued or running.
+    Expired {
+        request_id: u64,
+        metrics: JobMetrics,
+    },
+}
+
+impl JobResult {
+    pub fn request_id(&self) -> u64 {
+        match self {
+            JobResult::Success { request_id, .. }
+            | JobResult::Error { request_id, .. }
+            | JobResult::Cancelled { request_id }
+            | JobResult::Expired { request_id, .. } => *request_id,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct JobMetrics {
     /// Time from enqueue to worker picking up the job.
     pub queue_wait_ms: u64,
@@ -104,4 +175,70 @@ pub struct JobMetrics {
     pub inference_ms: u64,
     /// End-to-end time inside worker thread (queue wait + inference + post).
     pub worker_total_ms: u64,
+    /// Served from the server's result cache; the timings are then zero.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cache_hit: bool,
+}
+
+/// Content type of a streamed `/transcribe` response: one JSON [`StreamEvent`] per line.
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Content type of a `/transcribe` response holding one [`TranscriptionResponse`].
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// `/transcribe` reply for clients sending `Accept: application/json`, in place of SRT
+/// text and the `x-metric-*` headers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptionResponse {
+    /// Timed relative to the start of the submitted audio.
+    pub segments: Vec<TranscriptSegment>,
+    pub language: Option<String>,
+    pub duration_ms: u64,
+    pub metrics: JobMetrics,
+}
+
+/// One line of a streamed transcription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// A finished segment, timed relative to the start of the submitted audio.
+    Segment(TranscriptSegment),
+    /// Last event of a successful stream.
+    Done {
+        language: Option<String>,
+        duration_ms: u64,
+        metrics: JobMetrics,
+    },
+    /// The job failed after the stream had started; `code` matches the server's error kinds.
+    Error { code: String, message: String },
+}
+
+/// Server → client message on the `/live` WebSocket; timestamps are absolute stream time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// Hypothesis for the audio after the last final segment; replaces the previous partial.
+    Partial {
+        segments: Vec<TranscriptSegment>,
+    },
+    /// Segments that will not be revised.
+    Final {
+        segments: Vec<TranscriptSegment>,
+    },
+    /// All audio up to `until_ms` has been finalised; answers [`LiveControl::Flush`].
+    Flushed {
+        until_ms: u64,
+    },
+    Error {
+        code: String,
+        message: String,
+    },
+}
+
+/// Client → server text message on the `/live` WebSocket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveControl {
+    /// Finalise everything received so far, then answer with [`LiveEvent::Flushed`].
+    Flush,
 }
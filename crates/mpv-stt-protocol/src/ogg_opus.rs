@@ -0,0 +1,357 @@
+//! Ogg Opus container (RFC 7845) for `x-compression: ogg-opus` uploads.
+//!
+//! Only the container lives here; encoding and decoding the Opus packets is left to the
+//! plugin and the server, which link libopus themselves.
+
+use mpv_stt_common::{MpvSttError, Result};
+
+/// Granule positions of Opus streams always count 48 kHz samples.
+pub const GRANULE_RATE: u32 = 48_000;
+
+const CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+const PAGE_HEADER_LEN: usize = 27;
+const MAX_SEGMENTS: usize = 255;
+const FLAG_CONTINUED: u8 = 0x01;
+const FLAG_BOS: u8 = 0x02;
+const FLAG_EOS: u8 = 0x04;
+/// Pages without a completed packet carry this granule position.
+const NO_GRANULE: u64 = u64::MAX;
+// Flush a page once it holds this much audio so a lost page costs little.
+const TARGET_PAGE_BYTES: usize = 4096;
+const VENDOR: &str = concat!("mpv-stt ", env!("CARGO_PKG_VERSION"));
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Ogg's CRC-32: polynomial 0x04c11db7, no reflection, zero initial value.
+fn page_crc(page: &[u8]) -> u32 {
+    page.iter().fold(0u32, |crc, byte| {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+fn invalid(message: impl Into<String>) -> MpvSttError {
+    MpvSttError::OggOpus(message.into())
+}
+
+/// Writes Opus packets as a single-stream Ogg Opus file.
+pub struct OggOpusWriter {
+    out: Vec<u8>,
+    serial: u32,
+    sequence: u32,
+    input_sample_rate: u32,
+    pre_skip: u16,
+    /// Granule position after the last packet pushed.
+    granule: u64,
+    /// Granule position of the last packet completed on the page being filled.
+    page_granule: u64,
+    lacing: Vec<u8>,
+    body: Vec<u8>,
+    /// The page being filled starts with the tail of a packet from the previous page.
+    continued: bool,
+}
+
+impl OggOpusWriter {
+    /// Start a stream; `pre_skip` is the encoder lookahead in 48 kHz samples.
+    pub fn new(channels: u8, input_sample_rate: u32, pre_skip: u16, serial: u32) -> Self {
+        let mut writer = Self {
+            out: Vec::new(),
+            serial,
+            sequence: 0,
+            input_sample_rate,
+            pre_skip,
+            granule: 0,
+            page_granule: NO_GRANULE,
+            lacing: Vec::new(),
+            body: Vec::new(),
+            continued: false,
+        };
+
+        let mut head = Vec::with_capacity(19);
+        head.extend_from_slice(b"OpusHead");
+        head.push(1);
+        head.push(channels);
+        head.extend_from_slice(&pre_skip.to_le_bytes());
+        head.extend_from_slice(&input_sample_rate.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes());
+        // Channel mapping family 0: mono or stereo.
+        head.push(0);
+        writer.add_packet(&head);
+        writer.flush_page(FLAG_BOS);
+
+        let mut tags = Vec::new();
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+        tags.extend_from_slice(VENDOR.as_bytes());
+        tags.extend_from_slice(&0u32.to_le_bytes());
+        writer.add_packet(&tags);
+        writer.flush_page(0);
+
+        writer
+    }
+
+    /// Append one Opus packet that decodes to `samples_48k` samples at 48 kHz.
+    pub fn push_packet(&mut self, packet: &[u8], samples_48k: u64) {
+        self.granule += samples_48k;
+        self.add_packet(packet);
+        if self.body.len() >= TARGET_PAGE_BYTES {
+            self.flush_page(0);
+        }
+    }
+
+    /// End the stream after `input_samples` samples per channel at the input rate; the
+    /// last granule position trims the encoder's padding of the final frame.
+    pub fn finish(mut self, input_samples: u64) -> Vec<u8> {
+        let end = self.pre_skip as u64
+            + input_samples * GRANULE_RATE as u64 / self.input_sample_rate.max(1) as u64;
+        self.page_granule = end.min(self.granule.max(self.pre_skip as u64));
+        self.flush_page(FLAG_EOS);
+        self.out
+    }
+
+    fn add_packet(&mut self, mut packet: &[u8]) {
+        let mut started = false;
+        loop {
+            if self.lacing.len() == MAX_SEGMENTS {
+                // A packet that spills over is reported on the page where it ends.
+                self.flush_page(0);
+                self.continued = started;
+            }
+            let len = packet.len().min(255);
+            self.lacing.push(len as u8);
+            self.body.extend_from_slice(&packet[..len]);
+            packet = &packet[len..];
+            started = true;
+            // A lacing value below 255 ends the packet.
+            if len < 255 {
+                self.page_granule = self.granule;
+                return;
+            }
+        }
+    }
+
+    fn flush_page(&mut self, flags: u8) {
+        let flags = if self.continued {
+            flags | FLAG_CONTINUED
+        } else {
+            flags
+        };
+        let start = self.out.len();
+        self.out.extend_from_slice(CAPTURE_PATTERN);
+        self.out.push(0);
+        self.out.push(flags);
+        self.out.extend_from_slice(&self.page_granule.to_le_bytes());
+        self.out.extend_from_slice(&self.serial.to_le_bytes());
+        self.out.extend_from_slice(&self.sequence.to_le_bytes());
+        self.out.extend_from_slice(&0u32.to_le_bytes());
+        self.out.push(self.lacing.len() as u8);
+        self.out.append(&mut self.lacing);
+        self.out.append(&mut self.body);
+
+        let crc = page_crc(&self.out[start..]);
+        self.out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+        self.sequence += 1;
+        self.page_granule = NO_GRANULE;
+        self.continued = false;
+    }
+}
+
+/// The audio packets and stream parameters of an Ogg Opus file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OggOpusStream {
+    pub channels: u8,
+    /// Decoded samples (48 kHz) to discard from the start.
+    pub pre_skip: u16,
+    /// Sample rate of the original input; informational only.
+    pub input_sample_rate: u32,
+    pub packets: Vec<Vec<u8>>,
+    /// Granule position of the last page, when the stream has one.
+    pub final_granule: Option<u64>,
+}
+
+impl OggOpusStream {
+    /// Demultiplex the first logical stream of an Ogg file, checking page checksums.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut packets: Vec<Vec<u8>> = Vec::new();
+        let mut partial: Option<Vec<u8>> = None;
+        let mut serial = None;
+        let mut final_granule = None;
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let header = data
+                .get(pos..pos + PAGE_HEADER_LEN)
+                .ok_or_else(|| invalid("truncated page header"))?;
+            if &header[..4] != CAPTURE_PATTERN {
+                return Err(invalid(format!("missing page at byte {}", pos)));
+            }
+            if header[4] != 0 {
+                return Err(invalid(format!("unsupported Ogg version {}", header[4])));
+            }
+            let flags = header[5];
+            let granule = u64::from_le_bytes(header[6..14].try_into().expect("8 bytes"));
+            let page_serial = u32::from_le_bytes(header[14..18].try_into().expect("4 bytes"));
+            let expected_crc = u32::from_le_bytes(header[22..26].try_into().expect("4 bytes"));
+            let segments = header[26] as usize;
+            let lacing = data
+                .get(pos + PAGE_HEADER_LEN..pos + PAGE_HEADER_LEN + segments)
+                .ok_or_else(|| invalid("truncated segment table"))?;
+            let body_len: usize = lacing.iter().map(|len| *len as usize).sum();
+            let page_len = PAGE_HEADER_LEN + segments + body_len;
+            let page = data
+                .get(pos..pos + page_len)
+                .ok_or_else(|| invalid("truncated page"))?;
+
+            let mut unchecked = page.to_vec();
+            unchecked[22..26].fill(0);
+            if page_crc(&unchecked) != expected_crc {
+                return Err(invalid(format!(
+                    "checksum mismatch in page at byte {}",
+                    pos
+                )));
+            }
+            pos += page_len;
+
+            // Other multiplexed streams (e.g. video) are skipped.
+            if *serial.get_or_insert(page_serial) != page_serial {
+                continue;
+            }
+            if flags & FLAG_CONTINUED == 0 && partial.is_some() {
+                return Err(invalid("packet interrupted by a new page"));
+            }
+
+            let mut body = &page[PAGE_HEADER_LEN + segments..];
+            for len in lacing {
+                let len = *len as usize;
+                partial
+                    .get_or_insert_with(Vec::new)
+                    .extend_from_slice(&body[..len]);
+                body = &body[len..];
+                if len < 255 {
+                    packets.extend(partial.take());
+                }
+            }
+            if granule != NO_GRANULE {
+                final_granule = Some(granule);
+            }
+            if flags & FLAG_EOS != 0 {
+                break;
+            }
+        }
+
+        let mut packets = packets.into_iter();
+        let head = packets.next().ok_or_else(|| invalid("empty stream"))?;
+        if head.len() < 19 || !head.starts_with(b"OpusHead") {
+            return Err(invalid("first packet is not an OpusHead"));
+        }
+        if head[8] & 0xf0 != 0 {
+            return Err(invalid(format!("unsupported OpusHead version {}", head[8])));
+        }
+        let channels = head[9];
+        if head[18] != 0 || !(1..=2).contains(&channels) {
+            return Err(invalid(format!(
+                "unsupported channel mapping (family {}, {} channels)",
+                head[18], channels
+            )));
+        }
+        if !packets
+            .next()
+            .is_some_and(|tags| tags.starts_with(b"OpusTags"))
+        {
+            return Err(invalid("second packet is not an OpusTags"));
+        }
+
+        Ok(Self {
+            channels,
+            pre_skip: u16::from_le_bytes([head[10], head[11]]),
+            input_sample_rate: u32::from_le_bytes(head[12..16].try_into().expect("4 bytes")),
+            packets: packets.collect(),
+            final_granule,
+        })
+    }
+
+    /// Number of samples at `sample_rate` that remain once pre-skip is discarded and the
+    /// last page's granule position has trimmed the end padding; `None` without a granule.
+    pub fn playable_samples(&self, sample_rate: u32) -> Option<u64> {
+        let granule = self.final_granule?;
+        let samples_48k = granule.saturating_sub(self.pre_skip as u64);
+        Some(samples_48k * sample_rate as u64 / GRANULE_RATE as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc_matches_reference() {
+        // The CRC-32/POSIX check value without its final inversion.
+        assert_eq!(page_crc(b""), 0);
+        assert_eq!(page_crc(b"123456789"), 0x89a1_897f);
+    }
+
+    #[test]
+    fn test_roundtrip_keeps_packets_and_granule() {
+        let packets: Vec<Vec<u8>> = (0..600u32)
+            .map(|i| vec![(i % 251) as u8; 20 + (i as usize * 7) % 400])
+            .collect();
+        let mut writer = OggOpusWriter::new(1, 16_000, 312, 0x1234_5678);
+        for packet in &packets {
+            writer.push_packet(packet, 960);
+        }
+        // 600 frames of 20 ms hold the input, the encoder delay and the last frame's padding.
+        let input_samples = 600 * 320 - 200;
+        let data = writer.finish(input_samples);
+        assert!(data.starts_with(b"OggS"));
+
+        let stream = OggOpusStream::parse(&data).unwrap();
+        assert_eq!(stream.channels, 1);
+        assert_eq!(stream.pre_skip, 312);
+        assert_eq!(stream.input_sample_rate, 16_000);
+        assert_eq!(stream.packets, packets);
+        assert_eq!(stream.playable_samples(16_000), Some(input_samples));
+    }
+
+    #[test]
+    fn test_packets_spanning_pages() {
+        let big = (0..70_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        let mut writer = OggOpusWriter::new(2, 48_000, 0, 1);
+        writer.push_packet(&big, 960);
+        writer.push_packet(&[7; 255], 960);
+        let stream = OggOpusStream::parse(&writer.finish(1_920)).unwrap();
+        assert_eq!(stream.packets, vec![big, vec![7; 255]]);
+    }
+
+    #[test]
+    fn test_rejects_corruption() {
+        let mut writer = OggOpusWriter::new(1, 16_000, 312, 9);
+        writer.push_packet(&[1, 2, 3], 960);
+        let mut data = writer.finish(320);
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        assert!(matches!(
+            OggOpusStream::parse(&data),
+            Err(MpvSttError::OggOpus(_))
+        ));
+        assert!(OggOpusStream::parse(b"RIFF....WAVE").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Admin endpoints for changing the hosted models without a restart:
+//! `POST /admin/models/{name}/reload`, `PUT /admin/models/{name}/workers` and
+//! `POST /admin/warmup`.
+//!
+//! Reloading or resizing starts a fresh worker pool and swaps it in; requests already
+//! accepted by the old pool finish there before its workers stop, so both pools run side
+//! by side for a while. Reloading also drops the model's cached results. The endpoints
+//! need `--admin-secret` and are disabled without it.
+
+use crate::models::{HostedModel, ModelRegistry};
+use crate::server::{self, AppState, JobFailure};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use bytes::Bytes;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// `PUT /admin/models/{name}/workers` body.
+#[derive(Deserialize)]
+struct ResizeRequest {
+    workers: usize,
+}
+
+/// Body of a successful reload or resize.
+#[derive(Serialize)]
+struct ModelStatus<'a> {
+    name: &'a str,
+    workers: usize,
+    queue_capacity: usize,
+}
+
+pub(crate) async fn handle_reload(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let response = reload(&state, &name, &headers)
+        .await
+        .unwrap_or_else(JobFailure::into_response);
+    server::record_failure(&state, &response);
+    response
+}
+
+async fn reload(state: &AppState, name: &str, headers: &HeaderMap) -> Result<Response, JobFailure> {
+    state.authorize_admin(headers)?;
+    let model = hosted_model(state, name)?;
+    // Load the model once outside the pool, so a broken file leaves the old workers serving.
+    server::run_warmup(model.runner.clone())
+        .await
+        .map_err(|e| {
+            JobFailure::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "reload_failed",
+                format!(
+                    "reloading model '{}' failed, the previous workers keep serving: {:#}",
+                    name, e
+                ),
+            )
+        })?;
+    let restarted = model.restart(model.pool.num_workers());
+    drop(model);
+    info!("Reloaded model {}", name);
+    let response = swap(state, restarted);
+    state.clear_cached_results(name).await;
+    Ok(response)
+}
+
+pub(crate) async fn handle_resize(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let response = resize(&state, &name, &headers, &body).unwrap_or_else(JobFailure::into_response);
+    server::record_failure(&state, &response);
+    response
+}
+
+fn resize(
+    state: &AppState,
+    name: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Response, JobFailure> {
+    state.authorize_admin(headers)?;
+    let request: ResizeRequest = serde_json::from_slice(body).map_err(|e| {
+        JobFailure::new(
+            StatusCode::BAD_REQUEST,
+            "bad_request",
+            format!("invalid body: {}", e),
+        )
+    })?;
+    if request.workers == 0 {
+        return Err(JobFailure::new(
+            StatusCode::BAD_REQUEST,
+            "bad_request",
+            "a model needs at least one worker",
+        ));
+    }
+    let model = hosted_model(state, name)?;
+    let restarted = model.restart(request.workers);
+    info!(
+        "Resizing model {} from {} to {} workers",
+        name,
+        model.pool.num_workers(),
+        request.workers
+    );
+    drop(model);
+    Ok(swap(state, restarted))
+}
+
+pub(crate) async fn handle_warmup(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let response = warmup(&state, &headers)
+        .await
+        .unwrap_or_else(JobFailure::into_response);
+    server::record_failure(&state, &response);
+    response
+}
+
+async fn warmup(state: &AppState, headers: &HeaderMap) -> Result<Response, JobFailure> {
+    state.authorize_admin(headers)?;
+    server::warm_up(state).await.map_err(|reason| {
+        JobFailure::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "warmup_failed",
+            format!("warmup failed: {}", reason),
+        )
+    })?;
+    Ok(server::response_with_status(StatusCode::OK, b"ready"))
+}
+
+/// The model named in the path; unlike requests, admin calls never fall back to the default.
+fn hosted_model(state: &AppState, name: &str) -> Result<Arc<HostedModel>, JobFailure> {
+    state.models().get(name).ok_or_else(|| {
+        JobFailure::new(
+            StatusCode::NOT_FOUND,
+            "unknown_model",
+            format!("unknown model '{}'", name),
+        )
+    })
+}
+
+/// Serve `model` from now on and let the pool it replaces drain in the background.
+fn swap(state: &AppState, model: HostedModel) -> Response {
+    let response = server::json_response(
+        StatusCode::OK,
+        &ModelStatus {
+            name: &model.name,
+            workers: model.pool.num_workers(),
+            queue_capacity: model.pool.queue_capacity(),
+        },
+    );
+    if let Some(old) = state.models().replace(model) {
+        tokio::spawn(ModelRegistry::retire(old));
+    }
+    response
+}
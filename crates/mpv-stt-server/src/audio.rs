@@ -0,0 +1,182 @@
+//! Decoding of uploaded audio to the 16 kHz mono PCM the models take.
+
+use anyhow::Result;
+use log::{debug, info};
+use mpv_stt_common::PcmBuffer;
+use mpv_stt_plugin::AudioExtractor;
+use mpv_stt_protocol::{GRANULE_RATE, OggOpusStream};
+use opus_static_sys as opus;
+use std::ffi::CStr;
+use std::os::raw::c_int;
+
+pub(crate) fn decompress_opus(compressed: &[u8]) -> Result<PcmBuffer> {
+    let samples = OpusDecoder::new()?.decode_framed(compressed)?;
+
+    info!(
+        "Opus decompression: {} bytes → {} samples",
+        compressed.len(),
+        samples.len()
+    );
+
+    Ok(PcmBuffer::new(
+        samples,
+        OpusDecoder::SAMPLE_RATE as u32,
+        OpusDecoder::CHANNELS as u16,
+    ))
+}
+
+/// Decode an Ogg Opus file, dropping the encoder delay and the padding of the last frame.
+pub(crate) fn decompress_ogg_opus(data: &[u8]) -> Result<PcmBuffer> {
+    let stream = OggOpusStream::parse(data)?;
+    let mut decoder = OpusDecoder::new()?;
+    let mut samples = Vec::new();
+    for packet in &stream.packets {
+        decoder.decode_packet(packet, &mut samples)?;
+    }
+
+    let rate = OpusDecoder::SAMPLE_RATE as u32;
+    let skip =
+        (stream.pre_skip as usize * rate as usize / GRANULE_RATE as usize).min(samples.len());
+    samples.drain(..skip);
+    if let Some(playable) = stream.playable_samples(rate) {
+        samples.truncate(playable as usize);
+    }
+
+    info!(
+        "Ogg Opus decompression: {} bytes ({} packets) → {} samples",
+        data.len(),
+        stream.packets.len(),
+        samples.len()
+    );
+
+    Ok(PcmBuffer::new(samples, rate, OpusDecoder::CHANNELS as u16))
+}
+
+/// 16 kHz mono Opus decoder for `[u32_le_len][packet]...` framed payloads.
+pub(crate) struct OpusDecoder {
+    decoder: *mut opus_static_sys::OpusDecoder,
+}
+
+// The decoder is only ever used through `&mut self`, so moving it between threads is sound.
+unsafe impl Send for OpusDecoder {}
+
+impl OpusDecoder {
+    const SAMPLE_RATE: c_int = 16_000;
+    const CHANNELS: c_int = 1;
+    // 120 ms @ 48k = 5760 samples; safe upper bound for 16k streams too.
+    const MAX_FRAME_SIZE: usize = 5760;
+
+    pub(crate) fn new() -> Result<Self> {
+        let mut err: c_int = 0;
+        let decoder =
+            unsafe { opus::opus_decoder_create(Self::SAMPLE_RATE, Self::CHANNELS, &mut err) };
+        if decoder.is_null() || err != opus::OPUS_OK as c_int {
+            anyhow::bail!("Failed to create Opus decoder: {}", opus_error(err));
+        }
+        Ok(Self { decoder })
+    }
+
+    /// Decode every packet in `compressed`; decoder state carries over between calls.
+    pub(crate) fn decode_framed(&mut self, compressed: &[u8]) -> Result<Vec<i16>> {
+        let mut samples = Vec::new();
+        let mut pos = 0;
+
+        while pos + 4 <= compressed.len() {
+            let frame_len = u32::from_le_bytes(
+                compressed[pos..pos + 4]
+                    .try_into()
+                    .expect("slice length validated"),
+            ) as usize;
+            pos += 4;
+
+            if pos + frame_len > compressed.len() {
+                anyhow::bail!("Invalid Opus frame length");
+            }
+
+            self.decode_packet(&compressed[pos..pos + frame_len], &mut samples)?;
+            pos += frame_len;
+        }
+
+        Ok(samples)
+    }
+
+    /// Decode one Opus packet, appending its samples to `samples`.
+    ///
+    /// Packets of stereo streams are downmixed, so any standard Opus stream can be read.
+    pub(crate) fn decode_packet(&mut self, packet: &[u8], samples: &mut Vec<i16>) -> Result<()> {
+        let mut output = vec![0i16; Self::MAX_FRAME_SIZE];
+        let decoded_samples = unsafe {
+            opus::opus_decode(
+                self.decoder,
+                packet.as_ptr(),
+                packet.len() as opus::opus_int32,
+                output.as_mut_ptr(),
+                Self::MAX_FRAME_SIZE as c_int,
+                0,
+            )
+        };
+
+        if decoded_samples < 0 {
+            anyhow::bail!("Opus decode failed: {}", opus_error(decoded_samples));
+        }
+
+        samples.extend_from_slice(&output[..decoded_samples as usize]);
+        Ok(())
+    }
+}
+
+impl Drop for OpusDecoder {
+    fn drop(&mut self) {
+        unsafe { opus::opus_decoder_destroy(self.decoder) };
+    }
+}
+
+fn opus_error(code: c_int) -> String {
+    unsafe {
+        CStr::from_ptr(opus::opus_strerror(code))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+fn decode_wav(data: &[u8]) -> std::result::Result<PcmBuffer, String> {
+    PcmBuffer::from_wav_bytes(data).map_err(|e| format!("invalid wav: {}", e))
+}
+
+/// Decode an uploaded audio file to 16 kHz mono PCM.
+///
+/// WAVs already in that format are read directly; anything else (other WAV layouts,
+/// mp3, m4a, flac, ogg, webm, ...) is decoded and resampled by ffmpeg.
+pub(crate) async fn decode_media(data: Vec<u8>) -> std::result::Result<PcmBuffer, String> {
+    if let Some(pcm) = decode_wav(&data)
+        .ok()
+        .filter(|pcm| pcm.channels == 1 && pcm.sample_rate == 16_000)
+    {
+        return Ok(pcm);
+    }
+    let bytes_in = data.len();
+    let pcm = tokio::task::spawn_blocking(move || AudioExtractor::default().decode_bytes(&data))
+        .await
+        .map_err(|e| format!("audio decoder failed: {}", e))?
+        .map_err(|e| format!("unsupported audio: {}", e))?;
+    debug!(
+        "Decoded {} byte upload with ffmpeg → {} samples",
+        bytes_in,
+        pcm.samples.len()
+    );
+    Ok(pcm)
+}
+
+pub(crate) fn validate_pcm(pcm: PcmBuffer) -> std::result::Result<PcmBuffer, String> {
+    if pcm.channels != 1 || pcm.sample_rate != 16_000 {
+        return Err(format!(
+            "unsupported wav format: {}ch {}Hz 16-bit",
+            pcm.channels, pcm.sample_rate
+        ));
+    }
+    // Ensure there is at least one sample; avoid empty payloads that whisper cannot handle.
+    if pcm.is_empty() {
+        return Err("wav contains no samples".to_string());
+    }
+    Ok(pcm)
+}
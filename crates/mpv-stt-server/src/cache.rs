@@ -0,0 +1,544 @@
+//! Result cache: transcripts keyed by a hash of the decoded audio, the model and the
+//! request options, so identical chunks sent by several players are transcribed once.
+//!
+//! Entries are evicted least-recently-used beyond the size limit and expire after the
+//! TTL. With a cache directory the transcripts live on disk (one JSON file per entry)
+//! and are indexed again on startup; otherwise they are kept in memory. Reloading a
+//! model drops its entries, since the new model file may transcribe differently.
+
+use crate::metrics::CacheSnapshot;
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use mpv_stt_common::Transcript;
+use mpv_stt_protocol::TranscriptionJob;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Bumped whenever the key derivation or the stored format changes.
+const KEY_VERSION: &[u8] = b"mpv-stt-cache-v3";
+const ENTRY_EXTENSION: &str = "json";
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// Upper bound on the size of the stored transcripts.
+    pub max_bytes: u64,
+    pub ttl: Duration,
+    /// Keep entries in this directory instead of memory.
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    /// Hash of the decoded audio plus everything else that shapes the transcript. The
+    /// client's `x-duration-ms` is left out: players report the same chunk with slightly
+    /// different lengths, and the samples already determine the audio's.
+    pub fn new(model: &str, job: &TranscriptionJob) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(KEY_VERSION);
+        hasher.update(job.audio.sample_rate.to_le_bytes());
+        hasher.update(job.audio.channels.to_le_bytes());
+        hasher.update((job.audio.samples.len() as u64).to_le_bytes());
+        let samples: Vec<u8> = job
+            .audio
+            .samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        hasher.update(&samples);
+        hasher.update((model.len() as u64).to_le_bytes());
+        hasher.update(model.as_bytes());
+        hasher.update(serde_json::to_vec(&job.options).expect("options serialize to JSON"));
+        Self(hasher.finalize().into())
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}.{}", hex::encode(self.0), ENTRY_EXTENSION)
+    }
+
+    fn from_file_name(path: &Path) -> Option<Self> {
+        if path.extension()? != ENTRY_EXTENSION {
+            return None;
+        }
+        let bytes = hex::decode(path.file_stem()?.to_str()?).ok()?;
+        Some(Self(bytes.try_into().ok()?))
+    }
+}
+
+/// Where a job's transcript is cached.
+#[derive(Debug, Clone)]
+pub(crate) struct CacheSlot {
+    pub key: CacheKey,
+    model: String,
+    /// Results of jobs requested before their model was last reloaded are not stored.
+    requested_at: Instant,
+}
+
+impl CacheSlot {
+    pub fn new(model: &str, job: &TranscriptionJob) -> Self {
+        Self {
+            key: CacheKey::new(model, job),
+            model: model.to_string(),
+            requested_at: job.enqueue_at,
+        }
+    }
+}
+
+/// On-disk form of an entry.
+#[derive(Serialize, Deserialize)]
+struct StoredResult<T = Transcript> {
+    /// Unix seconds.
+    stored_at: u64,
+    model: String,
+    transcript: T,
+}
+
+struct Entry {
+    model: String,
+    size: u64,
+    stored_at: SystemTime,
+    /// Value of [`CacheState::clock`] when the entry was last read or written; set by
+    /// [`CacheState::insert`].
+    last_used: u64,
+    /// `None` when the transcript is on disk.
+    transcript: Option<Transcript>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, Entry>,
+    /// Keys by [`Entry::last_used`], least recently used first.
+    recency: BTreeMap<u64, CacheKey>,
+    bytes: u64,
+    clock: u64,
+    /// When each model was last reloaded.
+    reloaded: HashMap<String, Instant>,
+}
+
+impl CacheState {
+    /// Add `entry` as the most recently used one, replacing any entry under `key`.
+    fn insert(&mut self, key: CacheKey, mut entry: Entry) {
+        self.remove(&key);
+        entry.last_used = self.tick();
+        self.recency.insert(entry.last_used, key);
+        self.bytes += entry.size;
+        self.entries.insert(key, entry);
+    }
+
+    /// Mark the entry under `key` as just used.
+    fn touch(&mut self, key: &CacheKey) -> Option<&Entry> {
+        let last_used = self.tick();
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(last_used, *key);
+        entry.last_used = last_used;
+        Some(entry)
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        self.bytes -= entry.size;
+        Some(entry)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+pub(crate) struct ResultCache {
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResultCache {
+    /// Create the cache; a cache directory is created if needed and its entries indexed.
+    pub fn open(config: CacheConfig) -> Result<Self> {
+        let cache = Self {
+            config,
+            state: Mutex::new(CacheState::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        };
+        if let Some(dir) = &cache.config.dir {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("creating cache directory {}", dir.display()))?;
+            cache.index(dir)?;
+        }
+        Ok(cache)
+    }
+
+    /// Pick up entries stored by a previous run, oldest first so eviction order survives.
+    fn index(&self, dir: &Path) -> Result<()> {
+        let mut found = Vec::new();
+        let listing = std::fs::read_dir(dir)
+            .with_context(|| format!("reading cache directory {}", dir.display()))?;
+        for dir_entry in listing.flatten() {
+            let path = dir_entry.path();
+            let Some(key) = CacheKey::from_file_name(&path) else {
+                continue;
+            };
+            match read_stored(&path) {
+                Some(stored) => {
+                    let size = dir_entry.metadata().map(|meta| meta.len()).unwrap_or(0);
+                    found.push((
+                        key,
+                        stored.model,
+                        size,
+                        UNIX_EPOCH + Duration::from_secs(stored.stored_at),
+                    ));
+                }
+                None => remove_file(&path),
+            }
+        }
+        found.sort_by_key(|(_, _, _, stored_at)| *stored_at);
+
+        let mut state = self.lock();
+        for (key, model, size, stored_at) in found {
+            if self.expired(stored_at) {
+                remove_file(&dir.join(key.file_name()));
+                continue;
+            }
+            state.insert(
+                key,
+                Entry {
+                    model,
+                    size,
+                    stored_at,
+                    last_used: 0,
+                    transcript: None,
+                },
+            );
+        }
+        self.evict(&mut state);
+        debug!(
+            "Indexed {} cached results ({} bytes)",
+            state.entries.len(),
+            state.bytes
+        );
+        Ok(())
+    }
+
+    /// The stored transcript for `key`, if present and not expired.
+    pub fn get(&self, key: &CacheKey) -> Option<Transcript> {
+        let found = self.lookup(key);
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    fn lookup(&self, key: &CacheKey) -> Option<Transcript> {
+        {
+            let mut state = self.lock();
+            let stored_at = state.entries.get(key)?.stored_at;
+            if self.expired(stored_at) {
+                state.remove(key);
+                drop(state);
+                self.remove_stored(key);
+                return None;
+            }
+            let entry = state.touch(key)?;
+            if let Some(transcript) = &entry.transcript {
+                return Some(transcript.clone());
+            }
+        }
+
+        let path = self.config.dir.as_ref()?.join(key.file_name());
+        let stored = read_stored(&path);
+        if stored.is_none() {
+            // Deleted or corrupted behind our back.
+            self.lock().remove(key);
+        }
+        stored.map(|stored| stored.transcript)
+    }
+
+    /// Store `transcript` in `slot`, evicting older entries beyond the size limit.
+    pub fn insert(&self, slot: &CacheSlot, transcript: &Transcript) {
+        if self.outdated(slot) {
+            return;
+        }
+        let key = slot.key;
+        let stored_at = SystemTime::now();
+        let (size, kept) = match &self.config.dir {
+            Some(dir) => match write_stored(dir, slot, stored_at, transcript) {
+                Ok(size) => (size, None),
+                Err(e) => {
+                    warn!("Caching result failed: {:#}", e);
+                    return;
+                }
+            },
+            None => (stored_size(transcript), Some(transcript.clone())),
+        };
+        if size > self.config.max_bytes {
+            self.remove_stored(&key);
+            return;
+        }
+
+        let mut state = self.lock();
+        state.insert(
+            key,
+            Entry {
+                model: slot.model.clone(),
+                size,
+                stored_at,
+                last_used: 0,
+                transcript: kept,
+            },
+        );
+        self.evict(&mut state);
+    }
+
+    /// Whether the slot's model was reloaded after the job was requested, so its result
+    /// may come from the previous model file.
+    fn outdated(&self, slot: &CacheSlot) -> bool {
+        self.lock()
+            .reloaded
+            .get(&slot.model)
+            .is_some_and(|reloaded| *reloaded > slot.requested_at)
+    }
+
+    /// Drop every entry of `model`, including results of its jobs still running on the
+    /// previous workers once they finish.
+    pub fn clear_model(&self, model: &str) {
+        let mut state = self.lock();
+        state.reloaded.insert(model.to_string(), Instant::now());
+        let stale: Vec<CacheKey> = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.model == model)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &stale {
+            state.remove(key);
+            self.remove_stored(key);
+        }
+        debug!("Dropped {} cached results of model {}", stale.len(), model);
+    }
+
+    fn evict(&self, state: &mut CacheState) {
+        while state.bytes > self.config.max_bytes {
+            let Some((_, oldest)) = state.recency.first_key_value() else {
+                break;
+            };
+            let oldest = *oldest;
+            state.remove(&oldest);
+            self.remove_stored(&oldest);
+        }
+    }
+
+    fn expired(&self, stored_at: SystemTime) -> bool {
+        stored_at.elapsed().is_ok_and(|age| age >= self.config.ttl)
+    }
+
+    fn remove_stored(&self, key: &CacheKey) {
+        if let Some(dir) = &self.config.dir {
+            remove_file(&dir.join(key.file_name()));
+        }
+    }
+
+    pub fn snapshot(&self) -> CacheSnapshot {
+        let state = self.lock();
+        CacheSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: state.entries.len(),
+            bytes: state.bytes,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Entries are measured by their JSON size, in memory as well as on disk.
+fn stored_size(transcript: &Transcript) -> u64 {
+    serde_json::to_vec(transcript).map_or(0, |data| data.len() as u64)
+}
+
+fn read_stored(path: &Path) -> Option<StoredResult> {
+    let data = std::fs::read(path).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Write the entry through a temporary file so readers never see a partial one.
+fn write_stored(
+    dir: &Path,
+    slot: &CacheSlot,
+    stored_at: SystemTime,
+    transcript: &Transcript,
+) -> Result<u64> {
+    let stored = StoredResult {
+        stored_at: stored_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        model: slot.model.clone(),
+        transcript,
+    };
+    let data = serde_json::to_vec(&stored)?;
+    let path = dir.join(slot.key.file_name());
+    // Identical audio is often stored by several requests at once; each gets its own file.
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("creating a temporary file in {}", dir.display()))?;
+    tmp.write_all(&data)
+        .with_context(|| format!("writing {}", tmp.path().display()))?;
+    tmp.persist(&path)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(data.len() as u64)
+}
+
+fn remove_file(path: &Path) {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            warn!("Removing cached result {} failed: {}", path.display(), e);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpv_stt_common::{PcmBuffer, TranscriptSegment, TranscriptionOptions};
+    use std::time::Instant;
+
+    fn job(samples: Vec<i16>, language: Option<&str>) -> TranscriptionJob {
+        TranscriptionJob {
+            request_id: 1,
+            model: String::new(),
+            duration_ms: 0,
+            audio: PcmBuffer::new(samples, 16_000, 1),
+            options: TranscriptionOptions {
+                language: language.map(str::to_string),
+                ..Default::default()
+            },
+            priority: Default::default(),
+            on_segment: None,
+            enqueue_at: Instant::now(),
+            deadline: None,
+        }
+    }
+
+    fn transcript(text: &str) -> Transcript {
+        Transcript {
+            segments: vec![TranscriptSegment {
+                start_ms: 0,
+                end_ms: 1_000,
+                text: text.to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_key_covers_audio_model_and_options() {
+        let key = CacheKey::new("base", &job(vec![1, 2, 3], None));
+        assert_eq!(key, CacheKey::new("base", &job(vec![1, 2, 3], None)));
+        assert_ne!(key, CacheKey::new("base", &job(vec![1, 2, 4], None)));
+        assert_ne!(key, CacheKey::new("large", &job(vec![1, 2, 3], None)));
+        assert_ne!(key, CacheKey::new("base", &job(vec![1, 2, 3], Some("en"))));
+        let reported = TranscriptionJob {
+            duration_ms: 1,
+            ..job(vec![1, 2, 3], None)
+        };
+        assert_eq!(key, CacheKey::new("base", &reported));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_and_expired_entries() {
+        let entry_size = stored_size(&transcript("aaaa"));
+        let cache = ResultCache::open(CacheConfig {
+            max_bytes: entry_size * 2,
+            ttl: Duration::from_secs(60),
+            dir: None,
+        })
+        .unwrap();
+        let slots: Vec<CacheSlot> = (0..3)
+            .map(|i| CacheSlot::new("base", &job(vec![i], None)))
+            .collect();
+
+        cache.insert(&slots[0], &transcript("aaaa"));
+        cache.insert(&slots[1], &transcript("bbbb"));
+        assert!(cache.get(&slots[0].key).is_some());
+        cache.insert(&slots[2], &transcript("cccc"));
+        assert!(cache.get(&slots[1].key).is_none());
+        assert_eq!(cache.get(&slots[2].key).unwrap(), transcript("cccc"));
+
+        let snapshot = cache.snapshot();
+        assert_eq!((snapshot.hits, snapshot.misses), (2, 1));
+        assert_eq!(snapshot.entries, 2);
+
+        let expiring = ResultCache::open(CacheConfig {
+            max_bytes: 1024,
+            ttl: Duration::ZERO,
+            dir: None,
+        })
+        .unwrap();
+        expiring.insert(&slots[0], &transcript("aaaa"));
+        assert!(expiring.get(&slots[0].key).is_none());
+        assert_eq!(expiring.snapshot().entries, 0);
+    }
+
+    #[test]
+    fn test_disk_entries_survive_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CacheConfig {
+            max_bytes: 1024 * 1024,
+            ttl: Duration::from_secs(60),
+            dir: Some(dir.path().to_path_buf()),
+        };
+        let slot = CacheSlot::new("base", &job(vec![7; 160], None));
+        ResultCache::open(config.clone())
+            .unwrap()
+            .insert(&slot, &transcript("persisted"));
+        std::fs::write(dir.path().join("not-a-key.json"), b"ignored").unwrap();
+
+        let reopened = ResultCache::open(config).unwrap();
+        assert_eq!(reopened.snapshot().entries, 1);
+        assert_eq!(reopened.get(&slot.key).unwrap(), transcript("persisted"));
+    }
+
+    #[test]
+    fn test_reloading_a_model_drops_its_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CacheConfig {
+            max_bytes: 1024 * 1024,
+            ttl: Duration::from_secs(60),
+            dir: Some(dir.path().to_path_buf()),
+        };
+        let cache = ResultCache::open(config.clone()).unwrap();
+        let base = CacheSlot::new("base", &job(vec![1; 160], None));
+        let large = CacheSlot::new("large", &job(vec![1; 160], None));
+        cache.insert(&base, &transcript("old base"));
+        cache.insert(&large, &transcript("large"));
+        // Requested before the reload, finished after it.
+        let running = CacheSlot::new("base", &job(vec![2; 160], None));
+
+        cache.clear_model("base");
+        cache.insert(&running, &transcript("old base"));
+        assert!(cache.get(&base.key).is_none());
+        assert!(cache.get(&running.key).is_none());
+        assert_eq!(cache.get(&large.key).unwrap(), transcript("large"));
+
+        let fresh = CacheSlot::new("base", &job(vec![1; 160], None));
+        cache.insert(&fresh, &transcript("new base"));
+        assert_eq!(cache.get(&fresh.key).unwrap(), transcript("new base"));
+        assert_eq!(ResultCache::open(config).unwrap().snapshot().entries, 2);
+    }
+}
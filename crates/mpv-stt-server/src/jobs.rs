@@ -0,0 +1,791 @@
+//! Batch jobs: `POST /jobs` takes a whole media file, `GET /jobs/{id}` reports progress
+//! and `GET /jobs/{id}/result?format=srt|vtt|json` returns the finished transcript.
+//!
+//! The upload is streamed to disk and the request answered before anything is decoded;
+//! the job then decodes it without a time limit, cuts the audio into chunks of at most
+//! 30 s that end in the quietest moment near their limit, and feeds them to the model's
+//! worker pool at [`JobPriority::Batch`], so interactive requests always go first. Each
+//! job is kept in the jobs directory as `<id>.json` (state and the segments so far) plus
+//! `<id>.upload` until it is decoded and `<id>.wav` after (both removed once the job
+//! ends); unfinished jobs found there on startup decode their upload if that never
+//! finished, then pick up at their first untranscribed chunk.
+
+use crate::audio;
+use crate::keys::{Caller, unix_secs};
+use crate::openai::{CONTENT_TYPE_JSON, CONTENT_TYPE_TEXT, CONTENT_TYPE_VTT};
+use crate::server::{self, AppState, Billing, JobFailure};
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CONTENT_TYPE, LOCATION},
+    },
+    response::Response,
+};
+use futures::StreamExt;
+use log::{info, warn};
+use mpv_stt_common::{PcmBuffer, Transcript, TranscriptSegment, TranscriptionOptions, rms_db};
+use mpv_stt_plugin::AudioExtractor;
+use mpv_stt_protocol::{JobPriority, TranscriptionJob};
+use mpv_stt_srt::SrtFile;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+
+const SAMPLE_RATE: u32 = 16_000;
+/// Longest job chunk; Whisper's own window.
+const CHUNK_MS: u64 = 30_000;
+/// Stretch at the end of a full chunk searched for a quiet frame to cut at.
+const SPLIT_SEARCH_MS: u64 = 5_000;
+const SPLIT_FRAME_MS: u64 = 20;
+/// Batch jobs take whole media files.
+const MAX_UPLOAD_SIZE: u64 = 1024 * 1024 * 1024;
+/// A chunk waits behind every interactive request, so it gets far longer than they do.
+const CHUNK_DEADLINE: Duration = Duration::from_secs(30 * 60);
+/// Attempts at a chunk that fails before the whole job is marked failed. A failed attempt
+/// gives its audio back to the key's quota, so a chunk is only ever charged once.
+const MAX_CHUNK_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const RECORD_EXTENSION: &str = "json";
+const AUDIO_EXTENSION: &str = "wav";
+const UPLOAD_EXTENSION: &str = "upload";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JobStatus {
+    Queued,
+    Running,
+    /// A chunk is held back by a full queue or the key's used-up quota; see
+    /// [`JobRecord::waiting_for`].
+    Waiting,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+}
+
+/// Everything known about a job; stored as `<id>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JobRecord {
+    id: String,
+    /// API key that submitted the job; only that key may read it back.
+    key: Option<String>,
+    model: String,
+    options: TranscriptionOptions,
+    status: JobStatus,
+    error: Option<String>,
+    /// Why the job is [`JobStatus::Waiting`].
+    #[serde(default)]
+    waiting_for: Option<String>,
+    /// Zero, like `chunks_total`, until the upload is decoded.
+    duration_ms: u64,
+    chunks_total: usize,
+    chunks_done: usize,
+    /// Sample at which each chunk of the decoded audio ends; empty until it is decoded.
+    #[serde(default)]
+    chunk_ends: Vec<usize>,
+    language: Option<String>,
+    /// Segments of the finished chunks, on the timeline of the whole file.
+    segments: Vec<TranscriptSegment>,
+    /// Unix seconds.
+    created_at: u64,
+    finished_at: Option<u64>,
+}
+
+impl JobRecord {
+    fn transcript(&self) -> Transcript {
+        Transcript {
+            segments: self.segments.clone(),
+            language: self.language.clone(),
+            duration_ms: self.duration_ms,
+            ..Default::default()
+        }
+    }
+
+    fn visible_to(&self, caller: &Caller) -> bool {
+        self.key.is_none() || self.key.as_deref() == caller.key_name()
+    }
+
+    fn is_decoded(&self) -> bool {
+        !self.chunk_ends.is_empty()
+    }
+
+    /// Sample at which chunk `index` starts.
+    fn chunk_start(&self, index: usize) -> usize {
+        index.checked_sub(1).map_or(0, |prev| self.chunk_ends[prev])
+    }
+}
+
+/// `GET /jobs/{id}` body.
+#[derive(Serialize)]
+struct JobProgress<'a> {
+    id: &'a str,
+    status: JobStatus,
+    model: &'a str,
+    duration_ms: u64,
+    chunks_done: usize,
+    chunks_total: usize,
+    /// Estimated seconds until the job completes, once a chunk has finished in this run.
+    eta_secs: Option<u64>,
+    error: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    waiting_for: Option<&'a str>,
+    created_at: u64,
+    finished_at: Option<u64>,
+}
+
+/// `GET /jobs/{id}/result?format=json` body.
+#[derive(Serialize)]
+struct JobResultBody<'a> {
+    language: Option<&'a str>,
+    duration_ms: u64,
+    segments: &'a [TranscriptSegment],
+}
+
+/// Throughput of the current run of a job, for its ETA.
+struct Run {
+    started: Instant,
+    chunks_at_start: usize,
+}
+
+struct TrackedJob {
+    record: JobRecord,
+    run: Option<Run>,
+}
+
+impl TrackedJob {
+    fn eta(&self) -> Option<Duration> {
+        let run = self.run.as_ref()?;
+        let done = self.record.chunks_done.checked_sub(run.chunks_at_start)?;
+        if done == 0 {
+            return None;
+        }
+        let remaining = self.record.chunks_total - self.record.chunks_done;
+        Some(
+            run.started
+                .elapsed()
+                .mul_f64(remaining as f64 / done as f64),
+        )
+    }
+}
+
+/// Batch jobs, mirrored to the jobs directory.
+pub(crate) struct JobStore {
+    dir: PathBuf,
+    jobs: Mutex<HashMap<String, TrackedJob>>,
+    next_id: AtomicU64,
+}
+
+impl JobStore {
+    /// Create the jobs directory if needed and load the jobs a previous run left there.
+    pub fn open(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("creating jobs directory {}", dir.display()))?;
+        let mut jobs = HashMap::new();
+        let listing = std::fs::read_dir(&dir)
+            .with_context(|| format!("reading jobs directory {}", dir.display()))?;
+        for dir_entry in listing.flatten() {
+            let path = dir_entry.path();
+            if path.extension().is_none_or(|ext| ext != RECORD_EXTENSION) {
+                continue;
+            }
+            let record = std::fs::read(&path)
+                .ok()
+                .and_then(|data| serde_json::from_slice::<JobRecord>(&data).ok());
+            match record {
+                Some(record) => {
+                    jobs.insert(record.id.clone(), TrackedJob { record, run: None });
+                }
+                None => warn!("Ignoring unreadable job file {}", path.display()),
+            }
+        }
+        Ok(Self {
+            dir,
+            jobs: Mutex::new(jobs),
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Jobs that still have chunks to transcribe.
+    pub fn unfinished(&self) -> Vec<String> {
+        self.lock()
+            .values()
+            .filter(|job| !job.record.status.is_finished())
+            .map(|job| job.record.id.clone())
+            .collect()
+    }
+
+    /// Store the state of a new job whose upload is already at [`Self::upload_path`].
+    fn create(
+        &self,
+        id: String,
+        caller: &Caller,
+        model: String,
+        options: TranscriptionOptions,
+    ) -> Result<JobRecord> {
+        let record = JobRecord {
+            id: id.clone(),
+            key: caller.key_name().map(str::to_string),
+            model,
+            options,
+            status: JobStatus::Queued,
+            error: None,
+            waiting_for: None,
+            duration_ms: 0,
+            chunks_total: 0,
+            chunks_done: 0,
+            chunk_ends: Vec::new(),
+            language: None,
+            segments: Vec::new(),
+            created_at: unix_secs(),
+            finished_at: None,
+        };
+        self.persist(&record)?;
+        self.lock().insert(
+            id,
+            TrackedJob {
+                record: record.clone(),
+                run: None,
+            },
+        );
+        Ok(record)
+    }
+
+    /// Unguessable without the server's clock, and unique within this directory.
+    fn new_id(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+                .to_le_bytes(),
+        );
+        hasher.update(self.next_id.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+        hasher.update(std::process::id().to_le_bytes());
+        hex::encode(&hasher.finalize()[..16])
+    }
+
+    fn record(&self, id: &str) -> Option<JobRecord> {
+        self.lock().get(id).map(|job| job.record.clone())
+    }
+
+    /// Decode the upload of `id` to its audio file and cut that into chunks.
+    async fn decode(&self, id: &str) -> Result<()> {
+        let upload_path = self.upload_path(id);
+        let audio_path = self.audio_path(id);
+        let (duration_ms, chunk_ends) = tokio::task::spawn_blocking(move || -> Result<_> {
+            let wav = PcmBuffer::read_wav(&upload_path)
+                .ok()
+                .filter(|pcm| pcm.channels == 1 && pcm.sample_rate == SAMPLE_RATE);
+            let audio = match wav {
+                Some(audio) => audio,
+                // Nobody waits on this, and hours of audio can take minutes to decode.
+                None => AudioExtractor::default()
+                    .with_ffmpeg_timeout(0)
+                    .extract_pcm(&upload_path, 0, 0)
+                    .map_err(|e| anyhow::anyhow!("unsupported audio: {}", e))?,
+            };
+            let audio = audio::validate_pcm(audio).map_err(anyhow::Error::msg)?;
+            // Written aside first, so a crash never leaves a truncated file in its place.
+            let tmp = audio_path.with_extension("wav.tmp");
+            audio.write_wav(&tmp).context("storing job audio")?;
+            std::fs::rename(&tmp, &audio_path).context("storing job audio")?;
+            Ok((audio.duration_ms(), chunk_ends(&audio.samples)))
+        })
+        .await??;
+
+        self.update(id, |job| {
+            job.record.duration_ms = duration_ms;
+            job.record.chunks_total = chunk_ends.len();
+            job.record.chunk_ends = chunk_ends;
+        })?;
+        remove_file(&self.upload_path(id));
+        Ok(())
+    }
+
+    async fn load_audio(&self, id: &str) -> Result<PcmBuffer> {
+        let path = self.audio_path(id);
+        let audio = tokio::task::spawn_blocking(move || PcmBuffer::read_wav(&path)).await??;
+        Ok(audio)
+    }
+
+    /// Mark `id` as running from its current chunk on.
+    fn start(&self, id: &str) -> Result<()> {
+        self.update(id, |job| {
+            job.record.status = JobStatus::Running;
+            job.run = Some(Run {
+                started: Instant::now(),
+                chunks_at_start: job.record.chunks_done,
+            });
+        })
+    }
+
+    /// Append the transcript of the next chunk of `id`.
+    fn chunk_done(&self, id: &str, mut transcript: Transcript) -> Result<()> {
+        self.update(id, |job| {
+            let record = &mut job.record;
+            let start = record.chunk_start(record.chunks_done) as u64;
+            transcript.offset(start * 1000 / SAMPLE_RATE as u64);
+            record.segments.append(&mut transcript.segments);
+            record.language = record.language.take().or(transcript.language);
+            record.chunks_done += 1;
+            record.status = JobStatus::Running;
+            record.waiting_for = None;
+        })
+    }
+
+    /// Show `id` as waiting for `reason` until its next chunk is done.
+    fn wait(&self, id: &str, reason: &str) -> Result<()> {
+        self.update(id, |job| {
+            job.record.status = JobStatus::Waiting;
+            job.record.waiting_for = Some(reason.to_string());
+        })
+    }
+
+    /// End `id`, successfully unless there is an `error`, and drop its upload and audio.
+    fn finish(&self, id: &str, error: Option<String>) -> Result<()> {
+        self.update(id, |job| {
+            job.record.status = match error {
+                Some(_) => JobStatus::Failed,
+                None => JobStatus::Completed,
+            };
+            job.record.error = error;
+            job.record.waiting_for = None;
+            job.record.finished_at = Some(unix_secs());
+            job.run = None;
+        })?;
+        remove_file(&self.upload_path(id));
+        remove_file(&self.audio_path(id));
+        Ok(())
+    }
+
+    fn update(&self, id: &str, change: impl FnOnce(&mut TrackedJob)) -> Result<()> {
+        let mut jobs = self.lock();
+        let job = jobs.get_mut(id).context("unknown job")?;
+        change(job);
+        self.persist(&job.record)
+    }
+
+    /// Write the record through a temporary file so a crash never leaves half of one.
+    fn persist(&self, record: &JobRecord) -> Result<()> {
+        let path = self.dir.join(format!("{}.{}", record.id, RECORD_EXTENSION));
+        let tmp = path.with_extension("tmp");
+        let data = serde_json::to_vec(record)?;
+        std::fs::write(&tmp, &data).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path).with_context(|| format!("writing {}", path.display()))?;
+        Ok(())
+    }
+
+    fn audio_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, AUDIO_EXTENSION))
+    }
+
+    fn upload_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, UPLOAD_EXTENSION))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, TrackedJob>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Why a job's run ended early.
+enum Stop {
+    /// The server is shutting down; the job resumes on the next start.
+    Interrupted,
+    Failed(String),
+}
+
+/// Transcribe the remaining chunks of `id`, persisting the progress after each one.
+pub(crate) async fn run(state: AppState, store: Arc<JobStore>, id: String) {
+    match run_chunks(&state, &store, &id).await {
+        Ok(()) => {
+            info!("Job {} completed", id);
+            finish(&store, &id, None);
+        }
+        Err(Stop::Interrupted) => info!("Job {} interrupted; it resumes on restart", id),
+        Err(Stop::Failed(message)) => {
+            warn!("Job {} failed: {}", id, message);
+            finish(&store, &id, Some(message));
+        }
+    }
+}
+
+fn finish(store: &JobStore, id: &str, error: Option<String>) {
+    if let Err(e) = store.finish(id, error) {
+        warn!("Recording the end of job {} failed: {:#}", id, e);
+    }
+}
+
+async fn run_chunks(state: &AppState, store: &JobStore, id: &str) -> Result<(), Stop> {
+    let failed = |e: anyhow::Error| Stop::Failed(format!("{:#}", e));
+    let mut record = store.record(id).ok_or(Stop::Interrupted)?;
+    let workers = state
+        .resolve_model(Some(&record.model))
+        .map_err(|failure| Stop::Failed(failure.message))?
+        .pool
+        .num_workers();
+    if !record.is_decoded() {
+        store.decode(id).await.map_err(failed)?;
+        record = store.record(id).ok_or(Stop::Interrupted)?;
+    }
+    let audio = store.load_audio(id).await.map_err(failed)?;
+    store.start(id).map_err(failed)?;
+    let caller = Caller::with_key_name(record.key.clone());
+
+    let chunks = (record.chunks_done..record.chunks_total).map(|index| {
+        let start = record.chunk_start(index).min(audio.samples.len());
+        let end = record.chunk_ends[index].min(audio.samples.len());
+        let chunk = PcmBuffer::new(audio.samples[start..end].to_vec(), SAMPLE_RATE, 1);
+        transcribe_chunk(state, store, &caller, &record, chunk)
+    });
+    // Keep every worker of the model busy while results still arrive in order.
+    let mut results = futures::stream::iter(chunks).buffered(workers);
+    while let Some(result) = results.next().await {
+        store.chunk_done(id, result?).map_err(failed)?;
+    }
+    Ok(())
+}
+
+/// Where each chunk of `samples` ends. Chunks are at most [`CHUNK_MS`] long, and each full
+/// one ends after the quietest frame of its last [`SPLIT_SEARCH_MS`], so a cut falls in a
+/// pause between words wherever the audio has one.
+fn chunk_ends(samples: &[i16]) -> Vec<usize> {
+    let to_samples = |ms: u64| (ms * SAMPLE_RATE as u64 / 1000) as usize;
+    let chunk = to_samples(CHUNK_MS);
+    let frame = to_samples(SPLIT_FRAME_MS);
+    let search = to_samples(SPLIT_SEARCH_MS);
+    let mut ends = Vec::new();
+    let mut start = 0;
+    while samples.len() - start > chunk {
+        let limit = start + chunk;
+        // Later frames win ties, keeping chunks long.
+        let end = (limit - search + frame..=limit)
+            .rev()
+            .step_by(frame)
+            .map(|end| (end, rms_db(&samples[end - frame..end])))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(limit, |(end, _)| end);
+        ends.push(end);
+        start = end;
+    }
+    ends.push(samples.len());
+    ends
+}
+
+/// Transcribe one chunk, waiting out a full queue or quota and retrying failures.
+async fn transcribe_chunk(
+    state: &AppState,
+    store: &JobStore,
+    caller: &Caller,
+    record: &JobRecord,
+    audio: PcmBuffer,
+) -> Result<Transcript, Stop> {
+    if audio.is_empty() {
+        return Ok(Transcript::default());
+    }
+    let mut attempts = 0;
+    loop {
+        let job = TranscriptionJob {
+            request_id: state.allocate_request_id(),
+            model: record.model.clone(),
+            duration_ms: audio.duration_ms(),
+            audio: audio.clone(),
+            options: record.options.clone(),
+            priority: JobPriority::Batch,
+            on_segment: None,
+            enqueue_at: Instant::now(),
+            deadline: Some(Instant::now() + CHUNK_DEADLINE),
+        };
+        let failure = match server::run_job(state, caller, job, Billing::Charged).await {
+            Ok((transcript, metrics)) => {
+                state.metrics.record_job(&metrics, transcript.duration_ms);
+                return Ok(transcript);
+            }
+            Err(failure) => failure,
+        };
+        let delay = match failure.kind {
+            "shutting_down" => return Err(Stop::Interrupted),
+            "queue_full" | "quota_exceeded" => {
+                store
+                    .wait(&record.id, &failure.message)
+                    .map_err(|e| Stop::Failed(format!("{:#}", e)))?;
+                failure.retry_after.unwrap_or(RETRY_DELAY)
+            }
+            _ => {
+                attempts += 1;
+                if attempts >= MAX_CHUNK_ATTEMPTS {
+                    return Err(Stop::Failed(failure.message));
+                }
+                RETRY_DELAY
+            }
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+pub(crate) async fn handle_create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let response = create(&state, &headers, body)
+        .await
+        .unwrap_or_else(JobFailure::into_response);
+    server::record_failure(&state, &response);
+    response
+}
+
+async fn create(state: &AppState, headers: &HeaderMap, body: Body) -> Result<Response, JobFailure> {
+    let store = jobs_enabled(state)?;
+    let caller = state.admit(headers)?;
+    let (model, options) = state.request_target(headers)?;
+
+    let id = store.new_id();
+    let upload_path = store.upload_path(&id);
+    let size = save_upload(&upload_path, body)
+        .await
+        .inspect_err(|_| remove_file(&upload_path))?;
+    let record = store.create(id, &caller, model, options).map_err(|e| {
+        remove_file(&upload_path);
+        store_failed(e)
+    })?;
+    info!("Job {} created from a {} byte upload", record.id, size);
+    tokio::spawn(run(state.clone(), Arc::clone(store), record.id.clone()));
+
+    let mut response = server::json_response(StatusCode::ACCEPTED, &progress(&record, None));
+    if let Ok(location) = HeaderValue::from_str(&format!("/jobs/{}", record.id)) {
+        response.headers_mut().insert(LOCATION, location);
+    }
+    Ok(response)
+}
+
+pub(crate) async fn handle_status(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let response = status(&state, &id, &headers).unwrap_or_else(JobFailure::into_response);
+    server::record_failure(&state, &response);
+    response
+}
+
+fn status(state: &AppState, id: &str, headers: &HeaderMap) -> Result<Response, JobFailure> {
+    let store = jobs_enabled(state)?;
+    let caller = state.authenticate(headers)?;
+    let jobs = store.lock();
+    let job = jobs
+        .get(id)
+        .filter(|job| job.record.visible_to(&caller))
+        .ok_or_else(unknown_job)?;
+    Ok(server::json_response(
+        StatusCode::OK,
+        &progress(&job.record, job.eta()),
+    ))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ResultQuery {
+    format: Option<String>,
+}
+
+pub(crate) async fn handle_result(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ResultQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let response = result(&state, &id, query.format.as_deref(), &headers)
+        .unwrap_or_else(JobFailure::into_response);
+    server::record_failure(&state, &response);
+    response
+}
+
+fn result(
+    state: &AppState,
+    id: &str,
+    format: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Response, JobFailure> {
+    let store = jobs_enabled(state)?;
+    let caller = state.authenticate(headers)?;
+    let record = store
+        .record(id)
+        .filter(|record| record.visible_to(&caller))
+        .ok_or_else(unknown_job)?;
+    match record.status {
+        JobStatus::Completed => {}
+        JobStatus::Failed => {
+            return Err(JobFailure::new(
+                StatusCode::CONFLICT,
+                "job_failed",
+                format!(
+                    "job failed: {}",
+                    record.error.as_deref().unwrap_or("unknown")
+                ),
+            ));
+        }
+        JobStatus::Queued | JobStatus::Running | JobStatus::Waiting => {
+            return Err(JobFailure::new(
+                StatusCode::CONFLICT,
+                "job_pending",
+                format!(
+                    "job not finished ({}/{} chunks)",
+                    record.chunks_done, record.chunks_total
+                ),
+            ));
+        }
+    }
+
+    let (body, content_type) = match format.unwrap_or("srt") {
+        "srt" => (
+            SrtFile::from(&record.transcript()).to_string(),
+            CONTENT_TYPE_TEXT,
+        ),
+        "vtt" => (
+            SrtFile::from(&record.transcript()).to_vtt(),
+            CONTENT_TYPE_VTT,
+        ),
+        "json" => (
+            serde_json::to_string(&JobResultBody {
+                language: record.language.as_deref(),
+                duration_ms: record.duration_ms,
+                segments: &record.segments,
+            })
+            .unwrap_or_default(),
+            CONTENT_TYPE_JSON,
+        ),
+        other => {
+            return Err(JobFailure::new(
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+                format!("unsupported format '{}'; use srt, vtt or json", other),
+            ));
+        }
+    };
+    let mut response = server::response_with_status(StatusCode::OK, body.as_bytes());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    Ok(response)
+}
+
+/// Stream `body` to `path`; returns its size.
+async fn save_upload(path: &std::path::Path, body: Body) -> Result<u64, JobFailure> {
+    let mut file = tokio::fs::File::create(path).await.map_err(store_failed)?;
+    let mut stream = body.into_data_stream();
+    let mut size = 0;
+    while let Some(data) = stream.next().await {
+        let data = data.map_err(|e| {
+            JobFailure::new(
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+                format!("reading upload failed: {}", e),
+            )
+        })?;
+        size += data.len() as u64;
+        if size > MAX_UPLOAD_SIZE {
+            return Err(JobFailure::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+                "uploads are limited to 1 GiB",
+            ));
+        }
+        file.write_all(&data).await.map_err(store_failed)?;
+    }
+    if size == 0 {
+        return Err(JobFailure::new(
+            StatusCode::BAD_REQUEST,
+            "bad_request",
+            "empty audio data",
+        ));
+    }
+    file.flush().await.map_err(store_failed)?;
+    Ok(size)
+}
+
+fn store_failed(e: impl Into<anyhow::Error>) -> JobFailure {
+    warn!("Storing a job failed: {:#}", e.into());
+    JobFailure::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal",
+        "failed to store job",
+    )
+}
+
+fn jobs_enabled(state: &AppState) -> Result<&Arc<JobStore>, JobFailure> {
+    state.jobs().ok_or_else(|| {
+        JobFailure::new(
+            StatusCode::NOT_FOUND,
+            "jobs_disabled",
+            "batch jobs are disabled; start the server with --jobs-dir",
+        )
+    })
+}
+
+fn unknown_job() -> JobFailure {
+    JobFailure::new(StatusCode::NOT_FOUND, "unknown_job", "unknown job")
+}
+
+fn progress(record: &JobRecord, eta: Option<Duration>) -> JobProgress<'_> {
+    JobProgress {
+        id: &record.id,
+        status: record.status,
+        model: &record.model,
+        duration_ms: record.duration_ms,
+        chunks_done: record.chunks_done,
+        chunks_total: record.chunks_total,
+        eta_secs: eta.map(|eta| eta.as_secs_f64().ceil() as u64),
+        error: record.error.as_deref(),
+        waiting_for: record.waiting_for.as_deref(),
+        created_at: record.created_at,
+        finished_at: record.finished_at,
+    }
+}
+
+fn remove_file(path: &std::path::Path) {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            warn!("Removing job file {} failed: {}", path.display(), e);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(ms: u64, amplitude: i16) -> Vec<i16> {
+        (0..ms * SAMPLE_RATE as u64 / 1000)
+            .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_end_in_the_quietest_frame_near_their_limit() {
+        let steady = tone(65_000, 8_000);
+        assert_eq!(chunk_ends(&steady), [480_000, 960_000, 1_040_000]);
+
+        let mut paused = tone(27_000, 8_000);
+        paused.extend(tone(200, 10));
+        paused.extend(tone(40_000, 8_000));
+        let ends = chunk_ends(&paused);
+        assert_eq!(ends.len(), 3);
+        assert!((432_000..=435_200).contains(&ends[0]), "{:?}", ends);
+        assert!(ends[1] - ends[0] <= 480_000);
+        assert_eq!(ends[2], paused.len());
+    }
+}
@@ -0,0 +1,517 @@
+//! API keys: named secrets, each with optional requests-per-minute and
+//! audio-minutes-per-day quotas.
+//!
+//! Keys come from the `--api-keys` TOML file, which is re-read when it changes so keys
+//! can be added or revoked without a restart. A plain `--auth-secret` still works and
+//! acts as one unlimited key named `default`. Usage is kept per key name and survives
+//! reloads; daily quotas reset at midnight UTC.
+
+use crate::metrics::KeyUsage;
+use anyhow::{Context, Result, bail};
+use figment::{
+    Figment,
+    providers::{Format, Toml},
+};
+use mpv_stt_crypto::AuthToken;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often the keys file is checked for changes.
+pub(crate) const KEYS_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+const LEGACY_KEY_NAME: &str = "default";
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// `[[keys]]` entry of the `--api-keys` file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyEntry {
+    name: String,
+    /// The secret clients send; prefer `secret_file` to keep it out of the keys file.
+    secret: Option<String>,
+    secret_file: Option<PathBuf>,
+    requests_per_minute: Option<u32>,
+    audio_minutes_per_day: Option<u64>,
+    /// Rejects the key while keeping its usage history.
+    #[serde(default)]
+    revoked: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeysFile {
+    keys: Vec<KeyEntry>,
+}
+
+struct ApiKey {
+    name: String,
+    token: AuthToken,
+    requests_per_minute: Option<u32>,
+    audio_ms_per_day: Option<u64>,
+    revoked: bool,
+}
+
+impl ApiKey {
+    fn unlimited(name: &str, secret: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            token: AuthToken::from_secret(secret),
+            requests_per_minute: None,
+            audio_ms_per_day: None,
+            revoked: false,
+        }
+    }
+}
+
+fn load_keys(path: &Path) -> Result<Vec<ApiKey>> {
+    let file: KeysFile = Figment::from(Toml::file_exact(path))
+        .extract()
+        .with_context(|| format!("reading API keys file {}", path.display()))?;
+
+    let mut names = HashSet::new();
+    let mut keys = Vec::with_capacity(file.keys.len());
+    for entry in file.keys {
+        if entry.name.is_empty() {
+            bail!("API key names must not be empty");
+        }
+        if !names.insert(entry.name.clone()) {
+            bail!("API key '{}' is defined twice", entry.name);
+        }
+        let secret = match (entry.secret, &entry.secret_file) {
+            (Some(secret), None) => secret,
+            (None, Some(file)) => std::fs::read_to_string(file)
+                .with_context(|| format!("reading secret of API key '{}'", entry.name))?
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+            _ => bail!(
+                "API key '{}' needs exactly one of secret or secret_file",
+                entry.name
+            ),
+        };
+        if secret.is_empty() {
+            bail!("API key '{}' has an empty secret", entry.name);
+        }
+        keys.push(ApiKey {
+            token: AuthToken::from_secret(&secret),
+            requests_per_minute: entry.requests_per_minute,
+            audio_ms_per_day: entry.audio_minutes_per_day.map(|minutes| minutes * 60_000),
+            revoked: entry.revoked,
+            name: entry.name,
+        });
+    }
+    Ok(keys)
+}
+
+/// Why a request was refused.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Denied {
+    Unauthorized,
+    Revoked,
+    RateLimited { retry_after: Duration },
+    QuotaExceeded { retry_after: Duration },
+}
+
+/// The key a request was made with; anonymous when the server has no keys. A job may
+/// only be cancelled by an equal caller.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Caller {
+    key: Option<String>,
+    /// `x-cancel-token` of the request; without keys, it alone tells clients apart.
+    cancel_token: Option<String>,
+}
+
+impl Caller {
+    /// The caller behind work stored under `key`, e.g. a batch job resumed after a restart.
+    pub fn with_key_name(key: Option<String>) -> Self {
+        Self {
+            key,
+            cancel_token: None,
+        }
+    }
+
+    /// Attaches the request's cancel token; ignored for key holders, who cancel by key.
+    pub fn with_cancel_token(self, cancel_token: Option<String>) -> Self {
+        Self {
+            cancel_token: cancel_token.filter(|_| self.key.is_none()),
+            ..self
+        }
+    }
+
+    /// Whether the caller is told apart from other clients, by key or cancel token.
+    pub fn is_identified(&self) -> bool {
+        self.key.is_some() || self.cancel_token.is_some()
+    }
+
+    pub fn key_name(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    recent_requests: VecDeque<Instant>,
+    /// Days since the Unix epoch that `audio_ms_today` belongs to.
+    day: u64,
+    audio_ms_today: u64,
+    requests: u64,
+    rejected: u64,
+    audio_ms: u64,
+}
+
+impl Usage {
+    fn roll_over(&mut self, today: u64) {
+        if self.day != today {
+            self.day = today;
+            self.audio_ms_today = 0;
+        }
+    }
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+pub(crate) struct KeyStore {
+    /// `--auth-secret`, kept across reloads of the file.
+    legacy: Option<ApiKey>,
+    keys: RwLock<Vec<ApiKey>>,
+    file: Mutex<Option<WatchedFile>>,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl KeyStore {
+    /// A store holding only the `--auth-secret` key, if any.
+    pub fn new(legacy_secret: &str) -> Self {
+        Self {
+            legacy: (!legacy_secret.is_empty())
+                .then(|| ApiKey::unlimited(LEGACY_KEY_NAME, legacy_secret)),
+            keys: RwLock::new(Vec::new()),
+            file: Mutex::new(None),
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Load keys from `path` and keep following its changes via [`Self::reload_if_changed`].
+    pub fn watch_file(&self, path: &Path) -> Result<()> {
+        self.load(path)?;
+        *self.file.lock().unwrap_or_else(|e| e.into_inner()) = Some(WatchedFile {
+            path: path.to_path_buf(),
+            modified: modified_time(path),
+        });
+        Ok(())
+    }
+
+    /// Re-read the keys file if it changed since the last load; a broken file keeps the
+    /// previous keys. Returns whether new keys were loaded.
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(watched) = file.as_mut() else {
+            return Ok(false);
+        };
+        let modified = modified_time(&watched.path);
+        if modified == watched.modified {
+            return Ok(false);
+        }
+        // Retry a broken file only after it changes again.
+        watched.modified = modified;
+        self.load(&watched.path)?;
+        Ok(true)
+    }
+
+    fn load(&self, path: &Path) -> Result<()> {
+        let keys = load_keys(path)?;
+        if self.legacy.is_some() && keys.iter().any(|key| key.name == LEGACY_KEY_NAME) {
+            bail!(
+                "API key name '{}' is reserved for --auth-secret",
+                LEGACY_KEY_NAME
+            );
+        }
+        *self.keys.write().unwrap_or_else(|e| e.into_inner()) = keys;
+        Ok(())
+    }
+
+    fn is_open(&self) -> bool {
+        self.legacy.is_none()
+            && self
+                .file
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_none()
+    }
+
+    /// Identify the caller by `token` without counting the request.
+    pub fn authenticate(&self, token: Option<&AuthToken>) -> Result<Caller, Denied> {
+        if self.is_open() {
+            return Ok(Caller::default());
+        }
+        let token = token.ok_or(Denied::Unauthorized)?;
+        if let Some(legacy) = self.legacy.as_ref().filter(|key| &key.token == token) {
+            return Ok(Caller::with_key_name(Some(legacy.name.clone())));
+        }
+        let keys = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        let key = keys
+            .iter()
+            .find(|key| &key.token == token)
+            .ok_or(Denied::Unauthorized)?;
+        if key.revoked {
+            return Err(Denied::Revoked);
+        }
+        Ok(Caller::with_key_name(Some(key.name.clone())))
+    }
+
+    /// Authenticate and count one request against the key's rate limit.
+    pub fn admit(&self, token: Option<&AuthToken>) -> Result<Caller, Denied> {
+        let caller = self.authenticate(token)?;
+        let Some((rpm, audio_limit)) = self.limits(&caller) else {
+            return Ok(caller);
+        };
+
+        let now = Instant::now();
+        let today = today();
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = usage
+            .entry(caller.key.clone().unwrap_or_default())
+            .or_default();
+        usage.roll_over(today);
+        while usage
+            .recent_requests
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= RATE_WINDOW)
+        {
+            usage.recent_requests.pop_front();
+        }
+
+        if rpm.is_some_and(|rpm| usage.recent_requests.len() >= rpm as usize) {
+            usage.rejected += 1;
+            let oldest = usage.recent_requests.front().copied().unwrap_or(now);
+            return Err(Denied::RateLimited {
+                retry_after: (oldest + RATE_WINDOW).saturating_duration_since(now),
+            });
+        }
+        if audio_limit.is_some_and(|limit| usage.audio_ms_today >= limit) {
+            usage.rejected += 1;
+            return Err(Denied::QuotaExceeded {
+                retry_after: until_tomorrow(),
+            });
+        }
+        usage.recent_requests.push_back(now);
+        usage.requests += 1;
+        Ok(caller)
+    }
+
+    /// Count `audio_ms` against the caller's daily quota before the audio is transcribed.
+    pub fn reserve_audio(&self, caller: &Caller, audio_ms: u64) -> Result<(), Denied> {
+        self.take_audio(caller, audio_ms, true)
+    }
+
+    /// Fail like [`reserve_audio`](Self::reserve_audio) if `audio_ms` does not fit in the
+    /// caller's quota, but leave it uncounted.
+    pub fn check_audio(&self, caller: &Caller, audio_ms: u64) -> Result<(), Denied> {
+        self.take_audio(caller, audio_ms, false)
+    }
+
+    fn take_audio(&self, caller: &Caller, audio_ms: u64, count: bool) -> Result<(), Denied> {
+        let Some(name) = caller.key_name() else {
+            return Ok(());
+        };
+        let limit = self.limits(caller).and_then(|(_, limit)| limit);
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = usage.entry(name.to_string()).or_default();
+        usage.roll_over(today());
+        if limit.is_some_and(|limit| usage.audio_ms_today + audio_ms > limit) {
+            usage.rejected += 1;
+            return Err(Denied::QuotaExceeded {
+                retry_after: until_tomorrow(),
+            });
+        }
+        if count {
+            usage.audio_ms_today += audio_ms;
+            usage.audio_ms += audio_ms;
+        }
+        Ok(())
+    }
+
+    /// Give back a reservation for audio that was never transcribed.
+    pub fn release_audio(&self, caller: &Caller, audio_ms: u64) {
+        let Some(name) = caller.key_name() else {
+            return;
+        };
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(usage) = usage.get_mut(name) {
+            usage.audio_ms_today = usage.audio_ms_today.saturating_sub(audio_ms);
+            usage.audio_ms = usage.audio_ms.saturating_sub(audio_ms);
+        }
+    }
+
+    /// `(requests_per_minute, audio_ms_per_day)` of the caller's key; `None` when anonymous.
+    fn limits(&self, caller: &Caller) -> Option<(Option<u32>, Option<u64>)> {
+        let name = caller.key_name()?;
+        if let Some(legacy) = self.legacy.as_ref().filter(|key| key.name == name) {
+            return Some((legacy.requests_per_minute, legacy.audio_ms_per_day));
+        }
+        let keys = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        let key = keys.iter().find(|key| key.name == name)?;
+        Some((key.requests_per_minute, key.audio_ms_per_day))
+    }
+
+    /// Per-key counters for `/metrics`, sorted by key name.
+    pub fn usage(&self) -> Vec<KeyUsage> {
+        let today = today();
+        let usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let mut keys: Vec<KeyUsage> = usage
+            .iter()
+            .map(|(name, usage)| KeyUsage {
+                name: name.clone(),
+                requests: usage.requests,
+                rejected: usage.rejected,
+                audio_ms: usage.audio_ms,
+                audio_ms_today: if usage.day == today {
+                    usage.audio_ms_today
+                } else {
+                    0
+                },
+            })
+            .collect();
+        keys.sort_by(|a, b| a.name.cmp(&b.name));
+        keys
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+pub(crate) fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn today() -> u64 {
+    unix_secs() / SECS_PER_DAY
+}
+
+fn until_tomorrow() -> Duration {
+    Duration::from_secs(SECS_PER_DAY - unix_secs() % SECS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_keys(file: &mut tempfile::NamedTempFile, contents: &str) {
+        use std::io::{Seek, Write};
+        file.as_file_mut().set_len(0).unwrap();
+        file.rewind().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn token(secret: &str) -> Option<AuthToken> {
+        Some(AuthToken::from_secret(secret))
+    }
+
+    #[test]
+    fn test_keys_enforce_rate_and_audio_quota() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write_keys(
+            &mut file,
+            r#"
+            [[keys]]
+            name = "team-a"
+            secret = "a-secret"
+            requests_per_minute = 2
+
+            [[keys]]
+            name = "team-b"
+            secret = "b-secret"
+            audio_minutes_per_day = 1
+            "#,
+        );
+        let store = KeyStore::new("");
+        store.watch_file(file.path()).unwrap();
+
+        assert_eq!(
+            store.admit(token("wrong").as_ref()).unwrap_err(),
+            Denied::Unauthorized
+        );
+        assert_eq!(store.admit(None).unwrap_err(), Denied::Unauthorized);
+
+        let a = token("a-secret");
+        assert_eq!(store.admit(a.as_ref()).unwrap().key_name(), Some("team-a"));
+        store.admit(a.as_ref()).unwrap();
+        assert!(matches!(
+            store.admit(a.as_ref()),
+            Err(Denied::RateLimited { retry_after }) if retry_after <= RATE_WINDOW
+        ));
+
+        let b = store.admit(token("b-secret").as_ref()).unwrap();
+        store.check_audio(&b, 100_000).unwrap();
+        store.reserve_audio(&b, 40_000).unwrap();
+        assert!(matches!(
+            store.check_audio(&b, 70_000),
+            Err(Denied::QuotaExceeded { .. })
+        ));
+        assert!(matches!(
+            store.reserve_audio(&b, 30_000),
+            Err(Denied::QuotaExceeded { .. })
+        ));
+        store.release_audio(&b, 40_000);
+        store.reserve_audio(&b, 60_000).unwrap();
+        assert!(matches!(
+            store.admit(token("b-secret").as_ref()),
+            Err(Denied::QuotaExceeded { .. })
+        ));
+
+        let usage = store.usage();
+        assert_eq!(usage[0].name, "team-a");
+        assert_eq!((usage[0].requests, usage[0].rejected), (2, 1));
+        assert_eq!(usage[1].audio_ms_today, 60_000);
+        assert_eq!(usage[1].rejected, 2);
+    }
+
+    #[test]
+    fn test_revocation_applies_on_reload() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write_keys(&mut file, "[[keys]]\nname = \"a\"\nsecret = \"s\"\n");
+        let store = KeyStore::new("legacy");
+        store.watch_file(file.path()).unwrap();
+        assert!(store.admit(token("s").as_ref()).is_ok());
+        assert_eq!(
+            store.admit(token("legacy").as_ref()).unwrap().key_name(),
+            Some(LEGACY_KEY_NAME)
+        );
+        assert!(!store.reload_if_changed().unwrap());
+
+        // Force a different mtime even on coarse-grained filesystems.
+        write_keys(
+            &mut file,
+            "[[keys]]\nname = \"a\"\nsecret = \"s\"\nrevoked = true\n",
+        );
+        file.as_file()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert!(store.reload_if_changed().unwrap());
+        assert_eq!(
+            store.admit(token("s").as_ref()).unwrap_err(),
+            Denied::Revoked
+        );
+
+        // A broken file keeps the keys loaded before it.
+        write_keys(&mut file, "[[keys]]\nname = \"a\"\n");
+        file.as_file()
+            .set_modified(SystemTime::now() + Duration::from_secs(20))
+            .unwrap();
+        assert!(store.reload_if_changed().is_err());
+        assert_eq!(
+            store.admit(token("s").as_ref()).unwrap_err(),
+            Denied::Revoked
+        );
+    }
+}
@@ -0,0 +1,578 @@
+//! `GET /live`: WebSocket transcription of a continuous audio stream.
+//!
+//! Binary messages carry audio: raw 16 kHz mono s16le PCM, or `[u32_le_len][packet]...`
+//! Opus when the upgrade request sends `x-compression: opus`. Text messages are
+//! [`LiveControl`] JSON. Audio is buffered into a window that is re-transcribed as it
+//! grows ([`LiveEvent::Partial`]) and finalised once an energy VAD sees the speaker pause
+//! ([`LiveEvent::Final`]); a window that gets too long is finalised up to its last quiet
+//! frame and the rest carried over. Partial passes run beside the receive loop, one at a
+//! time and at prefetch priority; only finals run at realtime. Timestamps are absolute:
+//! the `x-stream-start-ms` header plus the audio received so far. `x-model` and the option
+//! headers accepted by `/transcribe` apply to the whole session.
+
+use crate::audio::OpusDecoder;
+use crate::keys::Caller;
+use crate::server::{self, AppState, Billing};
+use axum::{
+    extract::{
+        State,
+        rejection::WebSocketUpgradeRejection,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use log::{debug, warn};
+use mpv_stt_common::{PcmBuffer, TranscriptSegment, TranscriptionOptions, rms_db};
+use mpv_stt_protocol::{JobPriority, LiveControl, LiveEvent, TranscriptionJob};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task::JoinHandle;
+
+const SAMPLE_RATE: u32 = 16_000;
+const SAMPLES_PER_MS: usize = (SAMPLE_RATE / 1000) as usize;
+const VAD_FRAME_MS: u64 = 30;
+const SPEECH_THRESHOLD_DB: f32 = -40.0;
+/// Trailing silence that ends an utterance.
+const END_OF_SPEECH_MS: u64 = 600;
+/// New audio needed before the partial hypothesis is refreshed.
+const PARTIAL_STEP_MS: u64 = 1_000;
+/// Longest window transcribed at once; longer speech is cut and finalised.
+const MAX_WINDOW_MS: u64 = 15_000;
+/// Stretch at the end of an overlong window searched for a pause to cut at.
+const CUT_SEARCH_MS: u64 = 5_000;
+/// Silence kept ahead of speech so the first word is not clipped.
+const PRE_ROLL_MS: u64 = 300;
+
+const COMPRESSION_PCM: &str = "pcm";
+const COMPRESSION_OPUS: &str = "opus";
+
+enum AudioDecoder {
+    Pcm,
+    Opus(Box<OpusDecoder>),
+}
+
+impl AudioDecoder {
+    fn decode(&mut self, data: &[u8]) -> Result<Vec<i16>, String> {
+        match self {
+            AudioDecoder::Pcm => {
+                if !data.len().is_multiple_of(2) {
+                    return Err("PCM frames must hold whole 16-bit samples".to_string());
+                }
+                Ok(data
+                    .chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                    .collect())
+            }
+            AudioDecoder::Opus(decoder) => decoder.decode_framed(data).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// What the window needs after new audio arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WindowAction {
+    Wait,
+    Partial,
+    Final,
+    /// Only silence so far; drop all but the pre-roll.
+    Trim,
+}
+
+/// Audio not yet finalised, with the VAD state needed to decide when to transcribe it.
+struct SlidingWindow {
+    samples: Vec<i16>,
+    /// Absolute time of the first sample of the stream.
+    origin_ms: u64,
+    /// Samples of the stream before `samples[0]`; kept in samples so that trims which are
+    /// not whole milliseconds do not make timestamps drift.
+    consumed: u64,
+    /// Samples already classified by the VAD.
+    analysed: usize,
+    speech_seen: bool,
+    trailing_silence_ms: u64,
+    /// Window length when the last partial was produced.
+    partial_at_ms: u64,
+}
+
+impl SlidingWindow {
+    fn new(origin_ms: u64) -> Self {
+        Self {
+            samples: Vec::new(),
+            origin_ms,
+            consumed: 0,
+            analysed: 0,
+            speech_seen: false,
+            trailing_silence_ms: 0,
+            partial_at_ms: 0,
+        }
+    }
+
+    fn duration_ms(&self) -> u64 {
+        (self.samples.len() / SAMPLES_PER_MS) as u64
+    }
+
+    /// Absolute time of `samples[0]`.
+    fn start_ms(&self) -> u64 {
+        self.position_ms(self.consumed)
+    }
+
+    fn end_ms(&self) -> u64 {
+        self.position_ms(self.consumed + self.samples.len() as u64)
+    }
+
+    fn position_ms(&self, samples: u64) -> u64 {
+        self.origin_ms + samples / SAMPLES_PER_MS as u64
+    }
+
+    fn push(&mut self, samples: &[i16]) {
+        self.samples.extend_from_slice(samples);
+        self.analyse();
+    }
+
+    /// Classify the whole frames the VAD has not seen yet.
+    fn analyse(&mut self) {
+        let frame_len = VAD_FRAME_MS as usize * SAMPLES_PER_MS;
+        while self.analysed + frame_len <= self.samples.len() {
+            let frame = &self.samples[self.analysed..self.analysed + frame_len];
+            if rms_db(frame) >= SPEECH_THRESHOLD_DB {
+                self.speech_seen = true;
+                self.trailing_silence_ms = 0;
+            } else {
+                self.trailing_silence_ms += VAD_FRAME_MS;
+            }
+            self.analysed += frame_len;
+        }
+    }
+
+    fn next_action(&self) -> WindowAction {
+        let duration_ms = self.duration_ms();
+        if !self.speech_seen {
+            if duration_ms > PRE_ROLL_MS * 2 {
+                WindowAction::Trim
+            } else {
+                WindowAction::Wait
+            }
+        } else if self.trailing_silence_ms >= END_OF_SPEECH_MS || duration_ms >= MAX_WINDOW_MS {
+            WindowAction::Final
+        } else if duration_ms - self.partial_at_ms >= PARTIAL_STEP_MS {
+            WindowAction::Partial
+        } else {
+            WindowAction::Wait
+        }
+    }
+
+    fn trim(&mut self) {
+        let keep = PRE_ROLL_MS as usize * SAMPLES_PER_MS;
+        let drop = self.samples.len().saturating_sub(keep);
+        self.advance(drop);
+    }
+
+    /// Current audio for a partial hypothesis.
+    fn snapshot(&mut self) -> PcmBuffer {
+        self.partial_at_ms = self.duration_ms();
+        PcmBuffer::new(self.samples.clone(), SAMPLE_RATE, 1)
+    }
+
+    /// Remove and return everything buffered, with its absolute start time.
+    fn take(&mut self) -> (u64, PcmBuffer) {
+        self.take_to(self.samples.len())
+    }
+
+    /// Remove and return the audio of a final pass: everything after a pause, otherwise
+    /// (the window outgrew [`MAX_WINDOW_MS`] mid-speech) up to the end of the last quiet
+    /// frame in its final [`CUT_SEARCH_MS`], so the word being spoken is not cut in two.
+    fn take_final(&mut self) -> (u64, PcmBuffer) {
+        if self.trailing_silence_ms >= END_OF_SPEECH_MS {
+            return self.take();
+        }
+        let frame_len = VAD_FRAME_MS as usize * SAMPLES_PER_MS;
+        let search_from = self
+            .samples
+            .len()
+            .saturating_sub(CUT_SEARCH_MS as usize * SAMPLES_PER_MS);
+        let end = (0..self.analysed / frame_len)
+            .rev()
+            .map(|frame| frame * frame_len)
+            .take_while(|&start| start >= search_from)
+            .find(|&start| rms_db(&self.samples[start..start + frame_len]) < SPEECH_THRESHOLD_DB)
+            .map_or(self.samples.len(), |start| start + frame_len);
+        self.take_to(end)
+    }
+
+    fn take_to(&mut self, end: usize) -> (u64, PcmBuffer) {
+        let start_ms = self.start_ms();
+        let pcm = PcmBuffer::new(self.samples[..end].to_vec(), SAMPLE_RATE, 1);
+        self.advance(end);
+        (start_ms, pcm)
+    }
+
+    fn advance(&mut self, samples: usize) {
+        self.samples.drain(..samples);
+        self.consumed += samples as u64;
+        self.analysed = 0;
+        self.speech_seen = false;
+        self.trailing_silence_ms = 0;
+        self.partial_at_ms = 0;
+        // What is left, a pre-roll or words carried over from a cut, is classified afresh.
+        self.analyse();
+    }
+}
+
+/// The partial pass in flight; its audio may be finalised before it finishes.
+type PartialPass = JoinHandle<Result<Vec<TranscriptSegment>, LiveEvent>>;
+
+/// Settings read from the upgrade request.
+struct LiveSession {
+    caller: Caller,
+    model: String,
+    start_ms: u64,
+    options: TranscriptionOptions,
+}
+
+pub(crate) async fn handle_live(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Response {
+    let response = upgrade(&state, &headers, ws);
+    server::record_failure(&state, &response);
+    response
+}
+
+fn upgrade(
+    state: &AppState,
+    headers: &HeaderMap,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Response {
+    // One admission per connection; finalised audio is charged, partial passes only
+    // checked against the quota.
+    let caller = match state.admit(headers) {
+        Ok(caller) => caller,
+        Err(failure) => return failure.into_response(),
+    };
+    let ws = match ws {
+        Ok(ws) => ws,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let header = |name: &str| headers.get(name).and_then(|h| h.to_str().ok());
+    let (model, options) = match state.request_target(headers) {
+        Ok(target) => target,
+        Err(failure) => return failure.into_response(),
+    };
+    let decoder = match header("x-compression").unwrap_or(COMPRESSION_PCM) {
+        COMPRESSION_PCM => AudioDecoder::Pcm,
+        COMPRESSION_OPUS => match OpusDecoder::new() {
+            Ok(decoder) => AudioDecoder::Opus(Box::new(decoder)),
+            Err(e) => {
+                return server::response_with_status(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    e.to_string().as_bytes(),
+                );
+            }
+        },
+        _ => {
+            return server::response_with_status(
+                StatusCode::BAD_REQUEST,
+                b"unsupported compression",
+            );
+        }
+    };
+    let session = LiveSession {
+        caller,
+        model,
+        start_ms: header("x-stream-start-ms")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0),
+        options,
+    };
+
+    let state = state.clone();
+    ws.on_upgrade(move |socket| run_session(state, socket, decoder, session))
+}
+
+async fn run_session(
+    state: AppState,
+    mut socket: WebSocket,
+    mut decoder: AudioDecoder,
+    session: LiveSession,
+) {
+    let session = Arc::new(session);
+    let mut window = SlidingWindow::new(session.start_ms);
+    let mut partial: Option<PartialPass> = None;
+
+    loop {
+        let ok = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(message)) => {
+                    receive(
+                        &state,
+                        &mut socket,
+                        &mut window,
+                        &session,
+                        &mut partial,
+                        &mut decoder,
+                        message,
+                    )
+                    .await
+                }
+                _ => false,
+            },
+            result = async { partial.as_mut().expect("guarded").await }, if partial.is_some() => {
+                partial = None;
+                match result {
+                    Ok(Ok(segments)) => send(&mut socket, &LiveEvent::Partial { segments }).await,
+                    // A later partial or the final pass covers the same audio.
+                    _ => true,
+                }
+            }
+        };
+        if !ok {
+            break;
+        }
+    }
+    cancel_partial(&mut partial);
+    debug!("Live session ended at {}ms", window.end_ms());
+}
+
+/// Handle one client message; `false` once the client is gone.
+async fn receive(
+    state: &AppState,
+    socket: &mut WebSocket,
+    window: &mut SlidingWindow,
+    session: &Arc<LiveSession>,
+    partial: &mut Option<PartialPass>,
+    decoder: &mut AudioDecoder,
+    message: Message,
+) -> bool {
+    match message {
+        Message::Binary(data) => match decoder.decode(&data) {
+            Ok(samples) => {
+                window.push(&samples);
+                advance(state, socket, window, session, partial).await
+            }
+            Err(message) => {
+                let event = LiveEvent::Error {
+                    code: "bad_request".to_string(),
+                    message,
+                };
+                send(socket, &event).await
+            }
+        },
+        Message::Text(text) => match serde_json::from_str::<LiveControl>(&text) {
+            Ok(LiveControl::Flush) => {
+                cancel_partial(partial);
+                flush(state, socket, window, session).await
+            }
+            Err(e) => {
+                let event = LiveEvent::Error {
+                    code: "bad_request".to_string(),
+                    message: format!("invalid control message: {}", e),
+                };
+                send(socket, &event).await
+            }
+        },
+        Message::Close(_) => false,
+        _ => true,
+    }
+}
+
+/// Act on the window after new audio; `false` once the client is gone.
+async fn advance(
+    state: &AppState,
+    socket: &mut WebSocket,
+    window: &mut SlidingWindow,
+    session: &Arc<LiveSession>,
+    partial: &mut Option<PartialPass>,
+) -> bool {
+    match window.next_action() {
+        WindowAction::Wait => true,
+        WindowAction::Trim => {
+            window.trim();
+            true
+        }
+        // While a pass is still running the audio keeps coming; the next chunk tries again.
+        WindowAction::Partial if partial.is_some() => true,
+        WindowAction::Partial => {
+            let start_ms = window.start_ms();
+            let pcm = window.snapshot();
+            let (state, session) = (state.clone(), Arc::clone(session));
+            *partial = Some(tokio::spawn(async move {
+                transcribe(&state, pcm, start_ms, &session, Billing::Preview).await
+            }));
+            true
+        }
+        WindowAction::Final => {
+            cancel_partial(partial);
+            let (start_ms, pcm) = window.take_final();
+            finalise(state, socket, start_ms, pcm, session).await
+        }
+    }
+}
+
+/// Stop a partial pass whose audio is about to be finalised; its job leaves the worker.
+fn cancel_partial(partial: &mut Option<PartialPass>) {
+    if let Some(pass) = partial.take() {
+        pass.abort();
+    }
+}
+
+async fn flush(
+    state: &AppState,
+    socket: &mut WebSocket,
+    window: &mut SlidingWindow,
+    session: &LiveSession,
+) -> bool {
+    if window.speech_seen {
+        let (start_ms, pcm) = window.take();
+        if !finalise(state, socket, start_ms, pcm, session).await {
+            return false;
+        }
+    } else {
+        window.take();
+    }
+    let until_ms = window.start_ms();
+    send(socket, &LiveEvent::Flushed { until_ms }).await
+}
+
+async fn finalise(
+    state: &AppState,
+    socket: &mut WebSocket,
+    start_ms: u64,
+    pcm: PcmBuffer,
+    session: &LiveSession,
+) -> bool {
+    let event = match transcribe(state, pcm, start_ms, session, Billing::Charged).await {
+        Ok(segments) => LiveEvent::Final { segments },
+        Err(event) => event,
+    };
+    send(socket, &event).await
+}
+
+/// Transcribe `pcm` through the worker pool; segments are shifted to `start_ms`.
+async fn transcribe(
+    state: &AppState,
+    pcm: PcmBuffer,
+    start_ms: u64,
+    session: &LiveSession,
+    billing: Billing,
+) -> Result<Vec<TranscriptSegment>, LiveEvent> {
+    let job = TranscriptionJob {
+        request_id: state.allocate_request_id(),
+        duration_ms: pcm.duration_ms(),
+        audio: pcm,
+        model: session.model.clone(),
+        options: session.options.clone(),
+        // Finals are the captions being read right now; partials only preview them and
+        // must not hold up other sessions' finals.
+        priority: match billing {
+            Billing::Charged => JobPriority::Realtime,
+            Billing::Preview => JobPriority::Prefetch,
+        },
+        on_segment: None,
+        enqueue_at: Instant::now(),
+        deadline: None,
+    };
+    match server::run_job(state, &session.caller, job, billing).await {
+        Ok((mut transcript, metrics)) => {
+            state.metrics.record_job(&metrics, transcript.duration_ms);
+            transcript.offset(start_ms);
+            Ok(transcript.segments)
+        }
+        Err(failure) => {
+            warn!(
+                "Live transcription at {}ms failed: {}",
+                start_ms, failure.message
+            );
+            state.metrics.record_error(failure.kind);
+            Err(LiveEvent::Error {
+                code: failure.kind.to_string(),
+                message: failure.message,
+            })
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, event: &LiveEvent) -> bool {
+    let text = serde_json::to_string(event).expect("live events serialize to JSON");
+    socket.send(Message::Text(text)).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(ms: u64, amplitude: i16) -> Vec<i16> {
+        (0..ms as usize * SAMPLES_PER_MS)
+            .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+            .collect()
+    }
+
+    #[test]
+    fn test_window_trims_silence_and_keeps_pre_roll() {
+        let mut window = SlidingWindow::new(10_000);
+        window.push(&tone(1_000, 0));
+        assert_eq!(window.next_action(), WindowAction::Trim);
+        window.trim();
+        assert_eq!(window.start_ms(), 10_700);
+        assert_eq!(window.duration_ms(), PRE_ROLL_MS);
+    }
+
+    #[test]
+    fn test_window_position_does_not_drift() {
+        let mut window = SlidingWindow::new(0);
+        for _ in 0..100 {
+            // 1.5 ms per trim.
+            window.push(&[0; 24]);
+            window.advance(24);
+        }
+        assert_eq!(window.start_ms(), 150);
+    }
+
+    #[test]
+    fn test_window_partials_then_final_after_pause() {
+        let mut window = SlidingWindow::new(0);
+        window.push(&tone(600, 8_000));
+        assert_eq!(window.next_action(), WindowAction::Wait);
+        window.push(&tone(600, 8_000));
+        assert_eq!(window.next_action(), WindowAction::Partial);
+        window.snapshot();
+        assert_eq!(window.next_action(), WindowAction::Wait);
+
+        window.push(&tone(END_OF_SPEECH_MS, 0));
+        assert_eq!(window.next_action(), WindowAction::Final);
+        let (start_ms, pcm) = window.take();
+        assert_eq!(start_ms, 0);
+        assert_eq!(pcm.duration_ms(), 1_800);
+        assert_eq!(window.start_ms(), 1_800);
+        assert_eq!(window.next_action(), WindowAction::Wait);
+    }
+
+    #[test]
+    fn test_window_cuts_long_speech() {
+        let mut window = SlidingWindow::new(0);
+        window.push(&tone(MAX_WINDOW_MS, 8_000));
+        assert_eq!(window.next_action(), WindowAction::Final);
+        // Without a pause the whole window goes.
+        let (_, pcm) = window.take_final();
+        assert_eq!(pcm.duration_ms(), MAX_WINDOW_MS);
+    }
+
+    #[test]
+    fn test_window_cuts_long_speech_at_its_last_pause() {
+        let mut window = SlidingWindow::new(0);
+        window.push(&tone(10_000, 8_000));
+        window.push(&tone(120, 0));
+        window.push(&tone(4_880, 8_000));
+        assert_eq!(window.next_action(), WindowAction::Final);
+
+        // The last whole quiet VAD frame ends at 10 110 ms.
+        let (start_ms, pcm) = window.take_final();
+        assert_eq!(start_ms, 0);
+        assert_eq!(pcm.duration_ms(), 10_110);
+        assert_eq!(window.start_ms(), 10_110);
+        assert_eq!(window.duration_ms(), 4_890);
+        assert!(window.speech_seen);
+    }
+}
@@ -1,61 +1,236 @@
+mod admin;
+mod audio;
+mod cache;
+mod jobs;
+mod keys;
+mod live;
+mod metrics;
+mod models;
+mod openai;
+mod options;
 mod server;
+mod settings;
+mod tls;
+mod transcribe;
 mod worker;
 
 use anyhow::Result;
 use clap::Parser;
 use log::info;
+use models::{ModelConfig, ModelEntry, ModelsFile};
+#[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
 use mpv_stt_plugin::LocalModelConfig;
+#[cfg(feature = "stt_mock")]
+use mpv_stt_plugin::MockSttConfig;
+use mpv_stt_plugin::SttRunnerConfig;
+use options::OptionLimits;
+use serde::Serialize;
+use settings::Settings;
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Parser, Debug)]
+/// Every option can also be set in the `--config` file or through an
+/// `MPV_STT_SERVER_<OPTION>` environment variable; flags take precedence over both.
+#[derive(Parser, Serialize, Debug)]
 #[command(author, version, about = "MPV STT HTTP Server", long_about = None)]
 struct Args {
-    /// HTTP bind address
-    #[arg(short, long, default_value = "0.0.0.0:9000")]
-    bind: String,
+    /// TOML config file (default: $MPV_STT_SERVER_CONFIG)
+    #[arg(short, long)]
+    #[serde(skip)]
+    config: Option<PathBuf>,
 
-    /// Path to Whisper model file
-    #[arg(short, long, default_value = "ggml-base.bin")]
-    model_path: String,
+    /// HTTP bind address [default: 0.0.0.0:9000]
+    #[arg(short, long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bind: Option<String>,
 
-    /// Number of CPU threads for inference
-    #[arg(short, long, default_value_t = 8)]
-    threads: u8,
+    /// Path to Whisper model file [default: ggml-base.bin]
+    #[arg(short, long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model_path: Option<String>,
 
-    /// Language code (e.g., "en", "zh", "auto")
-    #[arg(short, long, default_value = "auto")]
-    language: String,
+    /// TOML file with `[[models]]` entries to host several models; replaces --model-path
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    models: Option<PathBuf>,
+
+    /// Number of CPU threads for inference [default: 8]
+    #[arg(short, long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threads: Option<u8>,
 
-    /// GPU device ID (CUDA only)
-    #[arg(long, default_value_t = 0)]
-    gpu_device: i32,
+    /// Language code (e.g., "en", "zh", "auto") [default: auto]
+    #[arg(short, long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+
+    /// GPU device ID (CUDA only) [default: 0]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gpu_device: Option<i32>,
 
     /// Enable flash attention (CUDA only)
     #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
     flash_attn: bool,
 
-    /// Inference timeout in milliseconds
-    #[arg(long, default_value_t = 120000)]
-    timeout_ms: u64,
+    /// Inference timeout in milliseconds [default: 120000]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout_ms: Option<u64>,
+
+    /// Number of worker threads (per model) [default: 4]
+    #[arg(short, long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workers: Option<usize>,
+
+    /// Maximum number of queued jobs (per model) before requests are rejected with 503 [default: 64]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queue_capacity: Option<usize>,
 
-    /// Number of worker threads
-    #[arg(short, long, default_value_t = 4)]
-    workers: usize,
+    /// Run a one-shot warmup inference at startup to load model into memory [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warmup: Option<bool>,
+
+    /// Seconds in-flight jobs get to finish after Ctrl+C or SIGTERM [default: 30]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shutdown_timeout_secs: Option<u64>,
 
-    /// Run a one-shot warmup inference at startup to load model into memory
-    #[arg(long, default_value_t = true)]
-    warmup: bool,
+    /// Highest decoding temperature a request may ask for [default: 1.0]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_temperature: Option<f32>,
+
+    /// Longest initial prompt a request may send, in characters [default: 1000]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_prompt_chars: Option<usize>,
+
+    /// Reject requests asking for word-level timestamps
+    #[arg(long)]
+    #[serde(skip)]
+    no_word_timestamps: bool,
+
+    /// Size limit of the result cache in MiB; 0 disables caching [default: 64]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_size_mb: Option<u64>,
+
+    /// Seconds a cached result stays valid [default: 86400]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_ttl_secs: Option<u64>,
+
+    /// Store cached results in this directory instead of memory
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_dir: Option<PathBuf>,
+
+    /// Directory for batch jobs (POST /jobs), which resume after a restart; unset disables them
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jobs_dir: Option<PathBuf>,
 
     /// Enable AES-GCM encryption
     #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
     enable_encryption: bool,
 
-    /// Encryption passphrase (required if encryption enabled)
-    #[arg(long, default_value = "")]
-    encryption_key: String,
+    /// Encryption passphrase (required if encryption enabled; prefer --encryption-key-file)
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encryption_key: Option<String>,
+
+    /// File holding the encryption passphrase
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encryption_key_file: Option<PathBuf>,
+
+    /// Authorization secret (required for token validation; prefer --auth-secret-file)
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth_secret: Option<String>,
+
+    /// File holding the authorization secret
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth_secret_file: Option<PathBuf>,
 
-    /// Authorization secret (required for token validation)
-    #[arg(long, default_value = "")]
-    auth_secret: String,
+    /// Secret for the /admin endpoints (model reload, worker resizing, warmup); prefer --admin-secret-file
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin_secret: Option<String>,
+
+    /// File holding the admin secret
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin_secret_file: Option<PathBuf>,
+
+    /// TOML file of named API keys with per-key quotas; edits apply without a restart
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_keys: Option<PathBuf>,
+
+    /// PEM certificate chain; serves HTTPS when set (requires --tls-key)
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_key: Option<PathBuf>,
+
+    /// PEM CA; clients must present a certificate it issued (mTLS)
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_client_ca: Option<PathBuf>,
+
+    /// SRT fixture returned by the mock backend (empty: derive segments from audio energy)
+    #[cfg(feature = "stt_mock")]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mock_fixture: Option<String>,
+
+    /// Artificial per-request latency of the mock backend in milliseconds
+    #[cfg(feature = "stt_mock")]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mock_delay_ms: Option<u64>,
+
+    /// Make every Nth mock transcription fail (0 = never)
+    #[cfg(feature = "stt_mock")]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mock_fail_every: Option<u32>,
+}
+
+#[cfg(any(feature = "stt_local_cpu", feature = "stt_local_cuda"))]
+fn runner_config(settings: &Settings, model: &ModelEntry) -> SttRunnerConfig {
+    LocalModelConfig::new(model.path.clone())
+        .with_threads(model.threads.unwrap_or(settings.threads))
+        .with_language(
+            model
+                .language
+                .clone()
+                .unwrap_or_else(|| settings.language.clone()),
+        )
+        .with_gpu_device(settings.gpu_device)
+        .with_flash_attn(settings.flash_attn)
+        .with_timeout_ms(settings.timeout_ms)
+}
+
+#[cfg(feature = "stt_mock")]
+fn runner_config(settings: &Settings, _model: &ModelEntry) -> SttRunnerConfig {
+    MockSttConfig {
+        fixture_path: settings.mock_fixture.clone(),
+        delay_ms: settings.mock_delay_ms,
+        fail_every: settings.mock_fail_every,
+        ..Default::default()
+    }
 }
 
 #[tokio::main]
@@ -63,52 +238,116 @@ async fn main() -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
     let args = Args::parse();
+    let config_path = args.config.clone().or_else(Settings::config_path_from_env);
+    let mut settings = Settings::load(config_path.as_deref(), &args)?;
+    // The flag can only turn word timestamps off.
+    if args.no_word_timestamps {
+        settings.allow_word_timestamps = false;
+    }
 
     info!("MPV STT TCP Server starting");
-    info!("  Bind address: {}", args.bind);
-    info!("  Model: {}", args.model_path);
-    info!("  Threads: {}", args.threads);
-    info!("  Language: {}", args.language);
-    info!("  Workers: {}", args.workers);
+    info!("  Bind address: {}", settings.bind);
+    info!("  Threads: {}", settings.threads);
+    info!("  Language: {}", settings.language);
+    info!("  Workers: {}", settings.workers);
+    info!("  Queue capacity: {}", settings.queue_capacity);
+    info!(
+        "  Result cache: {}",
+        match (settings.cache_size_mb, &settings.cache_dir) {
+            (0, _) => "disabled".to_string(),
+            (mb, None) => format!("{} MiB in memory", mb),
+            (mb, Some(dir)) => format!("{} MiB in {}", mb, dir.display()),
+        }
+    );
+    info!(
+        "  Batch jobs: {}",
+        match &settings.jobs_dir {
+            Some(dir) => format!("stored in {}", dir.display()),
+            None => "disabled".to_string(),
+        }
+    );
     info!(
         "  Encryption: {}",
-        if args.enable_encryption {
+        if settings.enable_encryption {
             "enabled"
         } else {
             "disabled"
         }
     );
+    info!(
+        "  TLS: {}",
+        match (&settings.tls_cert, &settings.tls_client_ca) {
+            (None, _) => "disabled",
+            (Some(_), None) => "enabled",
+            (Some(_), Some(_)) => "enabled, client certificates required",
+        }
+    );
     info!(
         "  Auth: {}",
-        if !args.auth_secret.is_empty() {
-            "enabled"
-        } else {
+        match (&settings.api_keys, settings.auth_secret.is_empty()) {
+            (Some(_), _) => "API keys file",
+            (None, false) => "enabled",
+            (None, true) => "disabled",
+        }
+    );
+    info!(
+        "  Admin endpoints: {}",
+        if settings.admin_secret.is_empty() {
             "disabled"
+        } else {
+            "enabled"
         }
     );
 
-    if args.enable_encryption && args.encryption_key.is_empty() {
-        anyhow::bail!("--encryption-key is required when --enable-encryption is set");
+    let models_file = match &settings.models {
+        Some(path) => ModelsFile::load(path)?,
+        None => ModelsFile::single(&settings.model_path),
+    };
+    let models: Vec<ModelConfig> = models_file
+        .models
+        .iter()
+        .map(|model| ModelConfig {
+            name: model.name.clone(),
+            runner: runner_config(&settings, model),
+            workers: model.workers.unwrap_or(settings.workers),
+            queue_capacity: model.queue_capacity.unwrap_or(settings.queue_capacity),
+            languages: model.languages(),
+        })
+        .collect();
+    for model in &models_file.models {
+        info!(
+            "  Model {}: {} ({} workers)",
+            model.name,
+            model.path,
+            model.workers.unwrap_or(settings.workers)
+        );
     }
 
-    let whisper_config = LocalModelConfig::new(args.model_path)
-        .with_threads(args.threads)
-        .with_language(args.language)
-        .with_gpu_device(args.gpu_device)
-        .with_flash_attn(args.flash_attn)
-        .with_timeout_ms(args.timeout_ms);
-
     let server_config = server::ServerConfig {
-        enable_encryption: args.enable_encryption,
-        encryption_key: args.encryption_key,
-        auth_secret: args.auth_secret,
-        warmup: args.warmup,
+        tls: settings.tls_config(),
+        cache: settings.cache_config(),
+        jobs_dir: settings.jobs_dir,
+        enable_encryption: settings.enable_encryption,
+        encryption_key: settings.encryption_key,
+        auth_secret: settings.auth_secret,
+        admin_secret: settings.admin_secret,
+        api_keys: settings.api_keys,
+        warmup: settings.warmup,
+        shutdown_timeout: Duration::from_secs(settings.shutdown_timeout_secs),
+        default_model: models_file.default,
+        option_limits: OptionLimits {
+            max_temperature: settings.max_temperature,
+            max_prompt_chars: settings.max_prompt_chars,
+            allow_word_timestamps: settings.allow_word_timestamps,
+        },
     };
 
-    let server =
-        server::HttpServer::bind(&args.bind, whisper_config, args.workers, server_config).await?;
+    let server = server::HttpServer::bind(&settings.bind, models, server_config).await?;
 
-    info!("Server ready, processing HTTP requests...");
+    info!(
+        "Server ready on {}, processing HTTP requests...",
+        server.local_addr()
+    );
     server.run().await?;
 
     Ok(())
@@ -0,0 +1,442 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use mpv_stt_protocol::JobMetrics;
+
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+const RTF_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 0.75, 1.0, 1.5, 2.0];
+
+/// Point-in-time view of the worker pool, sampled when `/metrics` is scraped.
+pub struct PoolSnapshot {
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    pub workers: usize,
+    pub busy_workers: usize,
+}
+
+/// State of the result cache, sampled when `/metrics` is scraped.
+pub struct CacheSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+/// Usage of one API key, sampled when `/metrics` is scraped.
+pub struct KeyUsage {
+    pub name: String,
+    pub requests: u64,
+    /// Requests refused for exceeding a rate limit or quota.
+    pub rejected: u64,
+    pub audio_ms: u64,
+    /// Audio charged against today's quota (UTC).
+    pub audio_ms_today: u64,
+}
+
+/// Server-wide counters exported in Prometheus text format.
+pub struct ServerMetrics {
+    requests_ok: AtomicU64,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    audio_ms: AtomicU64,
+    queue_wait: Histogram,
+    inference: Histogram,
+    worker_total: Histogram,
+    real_time_factor: Histogram,
+}
+
+impl ServerMetrics {
+    pub fn new() -> Self {
+        Self {
+            requests_ok: AtomicU64::new(0),
+            errors: Mutex::new(BTreeMap::new()),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            audio_ms: AtomicU64::new(0),
+            queue_wait: Histogram::new(LATENCY_BUCKETS),
+            inference: Histogram::new(LATENCY_BUCKETS),
+            worker_total: Histogram::new(LATENCY_BUCKETS),
+            real_time_factor: Histogram::new(RTF_BUCKETS),
+        }
+    }
+
+    pub fn record_bytes(&self, bytes_in: usize, bytes_out: usize) {
+        self.bytes_in.fetch_add(bytes_in as u64, Ordering::Relaxed);
+        self.bytes_out
+            .fetch_add(bytes_out as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self, kind: &'static str) {
+        let mut errors = self.errors.lock().unwrap_or_else(|e| e.into_inner());
+        *errors.entry(kind).or_default() += 1;
+    }
+
+    /// Record a finished job; `audio_ms` is the length of the transcribed audio.
+    pub fn record_job(&self, metrics: &JobMetrics, audio_ms: u64) {
+        self.requests_ok.fetch_add(1, Ordering::Relaxed);
+        self.audio_ms.fetch_add(audio_ms, Ordering::Relaxed);
+        // Cached results never reached a worker; keep them out of the latency histograms.
+        if metrics.cache_hit {
+            return;
+        }
+        self.queue_wait.observe(ms_to_secs(metrics.queue_wait_ms));
+        self.inference.observe(ms_to_secs(metrics.inference_ms));
+        self.worker_total
+            .observe(ms_to_secs(metrics.worker_total_ms));
+        if audio_ms > 0 {
+            self.real_time_factor
+                .observe(metrics.inference_ms as f64 / audio_ms as f64);
+        }
+    }
+
+    pub fn render(
+        &self,
+        pool: &PoolSnapshot,
+        cache: Option<&CacheSnapshot>,
+        keys: &[KeyUsage],
+        ready: bool,
+    ) -> String {
+        let mut out = String::new();
+
+        gauge(
+            &mut out,
+            "mpv_stt_ready",
+            "Whether the server accepts work (1) or not (0).",
+            ready as u64,
+        );
+        gauge(
+            &mut out,
+            "mpv_stt_queue_depth",
+            "Jobs waiting for a worker.",
+            pool.queue_depth as u64,
+        );
+        gauge(
+            &mut out,
+            "mpv_stt_queue_capacity",
+            "Maximum number of queued jobs.",
+            pool.queue_capacity as u64,
+        );
+        gauge(
+            &mut out,
+            "mpv_stt_workers",
+            "Number of inference workers.",
+            pool.workers as u64,
+        );
+        gauge(
+            &mut out,
+            "mpv_stt_workers_busy",
+            "Workers currently running inference.",
+            pool.busy_workers as u64,
+        );
+        let utilisation = if pool.workers > 0 {
+            pool.busy_workers as f64 / pool.workers as f64
+        } else {
+            0.0
+        };
+        header(
+            &mut out,
+            "mpv_stt_worker_utilisation",
+            "Fraction of workers currently busy.",
+            "gauge",
+        );
+        let _ = writeln!(out, "mpv_stt_worker_utilisation {}", utilisation);
+
+        counter(
+            &mut out,
+            "mpv_stt_requests_total",
+            "Transcriptions completed successfully.",
+            self.requests_ok.load(Ordering::Relaxed),
+        );
+        header(
+            &mut out,
+            "mpv_stt_errors_total",
+            "Failed requests by kind.",
+            "counter",
+        );
+        for (kind, count) in self.errors.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            let _ = writeln!(out, "mpv_stt_errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+        counter(
+            &mut out,
+            "mpv_stt_bytes_in_total",
+            "Request body bytes received.",
+            self.bytes_in.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "mpv_stt_bytes_out_total",
+            "Response body bytes sent.",
+            self.bytes_out.load(Ordering::Relaxed),
+        );
+        header(
+            &mut out,
+            "mpv_stt_audio_seconds_total",
+            "Seconds of audio transcribed.",
+            "counter",
+        );
+        let _ = writeln!(
+            out,
+            "mpv_stt_audio_seconds_total {}",
+            ms_to_secs(self.audio_ms.load(Ordering::Relaxed))
+        );
+        if let Some(cache) = cache {
+            render_cache(&mut out, cache);
+        }
+        render_keys(&mut out, keys);
+
+        self.queue_wait.render(
+            &mut out,
+            "mpv_stt_queue_wait_seconds",
+            "Time jobs spent queued.",
+        );
+        self.inference.render(
+            &mut out,
+            "mpv_stt_inference_seconds",
+            "Model inference time per job.",
+        );
+        self.worker_total.render(
+            &mut out,
+            "mpv_stt_worker_seconds",
+            "Worker time per job, including inference.",
+        );
+        self.real_time_factor.render(
+            &mut out,
+            "mpv_stt_real_time_factor",
+            "Inference time divided by audio duration.",
+        );
+
+        out
+    }
+}
+
+impl Default for ServerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Histogram {
+    bounds: &'static [f64],
+    data: Mutex<HistogramData>,
+}
+
+#[derive(Default)]
+struct HistogramData {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            data: Mutex::new(HistogramData {
+                buckets: vec![0; bounds.len()],
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        let mut data = self.data.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(idx) = self.bounds.iter().position(|bound| value <= *bound) {
+            data.buckets[idx] += 1;
+        }
+        data.sum += value;
+        data.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let data = self.data.lock().unwrap_or_else(|e| e.into_inner());
+        header(out, name, help, "histogram");
+        // Prometheus buckets are cumulative.
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&data.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, data.count);
+        let _ = writeln!(out, "{}_sum {}", name, data.sum);
+        let _ = writeln!(out, "{}_count {}", name, data.count);
+    }
+}
+
+fn render_cache(out: &mut String, cache: &CacheSnapshot) {
+    counter(
+        out,
+        "mpv_stt_cache_hits_total",
+        "Transcriptions served from the result cache.",
+        cache.hits,
+    );
+    counter(
+        out,
+        "mpv_stt_cache_misses_total",
+        "Transcriptions not found in the result cache.",
+        cache.misses,
+    );
+    gauge(
+        out,
+        "mpv_stt_cache_entries",
+        "Results held in the cache.",
+        cache.entries as u64,
+    );
+    gauge(
+        out,
+        "mpv_stt_cache_bytes",
+        "Size of the cached results.",
+        cache.bytes,
+    );
+}
+
+fn render_keys(out: &mut String, keys: &[KeyUsage]) {
+    if keys.is_empty() {
+        return;
+    }
+    header(
+        out,
+        "mpv_stt_key_requests_total",
+        "Transcription requests admitted per API key.",
+        "counter",
+    );
+    for key in keys {
+        let _ = writeln!(
+            out,
+            "mpv_stt_key_requests_total{{key=\"{}\"}} {}",
+            key.name, key.requests
+        );
+    }
+    header(
+        out,
+        "mpv_stt_key_rejected_total",
+        "Requests refused per API key for exceeding its limits.",
+        "counter",
+    );
+    for key in keys {
+        let _ = writeln!(
+            out,
+            "mpv_stt_key_rejected_total{{key=\"{}\"}} {}",
+            key.name, key.rejected
+        );
+    }
+    header(
+        out,
+        "mpv_stt_key_audio_seconds_total",
+        "Seconds of audio submitted per API key.",
+        "counter",
+    );
+    for key in keys {
+        let _ = writeln!(
+            out,
+            "mpv_stt_key_audio_seconds_total{{key=\"{}\"}} {}",
+            key.name,
+            ms_to_secs(key.audio_ms)
+        );
+    }
+    header(
+        out,
+        "mpv_stt_key_audio_seconds_today",
+        "Seconds of audio charged against today's quota (UTC) per API key.",
+        "gauge",
+    );
+    for key in keys {
+        let _ = writeln!(
+            out,
+            "mpv_stt_key_audio_seconds_today{{key=\"{}\"}} {}",
+            key.name,
+            ms_to_secs(key.audio_ms_today)
+        );
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "gauge");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+pub(crate) fn ms_to_secs(ms: u64) -> f64 {
+    ms as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_histograms_and_errors() {
+        let metrics = ServerMetrics::new();
+        let job = |inference_ms| JobMetrics {
+            queue_wait_ms: 20,
+            inference_ms,
+            worker_total_ms: inference_ms + 5,
+            cache_hit: false,
+        };
+        metrics.record_job(&job(80), 1_000);
+        metrics.record_job(&job(3_000), 10_000);
+        metrics.record_job(
+            &JobMetrics {
+                cache_hit: true,
+                ..Default::default()
+            },
+            2_000,
+        );
+        metrics.record_error("bad_request");
+        metrics.record_error("bad_request");
+        metrics.record_bytes(100, 40);
+
+        let text = metrics.render(
+            &PoolSnapshot {
+                queue_depth: 2,
+                queue_capacity: 8,
+                workers: 4,
+                busy_workers: 1,
+            },
+            Some(&CacheSnapshot {
+                hits: 3,
+                misses: 2,
+                entries: 2,
+                bytes: 512,
+            }),
+            &[KeyUsage {
+                name: "team-a".to_string(),
+                requests: 3,
+                rejected: 1,
+                audio_ms: 90_000,
+                audio_ms_today: 1_500,
+            }],
+            true,
+        );
+
+        assert!(text.contains("mpv_stt_queue_depth 2\n"));
+        assert!(text.contains("mpv_stt_worker_utilisation 0.25\n"));
+        assert!(text.contains("mpv_stt_requests_total 3\n"));
+        assert!(text.contains("mpv_stt_errors_total{kind=\"bad_request\"} 2\n"));
+        assert!(text.contains("mpv_stt_bytes_in_total 100\n"));
+        assert!(text.contains("mpv_stt_audio_seconds_total 13\n"));
+        assert!(text.contains("mpv_stt_cache_hits_total 3\n"));
+        assert!(text.contains("mpv_stt_cache_bytes 512\n"));
+        assert!(text.contains("mpv_stt_key_rejected_total{key=\"team-a\"} 1\n"));
+        assert!(text.contains("mpv_stt_key_audio_seconds_total{key=\"team-a\"} 90\n"));
+        assert!(text.contains("mpv_stt_key_audio_seconds_today{key=\"team-a\"} 1.5\n"));
+        assert!(text.contains("mpv_stt_inference_seconds_bucket{le=\"0.1\"} 1\n"));
+        assert!(text.contains("mpv_stt_inference_seconds_bucket{le=\"2.5\"} 1\n"));
+        assert!(text.contains("mpv_stt_inference_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(text.contains("mpv_stt_inference_seconds_count 2\n"));
+        assert!(text.contains("mpv_stt_real_time_factor_bucket{le=\"0.1\"} 1\n"));
+        assert!(text.contains("mpv_stt_real_time_factor_bucket{le=\"0.25\"} 1\n"));
+        assert!(text.contains("mpv_stt_real_time_factor_bucket{le=\"0.5\"} 2\n"));
+    }
+}
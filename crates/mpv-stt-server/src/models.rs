@@ -0,0 +1,364 @@
+//! Named models hosted side by side, each with its own worker pool.
+//!
+//! A model's pool can be swapped at runtime, e.g. to reload the model file or change its
+//! worker count: new requests go to the new pool while the old one drains in the
+//! background.
+
+use crate::keys::Caller;
+use crate::metrics::PoolSnapshot;
+use crate::worker::WorkerPool;
+use anyhow::{Context, Result, bail};
+use figment::{
+    Figment,
+    providers::{Format, Toml},
+};
+use log::info;
+use mpv_stt_plugin::SttRunnerConfig;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Duration;
+
+/// Language codes Whisper's multilingual models can transcribe.
+pub(crate) const WHISPER_LANGUAGES: &[&str] = &[
+    "en", "zh", "de", "es", "ru", "ko", "fr", "ja", "pt", "tr", "pl", "ca", "nl", "ar", "sv", "it",
+    "id", "hi", "fi", "vi", "he", "uk", "el", "ms", "cs", "ro", "da", "hu", "ta", "no", "th", "ur",
+    "hr", "bg", "lt", "la", "mi", "ml", "cy", "sk", "te", "fa", "lv", "bn", "sr", "az", "sl", "kn",
+    "et", "mk", "br", "eu", "is", "hy", "ne", "mn", "bs", "kk", "sq", "sw", "gl", "mr", "pa", "si",
+    "km", "sn", "yo", "so", "af", "oc", "ka", "be", "tg", "sd", "gu", "am", "yi", "lo", "uz", "fo",
+    "ht", "ps", "tk", "nn", "mt", "sa", "lb", "my", "bo", "tl", "mg", "as", "tt", "haw", "ln",
+    "ha", "ba", "jw", "su", "yue",
+];
+
+/// How often a replaced pool is checked for remaining work.
+const RETIRE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Name of the model served when `--models` is not given.
+pub const DEFAULT_MODEL_NAME: &str = "default";
+
+/// `[[models]]` entry of the `--models` file; unset fields fall back to the command line.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelEntry {
+    pub name: String,
+    pub path: String,
+    pub workers: Option<usize>,
+    pub queue_capacity: Option<usize>,
+    // Only the Whisper backends take thread count and default language.
+    #[cfg_attr(feature = "stt_mock", allow(dead_code))]
+    pub threads: Option<u8>,
+    #[cfg_attr(feature = "stt_mock", allow(dead_code))]
+    pub language: Option<String>,
+    /// Advertised by `/v1/models`; defaults to `en` for `.en` models and all Whisper languages otherwise.
+    pub languages: Option<Vec<String>>,
+}
+
+impl ModelEntry {
+    pub fn languages(&self) -> Vec<String> {
+        if let Some(languages) = &self.languages {
+            return languages.clone();
+        }
+        let file_name = Path::new(&self.path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        if file_name.contains(".en.") {
+            vec!["en".to_string()]
+        } else {
+            WHISPER_LANGUAGES
+                .iter()
+                .map(|lang| lang.to_string())
+                .collect()
+        }
+    }
+}
+
+/// Contents of the `--models` TOML file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelsFile {
+    /// Model used when a request names none; defaults to the first entry.
+    pub default: Option<String>,
+    pub models: Vec<ModelEntry>,
+}
+
+impl ModelsFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file: Self = Figment::from(Toml::file_exact(path))
+            .extract()
+            .with_context(|| format!("reading models file {}", path.display()))?;
+
+        if file.models.is_empty() {
+            bail!("{} defines no [[models]]", path.display());
+        }
+        let mut names = HashSet::new();
+        for model in &file.models {
+            if model.name.is_empty() {
+                bail!("model names must not be empty");
+            }
+            if !names.insert(model.name.as_str()) {
+                bail!("model '{}' is defined twice", model.name);
+            }
+            if model.workers == Some(0) {
+                bail!("model '{}' needs at least one worker", model.name);
+            }
+        }
+        if let Some(default) = file
+            .default
+            .as_deref()
+            .filter(|default| !names.contains(default))
+        {
+            bail!("default model '{}' is not defined", default);
+        }
+        Ok(file)
+    }
+
+    /// The single model given by `--model-path`.
+    pub fn single(path: &str) -> Self {
+        Self {
+            default: None,
+            models: vec![ModelEntry {
+                name: DEFAULT_MODEL_NAME.to_string(),
+                path: path.to_string(),
+                workers: None,
+                queue_capacity: None,
+                threads: None,
+                language: None,
+                languages: None,
+            }],
+        }
+    }
+}
+
+/// Everything needed to start serving one model.
+pub struct ModelConfig {
+    pub name: String,
+    pub runner: SttRunnerConfig,
+    pub workers: usize,
+    pub queue_capacity: usize,
+    pub languages: Vec<String>,
+}
+
+pub(crate) struct HostedModel {
+    pub name: String,
+    pub pool: WorkerPool,
+    /// Kept for warmup runs outside the pool.
+    pub runner: SttRunnerConfig,
+    pub languages: Vec<String>,
+}
+
+impl HostedModel {
+    pub fn start(config: ModelConfig) -> Self {
+        Self {
+            pool: WorkerPool::new(config.runner.clone(), config.workers, config.queue_capacity),
+            name: config.name,
+            runner: config.runner,
+            languages: config.languages,
+        }
+    }
+
+    /// The same model on a fresh pool of `workers` workers, which load the model file anew.
+    pub fn restart(&self, workers: usize) -> Self {
+        Self::start(ModelConfig {
+            name: self.name.clone(),
+            runner: self.runner.clone(),
+            workers,
+            queue_capacity: self.pool.queue_capacity(),
+            languages: self.languages.clone(),
+        })
+    }
+}
+
+pub(crate) struct ModelRegistry {
+    models: RwLock<Vec<Arc<HostedModel>>>,
+    /// Models swapped out by [`Self::replace`] whose pools may still be draining.
+    retired: Mutex<Vec<Weak<HostedModel>>>,
+    default: usize,
+}
+
+impl ModelRegistry {
+    /// `default` names the model used when a request names none; the first one otherwise.
+    pub fn new(models: Vec<HostedModel>, default: Option<&str>) -> Self {
+        assert!(!models.is_empty(), "at least one model must be hosted");
+        let default = default
+            .and_then(|name| models.iter().position(|model| model.name == name))
+            .unwrap_or(0);
+        Self {
+            models: RwLock::new(models.into_iter().map(Arc::new).collect()),
+            retired: Mutex::new(Vec::new()),
+            default,
+        }
+    }
+
+    /// The models currently serving requests.
+    pub fn list(&self) -> Vec<Arc<HostedModel>> {
+        self.read().clone()
+    }
+
+    pub fn default_model(&self) -> Arc<HostedModel> {
+        Arc::clone(&self.read()[self.default])
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<HostedModel>> {
+        self.read().iter().find(|model| model.name == name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.read().iter().map(|model| model.name.clone()).collect()
+    }
+
+    /// Serve `model` in place of the one with the same name and return the old one, which
+    /// still finishes the jobs it accepted; see [`Self::retire`].
+    pub fn replace(&self, model: HostedModel) -> Option<Arc<HostedModel>> {
+        let mut models = self.models.write().unwrap_or_else(|e| e.into_inner());
+        let slot = models.iter_mut().find(|hosted| hosted.name == model.name)?;
+        let old = std::mem::replace(slot, Arc::new(model));
+        drop(models);
+        let mut retired = self.retired.lock().unwrap_or_else(|e| e.into_inner());
+        retired.retain(|model| model.strong_count() > 0);
+        retired.push(Arc::downgrade(&old));
+        Some(old)
+    }
+
+    /// Wait until no request holds `model` and its pool has no work left, then stop its
+    /// workers.
+    pub async fn retire(model: Arc<HostedModel>) {
+        while Arc::strong_count(&model) > 1
+            || model.pool.queue_depth() > 0
+            || model.pool.busy_workers() > 0
+        {
+            tokio::time::sleep(RETIRE_POLL_INTERVAL).await;
+        }
+        model.pool.shutdown().await;
+        info!("Stopped the previous workers of model {}", model.name);
+    }
+
+    /// Cancel `request_id` in whichever pool holds it.
+    pub fn cancel_request(&self, request_id: u64) -> bool {
+        self.all()
+            .iter()
+            .any(|model| model.pool.cancel_request(request_id))
+    }
+
+    /// Cancel `request_id` only if `caller` submitted it.
+    pub fn cancel_request_of(&self, request_id: u64, caller: &Caller) -> bool {
+        self.all()
+            .iter()
+            .any(|model| model.pool.cancel_request_of(request_id, caller))
+    }
+
+    /// Drop or abort `request_id` in whichever pool holds it once its deadline passed.
+    pub fn expire_request(&self, request_id: u64) -> bool {
+        self.all()
+            .iter()
+            .any(|model| model.pool.expire_request(request_id))
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.read().iter().any(|model| model.pool.is_closed())
+    }
+
+    /// True when no job is queued or running in any pool.
+    pub fn is_idle(&self) -> bool {
+        let snapshot = self.snapshot();
+        snapshot.queue_depth == 0 && snapshot.busy_workers == 0
+    }
+
+    /// Cancel every queued and running job; returns how many there were.
+    pub fn cancel_all(&self) -> usize {
+        self.all().iter().map(|model| model.pool.cancel_all()).sum()
+    }
+
+    /// Stop every pool's workers and wait for them to exit.
+    pub async fn shutdown(&self) {
+        for model in self.all() {
+            model.pool.shutdown().await;
+        }
+    }
+
+    /// Pool gauges summed over all models.
+    pub fn snapshot(&self) -> PoolSnapshot {
+        self.all().iter().fold(
+            PoolSnapshot {
+                queue_depth: 0,
+                queue_capacity: 0,
+                workers: 0,
+                busy_workers: 0,
+            },
+            |total, model| PoolSnapshot {
+                queue_depth: total.queue_depth + model.pool.queue_depth(),
+                queue_capacity: total.queue_capacity + model.pool.queue_capacity(),
+                workers: total.workers + model.pool.num_workers(),
+                busy_workers: total.busy_workers + model.pool.busy_workers(),
+            },
+        )
+    }
+
+    /// Serving and still draining models.
+    fn all(&self) -> Vec<Arc<HostedModel>> {
+        let mut models = self.list();
+        let retired = self.retired.lock().unwrap_or_else(|e| e.into_inner());
+        models.extend(retired.iter().filter_map(Weak::upgrade));
+        models
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Vec<Arc<HostedModel>>> {
+        self.models.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_models_file(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, contents.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_models_file_parses_entries() {
+        let file = write_models_file(
+            r#"
+            default = "medium"
+
+            [[models]]
+            name = "tiny"
+            path = "models/ggml-tiny.en.bin"
+            workers = 2
+
+            [[models]]
+            name = "medium"
+            path = "models/ggml-medium.bin"
+            queue_capacity = 8
+            languages = ["de", "en"]
+            "#,
+        );
+        let models = ModelsFile::load(file.path()).unwrap();
+        assert_eq!(models.default.as_deref(), Some("medium"));
+        assert_eq!(models.models[0].workers, Some(2));
+        assert_eq!(models.models[0].languages(), ["en"]);
+        assert_eq!(models.models[1].languages(), ["de", "en"]);
+        assert_eq!(
+            ModelsFile::single("ggml-base.bin").models[0]
+                .languages()
+                .len(),
+            WHISPER_LANGUAGES.len()
+        );
+    }
+
+    #[test]
+    fn test_models_file_rejects_bad_definitions() {
+        for contents in [
+            "models = []",
+            "[[models]]\nname = \"a\"\npath = \"a.bin\"\n[[models]]\nname = \"a\"\npath = \"b.bin\"",
+            "default = \"b\"\n[[models]]\nname = \"a\"\npath = \"a.bin\"",
+            "[[models]]\nname = \"a\"\npath = \"a.bin\"\nworkers = 0",
+        ] {
+            let file = write_models_file(contents);
+            assert!(ModelsFile::load(file.path()).is_err(), "{}", contents);
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! OpenAI-compatible `/v1/audio/transcriptions` and `/v1/audio/translations`.
+//!
+//! Accepts the multipart form used by OpenAI SDKs and answers in the requested
+//! `response_format`. Errors use OpenAI's `{"error": {...}}` envelope.
+
+use crate::audio;
+use crate::metrics::ms_to_secs;
+use crate::server::{self, AppState, Billing, JobFailure};
+use axum::{
+    extract::{
+        Multipart, State,
+        multipart::{MultipartError, MultipartRejection},
+    },
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CONTENT_TYPE, RETRY_AFTER},
+    },
+    response::Response,
+};
+use bytes::Bytes;
+use mpv_stt_common::{Transcript, TranscriptionOptions};
+use mpv_stt_protocol::TranscriptionJob;
+use mpv_stt_srt::SrtFile;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+pub(crate) const CONTENT_TYPE_JSON: &str = "application/json";
+pub(crate) const CONTENT_TYPE_TEXT: &str = "text/plain; charset=utf-8";
+pub(crate) const CONTENT_TYPE_VTT: &str = "text/vtt; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Task {
+    Transcribe,
+    Translate,
+}
+
+impl Task {
+    fn as_str(self) -> &'static str {
+        match self {
+            Task::Transcribe => "transcribe",
+            Task::Translate => "translate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResponseFormat {
+    Json,
+    Text,
+    Srt,
+    VerboseJson,
+    Vtt,
+}
+
+impl ResponseFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(Self::Json),
+            "text" => Some(Self::Text),
+            "srt" => Some(Self::Srt),
+            "verbose_json" => Some(Self::VerboseJson),
+            "vtt" => Some(Self::Vtt),
+            _ => None,
+        }
+    }
+}
+
+/// Model name OpenAI SDKs send by default; served by the default model.
+const OPENAI_MODEL_ALIAS: &str = "whisper-1";
+
+/// Fields of the multipart form; unknown fields are ignored.
+struct TranscriptionForm {
+    file: Option<Bytes>,
+    model: Option<String>,
+    language: Option<String>,
+    prompt: Option<String>,
+    temperature: Option<f32>,
+    response_format: ResponseFormat,
+    timestamp_granularities: Vec<String>,
+}
+
+impl TranscriptionForm {
+    async fn read(multipart: &mut Multipart) -> Result<Self, ApiError> {
+        let mut form = Self {
+            file: None,
+            model: None,
+            language: None,
+            prompt: None,
+            temperature: None,
+            response_format: ResponseFormat::Json,
+            timestamp_granularities: Vec::new(),
+        };
+
+        while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+            let Some(name) = field.name().map(str::to_owned) else {
+                continue;
+            };
+            match name.as_str() {
+                "file" => form.file = Some(field.bytes().await.map_err(multipart_error)?),
+                "model" => form.model = non_empty(field.text().await.map_err(multipart_error)?),
+                "language" => {
+                    form.language = non_empty(field.text().await.map_err(multipart_error)?)
+                }
+                "prompt" => form.prompt = non_empty(field.text().await.map_err(multipart_error)?),
+                "temperature" => {
+                    let value = field.text().await.map_err(multipart_error)?;
+                    form.temperature = Some(value.trim().parse().map_err(|_| {
+                        ApiError::invalid(
+                            "temperature",
+                            format!("invalid temperature '{}'", value.trim()),
+                        )
+                    })?);
+                }
+                "response_format" => {
+                    let value = field.text().await.map_err(multipart_error)?;
+                    form.response_format = ResponseFormat::parse(value.trim()).ok_or_else(|| {
+                        ApiError::invalid(
+                            "response_format",
+                            format!(
+                                "unsupported response_format '{}'; expected json, text, srt, verbose_json or vtt",
+                                value.trim()
+                            ),
+                        )
+                    })?;
+                }
+                "timestamp_granularities[]" | "timestamp_granularities" => {
+                    let value = field.text().await.map_err(multipart_error)?;
+                    form.timestamp_granularities.push(value.trim().to_string());
+                }
+                _ => {}
+            }
+        }
+
+        for granularity in &form.timestamp_granularities {
+            match granularity.as_str() {
+                "segment" => {}
+                "word" => {
+                    return Err(ApiError::invalid(
+                        "timestamp_granularities",
+                        "word timestamps are not supported; use 'segment'",
+                    ));
+                }
+                other => {
+                    return Err(ApiError::invalid(
+                        "timestamp_granularities",
+                        format!("unknown timestamp granularity '{}'", other),
+                    ));
+                }
+            }
+        }
+        if !form.timestamp_granularities.is_empty()
+            && form.response_format != ResponseFormat::VerboseJson
+        {
+            return Err(ApiError::invalid(
+                "timestamp_granularities",
+                "timestamp_granularities requires response_format=verbose_json",
+            ));
+        }
+
+        Ok(form)
+    }
+}
+
+/// Error rendered as OpenAI's error envelope.
+struct ApiError {
+    status: StatusCode,
+    /// Label for `mpv_stt_errors_total`, also returned as the error `code`.
+    kind: &'static str,
+    message: String,
+    param: Option<&'static str>,
+    retry_after: Option<Duration>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, kind: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            kind,
+            message: message.into(),
+            param: None,
+            retry_after: None,
+        }
+    }
+
+    fn invalid(param: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            param: Some(param),
+            ..Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+        }
+    }
+
+    fn into_response(self) -> Response {
+        let error_type = if self.status.is_server_error() {
+            "server_error"
+        } else {
+            "invalid_request_error"
+        };
+        let body = serde_json::json!({
+            "error": {
+                "message": self.message,
+                "type": error_type,
+                "param": self.param,
+                "code": self.kind,
+            }
+        });
+        let mut response = with_content_type(
+            server::response_with_status(self.status, body.to_string().as_bytes()),
+            CONTENT_TYPE_JSON,
+        );
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+        }
+        server::with_error_kind(response, self.kind)
+    }
+}
+
+impl From<JobFailure> for ApiError {
+    fn from(failure: JobFailure) -> Self {
+        Self {
+            retry_after: failure.retry_after,
+            ..Self::new(failure.status, failure.kind, failure.message)
+        }
+    }
+}
+
+fn multipart_error(e: MultipartError) -> ApiError {
+    let status = e.status();
+    let kind = if status == StatusCode::PAYLOAD_TOO_LARGE {
+        "payload_too_large"
+    } else {
+        "bad_request"
+    };
+    ApiError::new(status, kind, e.body_text())
+}
+
+#[derive(Serialize)]
+struct VerboseTranscription<'a> {
+    task: &'static str,
+    language: Option<&'a str>,
+    /// Seconds of audio.
+    duration: f64,
+    text: String,
+    segments: Vec<VerboseSegment<'a>>,
+}
+
+#[derive(Serialize)]
+struct VerboseSegment<'a> {
+    id: usize,
+    start: f64,
+    end: f64,
+    text: &'a str,
+}
+
+pub(crate) async fn handle_transcriptions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> Response {
+    respond(&state, &headers, multipart, Task::Transcribe).await
+}
+
+pub(crate) async fn handle_translations(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> Response {
+    respond(&state, &headers, multipart, Task::Translate).await
+}
+
+async fn respond(
+    state: &AppState,
+    headers: &HeaderMap,
+    multipart: Result<Multipart, MultipartRejection>,
+    task: Task,
+) -> Response {
+    let response = transcribe(state, headers, multipart, task)
+        .await
+        .unwrap_or_else(ApiError::into_response);
+    server::record_failure(state, &response);
+    response
+}
+
+async fn transcribe(
+    state: &AppState,
+    headers: &HeaderMap,
+    multipart: Result<Multipart, MultipartRejection>,
+    task: Task,
+) -> Result<Response, ApiError> {
+    let caller = state.admit(headers)?;
+
+    let mut multipart =
+        multipart.map_err(|e| ApiError::new(e.status(), "bad_request", e.body_text()))?;
+    let form = TranscriptionForm::read(&mut multipart).await?;
+    let model = match form.model.as_deref() {
+        Some(OPENAI_MODEL_ALIAS) | None => state.models().default_model(),
+        Some(name) => state
+            .resolve_model(Some(name))
+            .map_err(|failure| ApiError {
+                param: Some("model"),
+                ..failure.into()
+            })?,
+    };
+    let options = TranscriptionOptions {
+        language: form.language.map(|language| language.to_ascii_lowercase()),
+        prompt: form.prompt,
+        translate: task == Task::Translate,
+        temperature: form.temperature,
+        word_timestamps: false,
+    };
+    state
+        .check_options(&options, &model)
+        .map_err(|invalid| ApiError::invalid(invalid.param, invalid.message))?;
+    let priority = server::request_priority(headers)
+        .map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, "bad_request", msg))?;
+    let deadline = server::request_deadline(headers)
+        .map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, "bad_request", msg))?;
+    let file = form
+        .file
+        .ok_or_else(|| ApiError::invalid("file", "missing required field 'file'"))?;
+    let audio = audio::decode_media(file.to_vec())
+        .await
+        .and_then(audio::validate_pcm)
+        .map_err(|msg| ApiError::invalid("file", msg))?;
+
+    // Clients may pick the id themselves so the job can be cancelled via `DELETE /transcribe/{id}`.
+    let request_id = server::client_request_id(headers)
+        .map_err(|msg| ApiError::new(StatusCode::BAD_REQUEST, "bad_request", msg))?
+        .unwrap_or_else(|| state.allocate_request_id());
+
+    let job = TranscriptionJob {
+        request_id,
+        model: model.name.clone(),
+        duration_ms: audio.duration_ms(),
+        audio,
+        options,
+        priority,
+        on_segment: None,
+        enqueue_at: Instant::now(),
+        deadline,
+    };
+    let (transcript, metrics) = server::run_job(state, &caller, job, Billing::Charged).await?;
+
+    let (body, content_type) = render(&transcript, form.response_format, task);
+    state.metrics.record_bytes(file.len(), body.len());
+    state.metrics.record_job(&metrics, transcript.duration_ms);
+
+    let mut response = with_content_type(
+        server::response_with_status(StatusCode::OK, body.as_bytes()),
+        content_type,
+    );
+    response
+        .headers_mut()
+        .insert("x-request-id", HeaderValue::from(request_id));
+    Ok(response)
+}
+
+#[derive(Serialize)]
+struct ModelList<'a> {
+    object: &'static str,
+    data: Vec<ModelObject<'a>>,
+}
+
+/// OpenAI's model object, extended with what this server knows about the model.
+#[derive(Serialize)]
+struct ModelObject<'a> {
+    id: &'a str,
+    object: &'static str,
+    owned_by: &'static str,
+    default: bool,
+    workers: usize,
+    languages: &'a [String],
+}
+
+/// `GET /v1/models`: the hosted models and the languages they accept.
+pub(crate) async fn handle_models(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(failure) = state.authenticate(&headers) {
+        let response = ApiError::from(failure).into_response();
+        server::record_failure(&state, &response);
+        return response;
+    }
+
+    let default = state.models().default_model();
+    let models = state.models().list();
+    let list = ModelList {
+        object: "list",
+        data: models
+            .iter()
+            .map(|model| ModelObject {
+                id: &model.name,
+                object: "model",
+                owned_by: "mpv-stt",
+                default: model.name == default.name,
+                workers: model.pool.num_workers(),
+                languages: &model.languages,
+            })
+            .collect(),
+    };
+    with_content_type(
+        server::response_with_status(
+            StatusCode::OK,
+            serde_json::to_string(&list).unwrap_or_default().as_bytes(),
+        ),
+        CONTENT_TYPE_JSON,
+    )
+}
+
+fn render(transcript: &Transcript, format: ResponseFormat, task: Task) -> (String, &'static str) {
+    match format {
+        ResponseFormat::Json => (
+            serde_json::json!({ "text": plain_text(transcript) }).to_string(),
+            CONTENT_TYPE_JSON,
+        ),
+        ResponseFormat::Text => (plain_text(transcript), CONTENT_TYPE_TEXT),
+        ResponseFormat::Srt => (SrtFile::from(transcript).to_string(), CONTENT_TYPE_TEXT),
+        ResponseFormat::Vtt => (SrtFile::from(transcript).to_vtt(), CONTENT_TYPE_VTT),
+        ResponseFormat::VerboseJson => {
+            let verbose = VerboseTranscription {
+                task: task.as_str(),
+                language: transcript.language.as_deref(),
+                duration: ms_to_secs(transcript.duration_ms),
+                text: plain_text(transcript),
+                segments: transcript
+                    .segments
+                    .iter()
+                    .enumerate()
+                    .map(|(id, segment)| VerboseSegment {
+                        id,
+                        start: ms_to_secs(segment.start_ms),
+                        end: ms_to_secs(segment.end_ms),
+                        text: segment.text.trim(),
+                    })
+                    .collect(),
+            };
+            (
+                serde_json::to_string(&verbose).unwrap_or_default(),
+                CONTENT_TYPE_JSON,
+            )
+        }
+    }
+}
+
+/// Segment texts as one line, the way OpenAI returns `text`.
+fn plain_text(transcript: &Transcript) -> String {
+    transcript
+        .segments
+        .iter()
+        .map(|segment| segment.text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn with_content_type(mut response: Response, content_type: &'static str) -> Response {
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
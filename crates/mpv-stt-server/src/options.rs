@@ -0,0 +1,168 @@
+//! Per-request transcription options: read from `x-*` headers and checked against the
+//! limits the operator configured and the languages the chosen model supports.
+
+use crate::models::HostedModel;
+use axum::http::HeaderMap;
+use mpv_stt_common::TranscriptionOptions;
+use percent_encoding::percent_decode_str;
+
+pub(crate) const HEADER_LANGUAGE: &str = "x-language";
+pub(crate) const HEADER_TASK: &str = "x-task";
+/// Percent-encoded UTF-8, since header values are limited to visible ASCII.
+pub(crate) const HEADER_PROMPT: &str = "x-prompt";
+pub(crate) const HEADER_TEMPERATURE: &str = "x-temperature";
+pub(crate) const HEADER_WORD_TIMESTAMPS: &str = "x-word-timestamps";
+
+const AUTO_LANGUAGE: &str = "auto";
+
+/// Bounds on the options a request may ask for.
+#[derive(Debug, Clone, Copy)]
+pub struct OptionLimits {
+    pub max_temperature: f32,
+    /// Longest initial prompt in characters; Whisper only reads ~224 tokens of it.
+    pub max_prompt_chars: usize,
+    pub allow_word_timestamps: bool,
+}
+
+impl Default for OptionLimits {
+    fn default() -> Self {
+        Self {
+            max_temperature: 1.0,
+            max_prompt_chars: 1_000,
+            allow_word_timestamps: true,
+        }
+    }
+}
+
+/// A rejected option; `param` names the header or form field.
+#[derive(Debug)]
+pub(crate) struct InvalidOption {
+    pub param: &'static str,
+    pub message: String,
+}
+
+impl InvalidOption {
+    fn new(param: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            param,
+            message: message.into(),
+        }
+    }
+}
+
+pub(crate) fn from_headers(headers: &HeaderMap) -> Result<TranscriptionOptions, InvalidOption> {
+    let header = |name: &'static str| -> Result<Option<&str>, InvalidOption> {
+        match headers.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .to_str()
+                .map(|value| Some(value.trim()).filter(|value| !value.is_empty()))
+                .map_err(|_| InvalidOption::new(name, "header is not visible ASCII")),
+        }
+    };
+
+    let translate = match header(HEADER_TASK)? {
+        None | Some("transcribe") => false,
+        Some("translate") => true,
+        Some(other) => {
+            return Err(InvalidOption::new(
+                HEADER_TASK,
+                format!("unknown task '{}'; expected transcribe or translate", other),
+            ));
+        }
+    };
+    let prompt = header(HEADER_PROMPT)?
+        .map(|prompt| {
+            percent_decode_str(prompt)
+                .decode_utf8()
+                .map(|prompt| prompt.into_owned())
+                .map_err(|_| InvalidOption::new(HEADER_PROMPT, "prompt is not valid UTF-8"))
+        })
+        .transpose()?;
+    let temperature = header(HEADER_TEMPERATURE)?
+        .map(|value| {
+            value.parse::<f32>().map_err(|_| {
+                InvalidOption::new(
+                    HEADER_TEMPERATURE,
+                    format!("invalid temperature '{}'", value),
+                )
+            })
+        })
+        .transpose()?;
+    let word_timestamps = match header(HEADER_WORD_TIMESTAMPS)? {
+        None | Some("0") | Some("false") => false,
+        Some("1") | Some("true") => true,
+        Some(other) => {
+            return Err(InvalidOption::new(
+                HEADER_WORD_TIMESTAMPS,
+                format!("expected true or false, got '{}'", other),
+            ));
+        }
+    };
+
+    Ok(TranscriptionOptions {
+        language: header(HEADER_LANGUAGE)?.map(str::to_ascii_lowercase),
+        prompt,
+        translate,
+        temperature,
+        word_timestamps,
+    })
+}
+
+/// Check `options` against `limits` and what `model` can do.
+pub(crate) fn validate(
+    options: &TranscriptionOptions,
+    model: &HostedModel,
+    limits: &OptionLimits,
+) -> Result<(), InvalidOption> {
+    let supported = |language: &&str| {
+        *language == AUTO_LANGUAGE || model.languages.iter().any(|lang| lang == language)
+    };
+    if let Some(language) = options.language.as_deref().filter(|lang| !supported(lang)) {
+        return Err(InvalidOption::new(
+            "language",
+            format!(
+                "model '{}' does not support language '{}'",
+                model.name, language
+            ),
+        ));
+    }
+    if options.translate && model.languages.iter().all(|lang| lang == "en") {
+        return Err(InvalidOption::new(
+            "task",
+            format!(
+                "model '{}' is English-only and cannot translate",
+                model.name
+            ),
+        ));
+    }
+    if options
+        .temperature
+        .is_some_and(|temperature| !(0.0..=limits.max_temperature).contains(&temperature))
+    {
+        return Err(InvalidOption::new(
+            "temperature",
+            format!(
+                "temperature must be between 0 and {}",
+                limits.max_temperature
+            ),
+        ));
+    }
+    if options
+        .prompt
+        .as_deref()
+        .is_some_and(|prompt| prompt.chars().count() > limits.max_prompt_chars)
+    {
+        return Err(InvalidOption::new(
+            "prompt",
+            format!("prompt exceeds {} characters", limits.max_prompt_chars),
+        ));
+    }
+    if options.word_timestamps && !limits.allow_word_timestamps {
+        return Err(InvalidOption::new(
+            "word_timestamps",
+            "word timestamps are disabled on this server",
+        ));
+    }
+    Ok(())
+}
@@ -1,479 +1,1021 @@
-use crate::worker::WorkerPool;
+use crate::cache::{CacheConfig, CacheSlot, ResultCache};
+use crate::jobs::{self, JobStore};
+use crate::keys::{Caller, Denied, KEYS_RELOAD_INTERVAL, KeyStore};
+use crate::metrics::ServerMetrics;
+use crate::models::{HostedModel, ModelConfig, ModelRegistry};
+use crate::options::{self, InvalidOption, OptionLimits};
+use crate::tls::{self, TlsConfig};
+use crate::worker::SubmitError;
+use crate::{admin, live, openai, transcribe};
 use anyhow::{Context, Result};
 use axum::{
     Router,
-    extract::State,
-    http::{HeaderMap, HeaderValue, StatusCode},
+    extract::{DefaultBodyLimit, Request, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER},
+    },
+    middleware::{self, Next},
     response::Response,
-    routing::post,
+    routing::{delete, get, post, put},
 };
-use bytes::Bytes;
 use hex::FromHex;
-use log::{info, warn};
+use log::{debug, info, warn};
+use mpv_stt_common::{PcmBuffer, Transcript, TranscriptionOptions};
 use mpv_stt_crypto::{AuthToken, EncryptionKey};
-use mpv_stt_plugin::SttBackend;
-use mpv_stt_protocol::{JobMetrics, JobResult, TranscriptionJob};
-use std::io::Cursor;
+use mpv_stt_plugin::{SttBackend, SttRunnerConfig};
+use mpv_stt_protocol::{JSON_CONTENT_TYPE, JobMetrics, JobPriority, JobResult, TranscriptionJob};
+use serde::Serialize;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use std::time::Instant;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
-use tokio::sync::{Mutex, mpsc};
+use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
-use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
+use tokio_util::sync::CancellationToken;
 
-const MAX_BODY_SIZE: usize = 50 * 1024 * 1024;
-const COMPRESSION_PCM: &str = "pcm";
-const COMPRESSION_WAV: &str = "wav";
-const COMPRESSION_OPUS: &str = "opus";
+pub(crate) const MAX_BODY_SIZE: usize = 50 * 1024 * 1024;
+/// How long a job without `x-deadline-ms` may take before it is given up on.
+const RESULT_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long an expired job gets to report its metrics after being aborted.
+const EXPIRE_GRACE: Duration = Duration::from_secs(5);
 const HEADER_QUEUE_MS: &str = "x-metric-queue-ms";
 const HEADER_INFER_MS: &str = "x-metric-infer-ms";
 const HEADER_WORKER_MS: &str = "x-metric-worker-ms";
-const HEADER_BYTES_IN: &str = "x-bytes-in";
-const HEADER_BYTES_OUT: &str = "x-bytes-out";
+pub(crate) const HEADER_MODEL: &str = "x-model";
+pub(crate) const HEADER_PRIORITY: &str = "x-priority";
+pub(crate) const HEADER_DEADLINE_MS: &str = "x-deadline-ms";
+const HEADER_CANCEL_TOKEN: &str = "x-cancel-token";
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+// Ids the server assigns itself start here; clients must pick theirs below.
+const SERVER_REQUEST_ID_BASE: u64 = 1 << 63;
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Grace period for open connections after the jobs have drained.
+const CONNECTION_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+// Non-standard "client closed request" status, returned to a transcription that was cancelled.
+const STATUS_CANCELLED: u16 = 499;
 
 pub struct ServerConfig {
     pub enable_encryption: bool,
     pub encryption_key: String,
     pub auth_secret: String,
+    /// Enables the `/admin` endpoints for callers presenting it.
+    pub admin_secret: String,
+    /// TOML file of named API keys with per-key quotas, re-read when it changes.
+    pub api_keys: Option<PathBuf>,
     pub warmup: bool,
+    /// Model for requests that do not name one; the first hosted model when unset.
+    pub default_model: Option<String>,
+    pub option_limits: OptionLimits,
+    /// Serve HTTPS instead of plain HTTP.
+    pub tls: Option<TlsConfig>,
+    /// Reuse transcripts of identical audio.
+    pub cache: Option<CacheConfig>,
+    /// How long running jobs may take to finish once shutdown starts.
+    pub shutdown_timeout: Duration,
+    /// Accept batch jobs and keep them in this directory.
+    pub jobs_dir: Option<PathBuf>,
 }
 
 #[derive(Clone)]
-struct AppState {
-    worker_tx: mpsc::UnboundedSender<TranscriptionJob>,
-    result_rx: Arc<Mutex<UnboundedReceiverStream<JobResult>>>,
+pub(crate) struct AppState {
+    models: Arc<ModelRegistry>,
     encryption_key: Option<EncryptionKey>,
-    expected_auth_token: Option<AuthToken>,
+    keys: Arc<KeyStore>,
+    admin_token: Option<AuthToken>,
+    cache: Option<Arc<ResultCache>>,
+    jobs: Option<Arc<JobStore>>,
+    pub(crate) metrics: Arc<ServerMetrics>,
+    readiness: Arc<RwLock<Readiness>>,
+    next_request_id: Arc<AtomicU64>,
+    /// Set once shutdown starts; new work is refused from then on.
+    draining: Arc<AtomicBool>,
+    option_limits: OptionLimits,
 }
 
-pub struct HttpServer {
-    handle: JoinHandle<()>,
+/// Startup progress reported by `/readyz`.
+enum Readiness {
+    WarmingUp,
+    Ready,
+    WarmupFailed(String),
 }
 
-impl HttpServer {
-    pub async fn bind(
-        bind_addr: &str,
-        whisper_config: mpv_stt_plugin::LocalModelConfig,
-        num_workers: usize,
-        config: ServerConfig,
-    ) -> Result<Self> {
+/// Error label for `mpv_stt_errors_total`, attached to responses whose status alone is ambiguous.
+#[derive(Clone, Copy)]
+struct ErrorKind(&'static str);
+
+impl AppState {
+    fn new(models: ModelRegistry, config: &ServerConfig) -> Self {
         let encryption_key = if config.enable_encryption {
             Some(EncryptionKey::from_passphrase(&config.encryption_key))
         } else {
             None
         };
-        let expected_auth_token = if !config.auth_secret.is_empty() {
-            Some(AuthToken::from_secret(&config.auth_secret))
+        let readiness = if config.warmup {
+            Readiness::WarmingUp
         } else {
-            None
+            Readiness::Ready
         };
 
-        let worker_pool = WorkerPool::new(whisper_config.clone(), num_workers);
+        Self {
+            models: Arc::new(models),
+            encryption_key,
+            keys: Arc::new(KeyStore::new(&config.auth_secret)),
+            admin_token: (!config.admin_secret.is_empty())
+                .then(|| AuthToken::from_secret(&config.admin_secret)),
+            cache: None,
+            jobs: None,
+            metrics: Arc::new(ServerMetrics::new()),
+            readiness: Arc::new(RwLock::new(readiness)),
+            next_request_id: Arc::new(AtomicU64::new(SERVER_REQUEST_ID_BASE)),
+            draining: Arc::new(AtomicBool::new(false)),
+            option_limits: config.option_limits,
+        }
+    }
 
-        if config.warmup {
-            match run_warmup(whisper_config.clone()).await {
-                Ok(_) => info!("Warmup inference completed"),
-                Err(e) => warn!("Warmup inference failed: {}", e),
-            }
+    /// Request id for callers that do not send `x-request-id`.
+    pub(crate) fn allocate_request_id(&self) -> u64 {
+        self.next_request_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// The caller identified by `x-auth-token`, or `Authorization: Bearer <secret>` as sent
+    /// by OpenAI clients; the request is not counted against the key's rate limit.
+    pub(crate) fn authenticate(
+        &self,
+        headers: &HeaderMap,
+    ) -> std::result::Result<Caller, JobFailure> {
+        let caller = self.keys.authenticate(presented_token(headers).as_ref())?;
+        Ok(caller.with_cancel_token(cancel_token(headers)))
+    }
+
+    /// Like [`Self::authenticate`], but counts a transcription request against the key's
+    /// rate limit and refuses keys whose daily audio quota is used up.
+    pub(crate) fn admit(&self, headers: &HeaderMap) -> std::result::Result<Caller, JobFailure> {
+        let caller = self.keys.admit(presented_token(headers).as_ref())?;
+        Ok(caller.with_cancel_token(cancel_token(headers)))
+    }
+
+    /// Admit only callers presenting `--admin-secret`; API keys grant no admin access.
+    pub(crate) fn authorize_admin(
+        &self,
+        headers: &HeaderMap,
+    ) -> std::result::Result<(), JobFailure> {
+        let Some(admin_token) = &self.admin_token else {
+            return Err(JobFailure::new(
+                StatusCode::NOT_FOUND,
+                "admin_disabled",
+                "admin endpoints are disabled; start the server with --admin-secret",
+            ));
+        };
+        if presented_token(headers).as_ref() != Some(admin_token) {
+            return Err(JobFailure::new(
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "invalid or missing admin secret",
+            ));
         }
+        Ok(())
+    }
 
-        let worker_tx = worker_pool.job_sender();
-        let (result_stream_tx, result_stream_rx) = mpsc::unbounded_channel();
-        // Own the pool and forward results to shared stream.
-        tokio::spawn(async move {
-            let mut pool = worker_pool;
-            while let Some(res) = pool.next_result().await {
-                let _ = result_stream_tx.send(res);
-            }
-        });
+    /// Result cache slot of `job` run by `model`, when caching is enabled.
+    pub(crate) fn cache_slot(
+        &self,
+        model: &HostedModel,
+        job: &TranscriptionJob,
+    ) -> Option<CacheSlot> {
+        self.cache.as_ref()?;
+        Some(CacheSlot::new(&model.name, job))
+    }
 
-        let state = AppState {
-            worker_tx,
-            result_rx: Arc::new(Mutex::new(UnboundedReceiverStream::new(result_stream_rx))),
-            encryption_key,
-            expected_auth_token,
+    /// The transcript cached in `slot`; looked up on the blocking pool since disk-backed
+    /// entries are read from files.
+    pub(crate) async fn cached_result(&self, slot: Option<&CacheSlot>) -> Option<Transcript> {
+        let (cache, key) = (self.cache.clone()?, slot?.key);
+        tokio::task::spawn_blocking(move || cache.get(&key))
+            .await
+            .ok()?
+    }
+
+    /// Store `transcript` in `slot`, on the blocking pool like [`Self::cached_result`].
+    pub(crate) async fn cache_result(&self, slot: Option<CacheSlot>, transcript: &Transcript) {
+        let (Some(cache), Some(slot)) = (self.cache.clone(), slot) else {
+            return;
         };
+        let transcript = transcript.clone();
+        let _ = tokio::task::spawn_blocking(move || cache.insert(&slot, &transcript)).await;
+    }
 
-        let app = Router::new()
-            .route("/transcribe", post(handle_transcribe))
-            .with_state(state);
+    /// Forget the cached results of `model` after it was reloaded.
+    pub(crate) async fn clear_cached_results(&self, model: &str) {
+        let Some(cache) = self.cache.clone() else {
+            return;
+        };
+        let model = model.to_string();
+        let _ = tokio::task::spawn_blocking(move || cache.clear_model(&model)).await;
+    }
 
-        let addr: SocketAddr = bind_addr.parse()?;
-        let listener = TcpListener::bind(&addr).await?;
-        let server = axum::serve(listener, app);
+    /// The model named by a request (`x-model` or a form field), or the default one.
+    pub(crate) fn resolve_model(
+        &self,
+        name: Option<&str>,
+    ) -> std::result::Result<Arc<HostedModel>, JobFailure> {
+        match name.map(str::trim).filter(|name| !name.is_empty()) {
+            None => Ok(self.models.default_model()),
+            Some(name) => self.models.get(name).ok_or_else(|| {
+                JobFailure::new(
+                    StatusCode::NOT_FOUND,
+                    "unknown_model",
+                    format!(
+                        "unknown model '{}'; available: {}",
+                        name,
+                        self.models.names().join(", ")
+                    ),
+                )
+            }),
+        }
+    }
 
-        let handle = tokio::spawn(async move {
-            if let Err(e) = server.await {
-                eprintln!("axum server error: {}", e);
-            }
-        });
+    /// Reject options this server does not allow or `model` cannot honour.
+    pub(crate) fn check_options(
+        &self,
+        options: &TranscriptionOptions,
+        model: &HostedModel,
+    ) -> std::result::Result<(), InvalidOption> {
+        options::validate(options, model, &self.option_limits)
+    }
 
-        info!("HTTP server listening on {}", bind_addr);
-        Ok(Self { handle })
+    /// Model and options selected by `x-model` and the option headers.
+    pub(crate) fn request_target(
+        &self,
+        headers: &HeaderMap,
+    ) -> std::result::Result<(String, TranscriptionOptions), JobFailure> {
+        let model = self.resolve_model(headers.get(HEADER_MODEL).and_then(|h| h.to_str().ok()))?;
+        options::from_headers(headers)
+            .and_then(|options| {
+                self.check_options(&options, &model)?;
+                Ok((model.name.clone(), options))
+            })
+            .map_err(|invalid| {
+                JobFailure::new(
+                    StatusCode::BAD_REQUEST,
+                    "invalid_option",
+                    format!("{}: {}", invalid.param, invalid.message),
+                )
+            })
     }
 
-    pub async fn run(self) -> Result<()> {
-        self.handle.await.unwrap();
-        Ok(())
+    pub(crate) fn models(&self) -> &ModelRegistry {
+        &self.models
+    }
+
+    /// Key for encrypted `/transcribe` payloads, when encryption is enabled.
+    pub(crate) fn encryption_key(&self) -> Option<&EncryptionKey> {
+        self.encryption_key.as_ref()
+    }
+
+    /// Whether transcripts are cached, so replies say whether they were a hit.
+    pub(crate) fn caches_results(&self) -> bool {
+        self.cache.is_some()
+    }
+
+    /// Batch job store, when a jobs directory is configured.
+    pub(crate) fn jobs(&self) -> Option<&Arc<JobStore>> {
+        self.jobs.as_ref()
+    }
+
+    fn set_readiness(&self, readiness: Readiness) {
+        *self.readiness.write().unwrap_or_else(|e| e.into_inner()) = readiness;
+    }
+
+    fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// `Err` carries the reason the server should not receive traffic yet.
+    fn check_ready(&self) -> std::result::Result<(), String> {
+        if self.is_draining() {
+            return Err("shutting down".to_string());
+        }
+        if self.models.is_closed() {
+            return Err("workers stopped".to_string());
+        }
+        match &*self.readiness.read().unwrap_or_else(|e| e.into_inner()) {
+            Readiness::Ready => Ok(()),
+            Readiness::WarmingUp => Err("warmup in progress".to_string()),
+            Readiness::WarmupFailed(e) => Err(format!("warmup failed: {}", e)),
+        }
     }
 }
 
-async fn run_warmup(config: mpv_stt_plugin::LocalModelConfig) -> Result<()> {
-    tokio::task::spawn_blocking(move || warmup_blocking(config)).await??;
-    Ok(())
+/// Client-chosen secret that, like an API key, is required to cancel the request.
+fn cancel_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(HEADER_CANCEL_TOKEN)
+        .and_then(|h| h.to_str().ok())
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
 }
 
-fn warmup_blocking(config: mpv_stt_plugin::LocalModelConfig) -> Result<()> {
-    use hound::{SampleFormat, WavSpec, WavWriter};
-    use tempfile::NamedTempFile;
+fn presented_token(headers: &HeaderMap) -> Option<AuthToken> {
+    headers
+        .get("x-auth-token")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| Vec::from_hex(s).ok())
+        .and_then(|v| v.try_into().ok())
+        .map(AuthToken::from_bytes)
+        .or_else(|| {
+            headers
+                .get(AUTHORIZATION)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|s| s.strip_prefix("Bearer "))
+                .map(|secret| AuthToken::from_secret(secret.trim()))
+        })
+}
 
-    info!("Running warmup inference to preload model...");
+pub struct HttpServer {
+    handle: JoinHandle<()>,
+    state: AppState,
+    local_addr: SocketAddr,
+    /// Stops accepting connections and closes idle ones.
+    stop: CancellationToken,
+    shutdown_timeout: Duration,
+}
 
-    let mut runner = mpv_stt_plugin::SttRunner::new(config);
-    let temp = NamedTempFile::new().context("create temp WAV for warmup")?;
+impl HttpServer {
+    pub async fn bind(
+        bind_addr: &str,
+        models: Vec<ModelConfig>,
+        config: ServerConfig,
+    ) -> Result<Self> {
+        let acceptor = config.tls.as_ref().map(tls::acceptor).transpose()?;
+        let models = ModelRegistry::new(
+            models.into_iter().map(HostedModel::start).collect(),
+            config.default_model.as_deref(),
+        );
+        let mut state = AppState::new(models, &config);
+        state.cache = config
+            .cache
+            .clone()
+            .map(ResultCache::open)
+            .transpose()?
+            .map(Arc::new);
+        state.jobs = config
+            .jobs_dir
+            .clone()
+            .map(JobStore::open)
+            .transpose()?
+            .map(Arc::new);
+        if let Some(path) = &config.api_keys {
+            state.keys.watch_file(path)?;
+            let keys = Arc::clone(&state.keys);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(KEYS_RELOAD_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    match keys.reload_if_changed() {
+                        Ok(true) => info!("Reloaded API keys"),
+                        Ok(false) => {}
+                        Err(e) => warn!("Keeping previous API keys: {:#}", e),
+                    }
+                }
+            });
+        }
 
-    let spec = WavSpec {
-        channels: 1,
-        sample_rate: 16_000,
-        bits_per_sample: 16,
-        sample_format: SampleFormat::Int,
-    };
+        if config.warmup {
+            // Warm up in the background so /healthz answers while the models load.
+            let state = state.clone();
+            tokio::spawn(async move {
+                let _ = warm_up(&state).await;
+            });
+        }
 
-    {
-        let mut writer =
-            WavWriter::create(temp.path(), spec).context("create warmup WAV writer")?;
-        for _ in 0..16_000 {
-            writer.write_sample(0i16).context("write warmup sample")?;
+        if let Some(store) = &state.jobs {
+            for id in store.unfinished() {
+                info!("Resuming job {}", id);
+                tokio::spawn(jobs::run(state.clone(), Arc::clone(store), id));
+            }
         }
-        writer.finalize().context("finalize warmup WAV")?;
+
+        let app = build_router(state.clone());
+
+        let addr: SocketAddr = bind_addr.parse()?;
+        let listener = TcpListener::bind(&addr).await?;
+        let local_addr = listener.local_addr()?;
+        let stop = CancellationToken::new();
+        let handle = match acceptor {
+            Some(acceptor) => {
+                info!("HTTPS server listening on {}", local_addr);
+                tokio::spawn(tls::serve(listener, acceptor, app, stop.clone()))
+            }
+            None => {
+                let server = axum::serve(listener, app)
+                    .with_graceful_shutdown(stop.clone().cancelled_owned());
+                info!("HTTP server listening on {}", local_addr);
+                tokio::spawn(async move {
+                    if let Err(e) = server.await {
+                        eprintln!("axum server error: {}", e);
+                    }
+                })
+            }
+        };
+
+        Ok(Self {
+            handle,
+            state,
+            local_addr,
+            stop,
+            shutdown_timeout: config.shutdown_timeout,
+        })
     }
 
-    let prefix = temp.path();
-    runner
-        .transcribe(prefix, prefix, 1_000)
-        .context("warmup transcription")?;
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Serve until Ctrl+C or SIGTERM, then shut down gracefully.
+    pub async fn run(mut self) -> Result<()> {
+        tokio::select! {
+            result = &mut self.handle => {
+                result?;
+                return Ok(());
+            }
+            signal = shutdown_signal() => signal?,
+        }
+        self.shutdown().await;
+        Ok(())
+    }
 
-    let _ = std::fs::remove_file(prefix.with_extension("srt"));
-    let _ = std::fs::remove_file(prefix.with_extension("txt"));
+    /// Refuse new work with `503`, give queued and running jobs `shutdown_timeout` to
+    /// finish, cancel whatever is left, then close connections and stop the workers.
+    pub async fn shutdown(self) {
+        info!(
+            "Shutting down; waiting up to {}s for in-flight jobs",
+            self.shutdown_timeout.as_secs()
+        );
+        self.state.draining.store(true, Ordering::Relaxed);
+
+        let deadline = Instant::now() + self.shutdown_timeout;
+        while !self.state.models.is_idle() && Instant::now() < deadline {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        if !self.state.models.is_idle() {
+            let cancelled = self.state.models.cancel_all();
+            warn!(
+                "Cancelled {} jobs still unfinished after the shutdown timeout",
+                cancelled
+            );
+        }
 
+        // Let cancelled and finished jobs deliver their responses before connections close.
+        self.stop.cancel();
+        let mut handle = self.handle;
+        if tokio::time::timeout(CONNECTION_CLOSE_TIMEOUT, &mut handle)
+            .await
+            .is_err()
+        {
+            warn!("Closing connections that did not finish in time");
+            handle.abort();
+        }
+        self.state.models.shutdown().await;
+        info!("Server stopped");
+    }
+}
+
+/// Resolves on Ctrl+C or, on Unix, SIGTERM.
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
     Ok(())
 }
 
-async fn handle_transcribe(
+/// Answer `503` to everything but health and metrics probes once shutdown has started.
+async fn reject_while_draining(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    body: Bytes,
+    request: Request,
+    next: Next,
 ) -> Response {
-    if body.len() > MAX_BODY_SIZE {
-        return response_with_status(StatusCode::PAYLOAD_TOO_LARGE, b"body too large");
+    let probe = matches!(request.uri().path(), "/healthz" | "/readyz" | "/metrics");
+    if state.is_draining() && !probe {
+        let response = shutting_down().into_response();
+        record_failure(&state, &response);
+        return response;
     }
+    next.run(request).await
+}
 
-    let request_id = match headers
-        .get("x-request-id")
-        .and_then(|h| h.to_str().ok())
-        .and_then(|s| s.parse::<u64>().ok())
-    {
-        Some(id) => id,
-        None => return response_with_status(StatusCode::BAD_REQUEST, b"missing x-request-id"),
-    };
+fn shutting_down() -> JobFailure {
+    JobFailure::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "shutting_down",
+        "server is shutting down",
+    )
+}
 
-    let duration_ms = headers
-        .get("x-duration-ms")
-        .and_then(|h| h.to_str().ok())
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(0);
-
-    if let Some(expected) = &state.expected_auth_token {
-        let ok = headers
-            .get("x-auth-token")
-            .and_then(|h| h.to_str().ok())
-            .and_then(|s| Vec::from_hex(s).ok())
-            .and_then(|v| v.try_into().ok())
-            .map(AuthToken::from_bytes)
-            .map(|token| &token == expected)
-            .unwrap_or(false);
-        if !ok {
-            return response_with_status(StatusCode::UNAUTHORIZED, b"unauthorized");
+fn build_router(state: AppState) -> Router {
+    Router::new()
+        .route("/transcribe", post(transcribe::handle_transcribe))
+        .route("/transcribe/:request_id", delete(transcribe::handle_cancel))
+        .route("/jobs", post(jobs::handle_create))
+        .route("/jobs/:id", get(jobs::handle_status))
+        .route("/jobs/:id/result", get(jobs::handle_result))
+        .route("/admin/models/:name/reload", post(admin::handle_reload))
+        .route("/admin/models/:name/workers", put(admin::handle_resize))
+        .route("/admin/warmup", post(admin::handle_warmup))
+        .route(
+            "/v1/audio/transcriptions",
+            post(openai::handle_transcriptions),
+        )
+        .route("/v1/audio/translations", post(openai::handle_translations))
+        .route("/v1/models", get(openai::handle_models))
+        .route("/live", get(live::handle_live))
+        .route("/healthz", get(handle_healthz))
+        .route("/readyz", get(handle_readyz))
+        .route("/metrics", get(handle_metrics))
+        .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            reject_while_draining,
+        ))
+        .with_state(state)
+}
+
+/// Run a warmup inference on every model and report the outcome through `/readyz`.
+pub(crate) async fn warm_up(state: &AppState) -> std::result::Result<(), String> {
+    for model in state.models.list() {
+        if let Err(e) = run_warmup(model.runner.clone()).await {
+            warn!("Warmup inference of model {} failed: {}", model.name, e);
+            let reason = format!("{}: {}", model.name, e);
+            state.set_readiness(Readiness::WarmupFailed(reason.clone()));
+            return Err(reason);
         }
+        info!("Warmup inference of model {} completed", model.name);
     }
+    state.set_readiness(Readiness::Ready);
+    Ok(())
+}
 
-    let encrypted = headers
-        .get("x-encrypted")
-        .and_then(|h| h.to_str().ok())
-        .map(|s| s == "1")
-        .unwrap_or(false);
+pub(crate) async fn run_warmup(config: SttRunnerConfig) -> Result<()> {
+    tokio::task::spawn_blocking(move || warmup_blocking(config)).await??;
+    Ok(())
+}
 
-    let compression = headers
-        .get("x-compression")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or(COMPRESSION_PCM);
-
-    let mut audio_bytes = body.to_vec();
-    if encrypted {
-        if let Some(key) = state.encryption_key.as_ref() {
-            match key.decrypt(&audio_bytes) {
-                Ok(decrypted) => audio_bytes = decrypted,
-                Err(e) => {
-                    return response_with_status(
-                        StatusCode::BAD_REQUEST,
-                        format!("decrypt failed: {}", e).as_bytes(),
-                    );
-                }
-            }
-        } else {
-            return response_with_status(StatusCode::BAD_REQUEST, b"encryption not enabled");
-        }
-    }
+fn warmup_blocking(config: SttRunnerConfig) -> Result<()> {
+    info!("Running warmup inference to preload model...");
 
-    let audio_data = match compression {
-        COMPRESSION_PCM | COMPRESSION_WAV => audio_bytes,
-        COMPRESSION_OPUS => match decompress_opus(&audio_bytes) {
-            Ok(d) => d,
-            Err(e) => {
-                // Backward compatibility: some clients mislabeled WAV as OPUS.
-                if audio_bytes.starts_with(b"RIFF") {
-                    warn!("compression=opus but payload looks like WAV; bypassing opus decode");
-                    audio_bytes
-                } else {
-                    return response_with_status(StatusCode::BAD_REQUEST, e.to_string().as_bytes());
-                }
-            }
-        },
-        _ => return response_with_status(StatusCode::BAD_REQUEST, b"unsupported compression"),
-    };
+    let mut runner = mpv_stt_plugin::SttRunner::new(config);
+    let silence = PcmBuffer::silence(1_000, 16_000, 1);
+    runner
+        .transcribe(&silence, 1_000)
+        .context("warmup transcription")?;
 
-    if audio_data.is_empty() {
-        return response_with_status(StatusCode::BAD_REQUEST, b"empty audio data");
+    Ok(())
+}
+
+async fn handle_healthz() -> &'static str {
+    "ok"
+}
+
+async fn handle_readyz(State(state): State<AppState>) -> Response {
+    match state.check_ready() {
+        Ok(()) => response_with_status(StatusCode::OK, b"ready"),
+        Err(reason) => response_with_status(StatusCode::SERVICE_UNAVAILABLE, reason.as_bytes()),
     }
+}
+
+async fn handle_metrics(State(state): State<AppState>) -> Response {
+    let snapshot = state.models.snapshot();
+    let cache = state.cache.as_ref().map(|cache| cache.snapshot());
+    let body = state.metrics.render(
+        &snapshot,
+        cache.as_ref(),
+        &state.keys.usage(),
+        state.check_ready().is_ok(),
+    );
+    let mut response = response_with_status(StatusCode::OK, body.as_bytes());
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(PROMETHEUS_CONTENT_TYPE),
+    );
+    response
+}
 
-    if let Err(msg) = validate_wav(&audio_data) {
-        return response_with_status(StatusCode::BAD_REQUEST, msg.as_bytes());
+/// Count a failed response in `mpv_stt_errors_total`.
+pub(crate) fn record_failure(state: &AppState, response: &Response) {
+    if response.status().is_success() {
+        return;
     }
+    let kind = response
+        .extensions()
+        .get::<ErrorKind>()
+        .map(|kind| kind.0)
+        .unwrap_or_else(|| error_kind(response.status()));
+    state.metrics.record_error(kind);
+}
 
-    let job = TranscriptionJob {
-        request_id,
-        audio_data,
-        duration_ms,
-        enqueue_at: Instant::now(),
-    };
+/// Why a submitted job produced no transcript.
+pub(crate) struct JobFailure {
+    pub status: StatusCode,
+    /// Label for `mpv_stt_errors_total`.
+    pub kind: &'static str,
+    pub message: String,
+    pub retry_after: Option<Duration>,
+    /// Timings of a job that was queued or run before failing.
+    pub metrics: Option<JobMetrics>,
+}
 
-    if state.worker_tx.send(job).is_err() {
-        return response_with_status(StatusCode::INTERNAL_SERVER_ERROR, b"failed to enqueue job");
+impl JobFailure {
+    pub(crate) fn new(status: StatusCode, kind: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            kind,
+            message: message.into(),
+            retry_after: None,
+            metrics: None,
+        }
     }
 
-    // Wait for result
-    let (srt_data, metrics) = match wait_for_result(&state, request_id).await {
-        Ok(data) => data,
-        Err(msg) => return response_with_status(StatusCode::INTERNAL_SERVER_ERROR, msg.as_bytes()),
-    };
+    /// `504` for a job given up on at its deadline.
+    fn deadline_exceeded(metrics: Option<JobMetrics>) -> Self {
+        let message = match metrics {
+            Some(metrics) => format!(
+                "deadline exceeded after {} ms in queue and {} ms of inference",
+                metrics.queue_wait_ms, metrics.inference_ms
+            ),
+            None => "deadline exceeded".to_string(),
+        };
+        Self {
+            metrics,
+            ..Self::new(StatusCode::GATEWAY_TIMEOUT, "deadline_exceeded", message)
+        }
+    }
 
-    let mut resp_body = srt_data.clone();
-    if encrypted {
-        if let Some(key) = state.encryption_key.as_ref() {
-            match key.encrypt(&resp_body) {
-                Ok(enc) => resp_body = enc,
-                Err(e) => {
-                    return response_with_status(
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        e.to_string().as_bytes(),
-                    );
-                }
-            }
+    /// Plain-text response used by `/transcribe`.
+    pub(crate) fn into_response(self) -> Response {
+        let mut response = response_with_status(self.status, self.message.as_bytes());
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+        }
+        if let Some(metrics) = &self.metrics {
+            insert_metric_headers(response.headers_mut(), metrics);
         }
+        with_error_kind(response, self.kind)
     }
+}
 
-    let resp_body_len = resp_body.len();
-    let mut response = Response::new(resp_body.into());
-    *response.status_mut() = StatusCode::OK;
-    let headers = response.headers_mut();
-    let _ = headers.insert(
-        HEADER_QUEUE_MS,
-        HeaderValue::from_str(&metrics.queue_wait_ms.to_string()).unwrap_or_else(|_| HeaderValue::from_static("0")),
-    );
-    let _ = headers.insert(
-        HEADER_INFER_MS,
-        HeaderValue::from_str(&metrics.inference_ms.to_string()).unwrap_or_else(|_| HeaderValue::from_static("0")),
-    );
-    let _ = headers.insert(
-        HEADER_WORKER_MS,
-        HeaderValue::from_str(&metrics.worker_total_ms.to_string()).unwrap_or_else(|_| HeaderValue::from_static("0")),
-    );
-    let _ = headers.insert(
-        HEADER_BYTES_IN,
-        HeaderValue::from_str(&body.len().to_string()).unwrap_or_else(|_| HeaderValue::from_static("0")),
-    );
-    let _ = headers.insert(
-        HEADER_BYTES_OUT,
-        HeaderValue::from_str(&resp_body_len.to_string()).unwrap_or_else(|_| HeaderValue::from_static("0")),
-    );
+impl From<Denied> for JobFailure {
+    fn from(denied: Denied) -> Self {
+        let (status, kind, message, retry_after) = match denied {
+            Denied::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "invalid or missing API key",
+                None,
+            ),
+            Denied::Revoked => (
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "API key revoked",
+                None,
+            ),
+            Denied::RateLimited { retry_after } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+                "request rate limit of this API key exceeded",
+                Some(retry_after),
+            ),
+            Denied::QuotaExceeded { retry_after } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "quota_exceeded",
+                "daily audio quota of this API key exceeded",
+                Some(retry_after),
+            ),
+        };
+        Self {
+            // Round up so clients retrying on time are not refused again.
+            retry_after: retry_after.map(|d| Duration::from_secs(d.as_secs_f64().ceil() as u64)),
+            ..Self::new(status, kind, message)
+        }
+    }
+}
 
-    response
+pub(crate) fn cache_hit_metrics() -> JobMetrics {
+    JobMetrics {
+        cache_hit: true,
+        ..Default::default()
+    }
+}
+
+/// How a job's audio counts against the caller's daily quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Billing {
+    Charged,
+    /// Must fit in the quota but is not counted; for live partials, whose audio is charged
+    /// once it is finalised.
+    Preview,
 }
 
-async fn wait_for_result(
+/// Queue `job`, unless its result is cached, and wait for its transcript.
+pub(crate) async fn run_job(
     state: &AppState,
-    request_id: u64,
-) -> std::result::Result<(Vec<u8>, JobMetrics), String> {
-    use tokio::time::{Duration, Instant, sleep};
-    let deadline = Instant::now() + Duration::from_secs(120);
-    loop {
-        if Instant::now() > deadline {
-            return Err("timeout waiting result".to_string());
-        }
-        let mut rx = state.result_rx.lock().await;
-        match rx.next().await {
-            Some(JobResult::Success {
-                request_id: id,
-                srt_data,
-                metrics,
-            }) if id == request_id => return Ok((srt_data, metrics)),
-            Some(JobResult::Error {
-                request_id: id,
-                message,
-            }) if id == request_id => return Err(message),
-            Some(other) => {
-                // unrelated result, put back? simplest: drop
-                warn!("dropping unrelated result {:?}", other);
-            }
-            None => {
-                return Err("result channel closed".to_string());
-            }
-        }
-        drop(rx);
-        sleep(Duration::from_millis(50)).await;
+    caller: &Caller,
+    job: TranscriptionJob,
+    billing: Billing,
+) -> std::result::Result<(Transcript, JobMetrics), JobFailure> {
+    let model = accept(state, caller, &job, billing)?;
+    let cache_slot = state.cache_slot(&model, &job);
+    if let Some(transcript) = state.cached_result(cache_slot.as_ref()).await {
+        debug!("Request {} served from the result cache", job.request_id);
+        return Ok((transcript, cache_hit_metrics()));
     }
+    let submitted = submit(state, &model, caller, job, billing)?;
+    let (transcript, metrics) = wait_for_result(state, submitted).await?;
+    state.cache_result(cache_slot, &transcript).await;
+    Ok((transcript, metrics))
 }
 
-fn response_with_status(status: StatusCode, body: &[u8]) -> Response {
-    let mut resp = Response::new(body.to_vec().into());
-    *resp.status_mut() = status;
-    resp
+/// A queued job awaiting its result.
+pub(crate) struct Submitted {
+    pub request_id: u64,
+    result_rx: oneshot::Receiver<JobResult>,
+    /// When the server stops waiting: the client's deadline, or [`RESULT_TIMEOUT`] after
+    /// the job was accepted.
+    deadline: Instant,
+    /// Audio charged by [`accept`], given back unless the job produces a transcript.
+    charge: Option<Charge>,
 }
 
-fn decompress_opus(compressed: &[u8]) -> Result<Vec<u8>> {
-    use std::convert::TryInto;
-    use std::ffi::CStr;
-    use std::os::raw::c_int;
+/// Audio reserved against a key's daily quota for a job still in flight.
+struct Charge {
+    caller: Caller,
+    audio_ms: u64,
+}
 
-    use hound::WavWriter;
-    use opus_static_sys as opus;
-    use tempfile::NamedTempFile;
+impl Charge {
+    fn release(self, state: &AppState) {
+        state.keys.release_audio(&self.caller, self.audio_ms);
+    }
+}
 
-    const SAMPLE_RATE: c_int = 16_000;
-    const CHANNELS: c_int = 1;
-    // 120 ms @ 48k = 5760 samples; safe upper bound for 16k streams too.
-    const MAX_FRAME_SIZE: usize = 5760;
+/// Refuse `job` while draining, otherwise check or charge its audio against the caller's
+/// daily quota; cached results count too. Returns the model that runs it.
+pub(crate) fn accept(
+    state: &AppState,
+    caller: &Caller,
+    job: &TranscriptionJob,
+    billing: Billing,
+) -> std::result::Result<Arc<HostedModel>, JobFailure> {
+    if state.is_draining() {
+        return Err(shutting_down());
+    }
+    let model = state.resolve_model(Some(&job.model))?;
+    let audio_ms = job.audio.duration_ms();
+    match billing {
+        Billing::Charged => state.keys.reserve_audio(caller, audio_ms)?,
+        Billing::Preview => state.keys.check_audio(caller, audio_ms)?,
+    }
+    Ok(model)
+}
 
-    let mut err: c_int = 0;
-    let decoder = unsafe { opus::opus_decoder_create(SAMPLE_RATE, CHANNELS, &mut err) };
-    if decoder.is_null() || err != opus::OPUS_OK as c_int {
-        let msg = unsafe {
-            CStr::from_ptr(opus::opus_strerror(err))
-                .to_string_lossy()
-                .into_owned()
-        };
-        anyhow::bail!("Failed to create Opus decoder: {}", msg);
+/// Queue a job [`accept`]ed for `model`; its audio is given back if it cannot be queued.
+pub(crate) fn submit(
+    state: &AppState,
+    model: &HostedModel,
+    caller: &Caller,
+    job: TranscriptionJob,
+    billing: Billing,
+) -> std::result::Result<Submitted, JobFailure> {
+    let request_id = job.request_id;
+    let deadline = job.deadline.unwrap_or(job.enqueue_at + RESULT_TIMEOUT);
+    let charge = (billing == Billing::Charged).then(|| Charge {
+        caller: caller.clone(),
+        audio_ms: job.audio.duration_ms(),
+    });
+    let error = match model.pool.submit_job(job, caller.clone()) {
+        Ok(result_rx) => {
+            return Ok(Submitted {
+                request_id,
+                result_rx,
+                deadline,
+                charge,
+            });
+        }
+        Err(error) => error,
+    };
+    if let Some(charge) = charge {
+        charge.release(state);
     }
+    match error {
+        e @ SubmitError::DuplicateRequest(_) => Err(JobFailure::new(
+            StatusCode::CONFLICT,
+            "duplicate_request",
+            e.to_string(),
+        )),
+        e @ SubmitError::QueueFull { retry_after, .. } => {
+            warn!("Rejecting request {}: {}", request_id, e);
+            Err(JobFailure {
+                retry_after: Some(retry_after),
+                ..JobFailure::new(StatusCode::SERVICE_UNAVAILABLE, "queue_full", e.to_string())
+            })
+        }
+        SubmitError::Closed => Err(JobFailure::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            "failed to enqueue job",
+        )),
+    }
+}
 
-    let mut samples = Vec::new();
-    let mut pos = 0;
+/// Wait for a submitted job; at its deadline the job is dropped from the queue or aborted.
+/// Audio charged for a job that ends without a transcript is given back.
+pub(crate) async fn wait_for_result(
+    state: &AppState,
+    submitted: Submitted,
+) -> std::result::Result<(Transcript, JobMetrics), JobFailure> {
+    let Submitted {
+        request_id,
+        mut result_rx,
+        deadline,
+        charge,
+    } = submitted;
+    let abandoned = CancelOnDrop::new(state, request_id, charge);
+    let result = match tokio::time::timeout_at(deadline.into(), &mut result_rx).await {
+        Ok(result) => result,
+        Err(_) => {
+            debug!("Request {} reached its deadline", request_id);
+            state.models.expire_request(request_id);
+            match tokio::time::timeout(EXPIRE_GRACE, result_rx).await {
+                Ok(result) => result,
+                Err(_) => return Err(JobFailure::deadline_exceeded(None)),
+            }
+        }
+    };
+    let charge = abandoned.disarm();
+    let outcome = match result {
+        Ok(JobResult::Success {
+            transcript,
+            metrics,
+            ..
+        }) => Ok((transcript, metrics)),
+        Ok(JobResult::Cancelled { .. }) => Err(JobFailure::new(
+            cancelled_status(),
+            "cancelled",
+            "cancelled",
+        )),
+        Ok(JobResult::Expired { metrics, .. }) => Err(JobFailure::deadline_exceeded(Some(metrics))),
+        Ok(JobResult::Error { message, .. }) => Err(JobFailure::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "inference",
+            message,
+        )),
+        Err(_) => Err(JobFailure::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            "worker dropped request",
+        )),
+    };
+    if let (Err(_), Some(charge)) = (&outcome, charge) {
+        charge.release(state);
+    }
+    outcome
+}
 
-    while pos + 4 <= compressed.len() {
-        let frame_len = u32::from_le_bytes(
-            compressed[pos..pos + 4]
-                .try_into()
-                .expect("slice length validated"),
-        ) as usize;
-        pos += 4;
+/// Cancels a submitted job when the request waiting for it goes away, e.g. because the
+/// client disconnected, so nobody's worker keeps transcribing for no one. Its audio is
+/// given back too.
+struct CancelOnDrop<'a> {
+    state: &'a AppState,
+    request_id: Option<u64>,
+    charge: Option<Charge>,
+}
 
-        if pos + frame_len > compressed.len() {
-            unsafe { opus::opus_decoder_destroy(decoder) };
-            anyhow::bail!("Invalid Opus frame length");
+impl<'a> CancelOnDrop<'a> {
+    fn new(state: &'a AppState, request_id: u64, charge: Option<Charge>) -> Self {
+        Self {
+            state,
+            request_id: Some(request_id),
+            charge,
         }
+    }
 
-        let frame = &compressed[pos..pos + frame_len];
-        pos += frame_len;
-
-        let mut output = vec![0i16; MAX_FRAME_SIZE];
-        let decoded_samples = unsafe {
-            opus::opus_decode(
-                decoder,
-                frame.as_ptr(),
-                frame_len as opus::opus_int32,
-                output.as_mut_ptr(),
-                MAX_FRAME_SIZE as c_int,
-                0,
-            )
-        };
+    /// The job finished; nothing to cancel. Hands back its charge.
+    fn disarm(mut self) -> Option<Charge> {
+        self.request_id = None;
+        self.charge.take()
+    }
+}
 
-        if decoded_samples < 0 {
-            let msg = unsafe {
-                CStr::from_ptr(opus::opus_strerror(decoded_samples))
-                    .to_string_lossy()
-                    .into_owned()
-            };
-            unsafe { opus::opus_decoder_destroy(decoder) };
-            anyhow::bail!("Opus decode failed: {}", msg);
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        let Some(request_id) = self.request_id.take() else {
+            return;
+        };
+        if self.state.models.cancel_request(request_id) {
+            debug!("Request {} was abandoned; cancelled its job", request_id);
         }
+        if let Some(charge) = self.charge.take() {
+            charge.release(self.state);
+        }
+    }
+}
 
-        samples.extend_from_slice(&output[..decoded_samples as usize]);
+fn error_kind(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::CONFLICT => "duplicate_request",
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::SERVICE_UNAVAILABLE => "queue_full",
+        StatusCode::GATEWAY_TIMEOUT => "deadline_exceeded",
+        _ => "internal",
     }
+}
 
-    unsafe { opus::opus_decoder_destroy(decoder) };
+pub(crate) fn with_error_kind(mut response: Response, kind: &'static str) -> Response {
+    response.extensions_mut().insert(ErrorKind(kind));
+    response
+}
 
-    let temp_file = NamedTempFile::new().context("Failed to create temp file")?;
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: 16000,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
+/// `x-metric-*` timing headers of a job.
+pub(crate) fn insert_metric_headers(headers: &mut HeaderMap, metrics: &JobMetrics) {
+    let _ = headers.insert(HEADER_QUEUE_MS, HeaderValue::from(metrics.queue_wait_ms));
+    let _ = headers.insert(HEADER_INFER_MS, HeaderValue::from(metrics.inference_ms));
+    let _ = headers.insert(HEADER_WORKER_MS, HeaderValue::from(metrics.worker_total_ms));
+}
 
+/// The id the client picked in `x-request-id`, if any.
+///
+/// Ids from [`SERVER_REQUEST_ID_BASE`] up are refused so they never collide with the
+/// server's own live and batch jobs.
+pub(crate) fn client_request_id(headers: &HeaderMap) -> Result<Option<u64>, String> {
+    match headers
+        .get("x-request-id")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok())
     {
-        let mut writer =
-            WavWriter::create(temp_file.path(), spec).context("Failed to create WAV writer")?;
+        Some(id) if id >= SERVER_REQUEST_ID_BASE => Err(format!(
+            "x-request-id must be below {}",
+            SERVER_REQUEST_ID_BASE
+        )),
+        id => Ok(id),
+    }
+}
 
-        for sample in &samples {
-            writer
-                .write_sample(*sample)
-                .context("Failed to write WAV sample")?;
-        }
+/// When the client stops waiting, from `x-deadline-ms` (a budget counted from now).
+pub(crate) fn request_deadline(headers: &HeaderMap) -> Result<Option<Instant>, String> {
+    let Some(value) = headers.get(HEADER_DEADLINE_MS) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .filter(|ms| *ms > 0)
+        .map(|ms| Some(Instant::now() + Duration::from_millis(ms)))
+        .ok_or_else(|| "x-deadline-ms must be a positive number of milliseconds".to_string())
+}
 
-        writer.finalize().context("Failed to finalize WAV")?;
+/// Scheduling class from `x-priority`; requests without one are treated as realtime.
+pub(crate) fn request_priority(headers: &HeaderMap) -> Result<JobPriority, String> {
+    match headers.get(HEADER_PRIORITY).map(|h| h.to_str()) {
+        None => Ok(JobPriority::Realtime),
+        Some(Ok(value)) => JobPriority::parse(value.trim())
+            .ok_or_else(|| format!("unsupported priority '{}'", value.trim())),
+        Some(Err(_)) => Err("invalid x-priority header".to_string()),
     }
+}
 
-    let wav_data = std::fs::read(temp_file.path()).context("Failed to read WAV file")?;
+fn cancelled_status() -> StatusCode {
+    StatusCode::from_u16(STATUS_CANCELLED).expect("499 is a valid status code")
+}
 
-    info!(
-        "Opus decompression: {} frames → {} samples → {} bytes WAV",
-        (compressed.len() / 1024).max(1),
-        samples.len(),
-        wav_data.len()
-    );
+pub(crate) fn response_with_status(status: StatusCode, body: &[u8]) -> Response {
+    let mut resp = Response::new(body.to_vec().into());
+    *resp.status_mut() = status;
+    resp
+}
 
-    Ok(wav_data)
-}
-
-fn validate_wav(data: &[u8]) -> std::result::Result<(), String> {
-    let cursor = Cursor::new(data);
-    let mut reader = hound::WavReader::new(cursor).map_err(|e| format!("invalid wav: {}", e))?;
-    let spec = reader.spec();
-    if spec.channels != 1 || spec.sample_rate != 16_000 || spec.bits_per_sample != 16 {
-        return Err(format!(
-            "unsupported wav format: {}ch {}Hz {}-bit",
-            spec.channels, spec.sample_rate, spec.bits_per_sample
-        ));
-    }
-    // Ensure there is at least one sample; avoid empty payloads that whisper cannot handle.
-    if reader
-        .samples::<i16>()
-        .next()
-        .transpose()
-        .unwrap_or(None)
-        .is_none()
-    {
-        return Err("wav contains no samples".to_string());
-    }
-    Ok(())
+pub(crate) fn json_response(status: StatusCode, body: &impl Serialize) -> Response {
+    let body = serde_json::to_vec(body).unwrap_or_default();
+    let mut response = response_with_status(status, &body);
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+    response
 }
+
+#[cfg(all(test, feature = "stt_mock"))]
+mod tests;
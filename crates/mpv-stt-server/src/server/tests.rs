@@ -0,0 +1,1702 @@
+use super::*;
+use crate::models::{DEFAULT_MODEL_NAME, ModelConfig, WHISPER_LANGUAGES};
+use crate::transcribe::{
+    COMPRESSION_AUTO, COMPRESSION_OGG_OPUS, COMPRESSION_WAV, HEADER_CACHE, HEADER_LANGUAGE,
+};
+use crate::worker::WorkerPool;
+use axum::body::Body;
+use axum::http::Request;
+use axum::http::header::ACCEPT;
+use mpv_stt_common::TranscriptSegment;
+use mpv_stt_plugin::MockSttConfig;
+use mpv_stt_protocol::{NDJSON_CONTENT_TYPE, OggOpusWriter, StreamEvent, TranscriptionResponse};
+use mpv_stt_srt::SrtFile;
+use tower::ServiceExt;
+
+fn mock_model(runner: MockSttConfig, workers: usize, queue_capacity: usize) -> ModelConfig {
+    ModelConfig {
+        name: DEFAULT_MODEL_NAME.to_string(),
+        runner,
+        workers,
+        queue_capacity,
+        languages: WHISPER_LANGUAGES
+            .iter()
+            .map(|lang| lang.to_string())
+            .collect(),
+    }
+}
+
+fn single_model(runner: MockSttConfig, workers: usize, queue_capacity: usize) -> ModelRegistry {
+    let model = HostedModel::start(mock_model(runner, workers, queue_capacity));
+    ModelRegistry::new(vec![model], None)
+}
+
+/// Plain HTTP, no secrets, no cache; tests override what they exercise.
+fn test_config() -> ServerConfig {
+    ServerConfig {
+        enable_encryption: false,
+        encryption_key: String::new(),
+        auth_secret: String::new(),
+        admin_secret: String::new(),
+        warmup: false,
+        default_model: None,
+        option_limits: OptionLimits::default(),
+        api_keys: None,
+        tls: None,
+        cache: None,
+        shutdown_timeout: Duration::from_secs(30),
+        jobs_dir: None,
+    }
+}
+
+/// 100 ms of silence for the default model.
+fn silent_job(request_id: u64, priority: JobPriority) -> TranscriptionJob {
+    TranscriptionJob {
+        request_id,
+        model: DEFAULT_MODEL_NAME.to_string(),
+        audio: PcmBuffer::silence(100, 16_000, 1),
+        duration_ms: 100,
+        options: Default::default(),
+        priority,
+        on_segment: None,
+        enqueue_at: Instant::now(),
+        deadline: None,
+    }
+}
+
+fn test_router(num_workers: usize) -> Router {
+    let runner_config = MockSttConfig {
+        delay_ms: 20,
+        ..Default::default()
+    };
+    let config = test_config();
+    build_router(AppState::new(
+        single_model(runner_config, num_workers, 64),
+        &config,
+    ))
+}
+
+/// A tone of `ms` milliseconds, which the mock backend reports as one segment `0..ms`.
+fn tone_wav(ms: u64) -> Vec<u8> {
+    let samples = (0..ms * 16)
+        .map(|i| if i % 2 == 0 { 8_000 } else { -8_000 })
+        .collect();
+    PcmBuffer::new(samples, 16_000, 1).to_wav_bytes().unwrap()
+}
+
+fn transcribe_request(request_id: u64, wav: Vec<u8>) -> Request<Body> {
+    Request::post("/transcribe")
+        .header("x-request-id", request_id.to_string())
+        .header("x-compression", COMPRESSION_WAV)
+        .body(Body::from(wav))
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_requests_get_their_own_results() {
+    let app = test_router(3);
+
+    let requests = (1..=32u64).map(|request_id| {
+        let app = app.clone();
+        tokio::spawn(async move {
+            let duration_ms = request_id * 100;
+            let response = app
+                .oneshot(transcribe_request(request_id, tone_wav(duration_ms)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let srt = SrtFile::parse_content(std::str::from_utf8(&body).unwrap()).unwrap();
+            (duration_ms, srt.segments())
+        })
+    });
+
+    for handle in requests.collect::<Vec<_>>() {
+        let (duration_ms, segments) = handle.await.unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].end_ms, duration_ms);
+    }
+}
+
+#[tokio::test]
+async fn test_duplicate_inflight_request_id_is_rejected() {
+    let runner_config = MockSttConfig {
+        delay_ms: 500,
+        ..Default::default()
+    };
+    let pool = WorkerPool::new(runner_config, 1, 4);
+    let job = || silent_job(7, JobPriority::Realtime);
+
+    let first = pool.submit_job(job(), Caller::default()).unwrap();
+    assert!(matches!(
+        pool.submit_job(job(), Caller::default()),
+        Err(SubmitError::DuplicateRequest(7))
+    ));
+    assert!(matches!(first.await, Ok(JobResult::Success { .. })));
+    assert!(pool.submit_job(job(), Caller::default()).is_ok());
+}
+
+#[tokio::test]
+async fn test_full_queue_rejects_with_retry_after() {
+    let runner_config = MockSttConfig {
+        delay_ms: 500,
+        ..Default::default()
+    };
+    let pool = WorkerPool::new(runner_config, 1, 1);
+    let job = |request_id| TranscriptionJob {
+        request_id,
+        model: DEFAULT_MODEL_NAME.to_string(),
+        audio: PcmBuffer::silence(100, 16_000, 1),
+        duration_ms: 100,
+        options: Default::default(),
+        priority: JobPriority::Realtime,
+        on_segment: None,
+        enqueue_at: Instant::now(),
+        deadline: None,
+    };
+
+    let _running = pool.submit_job(job(1), Caller::default()).unwrap();
+    while pool.queue_depth() > 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    let _queued = pool.submit_job(job(2), Caller::default()).unwrap();
+    match pool.submit_job(job(3), Caller::default()) {
+        Err(SubmitError::QueueFull { depth, retry_after }) => {
+            assert_eq!(depth, 1);
+            assert!(retry_after >= Duration::from_secs(1));
+        }
+        other => panic!("expected a full queue, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+async fn test_realtime_jobs_run_first_and_preempt_prefetch() {
+    let runner_config = MockSttConfig {
+        delay_ms: 200,
+        ..Default::default()
+    };
+    let pool = WorkerPool::new(runner_config, 1, 8);
+
+    let finished = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut waiters = Vec::new();
+    let mut submit = |request_id, priority| {
+        let rx = pool
+            .submit_job(silent_job(request_id, priority), Caller::default())
+            .unwrap();
+        let finished = Arc::clone(&finished);
+        waiters.push(tokio::spawn(async move {
+            let result = rx.await.unwrap();
+            assert!(matches!(result, JobResult::Success { .. }), "{:?}", result);
+            finished.lock().unwrap().push(request_id);
+        }));
+    };
+
+    submit(1, JobPriority::Prefetch);
+    while pool.busy_workers() == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    submit(2, JobPriority::Batch);
+    submit(3, JobPriority::Prefetch);
+    submit(4, JobPriority::Realtime);
+    for waiter in waiters {
+        waiter.await.unwrap();
+    }
+
+    // The realtime job interrupts job 1, which resumes ahead of later prefetch work.
+    assert_eq!(*finished.lock().unwrap(), vec![4, 1, 3, 2]);
+
+    let mut headers = HeaderMap::new();
+    assert_eq!(request_priority(&headers), Ok(JobPriority::Realtime));
+    headers.insert(HEADER_PRIORITY, HeaderValue::from_static("prefetch"));
+    assert_eq!(request_priority(&headers), Ok(JobPriority::Prefetch));
+    headers.insert(HEADER_PRIORITY, HeaderValue::from_static("urgent"));
+    assert!(request_priority(&headers).is_err());
+}
+
+#[tokio::test]
+async fn test_health_readiness_and_metrics() {
+    let app = test_router(1);
+
+    let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(get("/healthz")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(get("/readyz")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(transcribe_request(1, tone_wav(500)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app
+        .clone()
+        .oneshot(transcribe_request(2, Vec::new()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // Not a WebSocket upgrade.
+    let response = app.clone().oneshot(get("/live")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = app.oneshot(get("/metrics")).await.unwrap();
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        PROMETHEUS_CONTENT_TYPE
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let text = std::str::from_utf8(&body).unwrap();
+    assert!(text.contains("mpv_stt_ready 1\n"));
+    assert!(text.contains("mpv_stt_workers 1\n"));
+    assert!(text.contains("mpv_stt_requests_total 1\n"));
+    assert!(text.contains("mpv_stt_errors_total{kind=\"bad_request\"} 2\n"));
+    assert!(text.contains("mpv_stt_inference_seconds_count 1\n"));
+}
+
+/// Start a server with one mock model on a free port.
+async fn start_server(delay_ms: u64, shutdown_timeout: Duration) -> HttpServer {
+    let runner = MockSttConfig {
+        delay_ms,
+        ..Default::default()
+    };
+    let config = ServerConfig {
+        shutdown_timeout,
+        ..test_config()
+    };
+    HttpServer::bind("127.0.0.1:0", vec![mock_model(runner, 1, 4)], config)
+        .await
+        .unwrap()
+}
+
+/// POST a WAV to `/transcribe` over a fresh connection; returns the raw response.
+async fn post_transcribe(addr: SocketAddr, request_id: u64) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let wav = tone_wav(500);
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let head = format!(
+        "POST /transcribe HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+         x-request-id: {}\r\nx-compression: {}\r\nContent-Length: {}\r\n\r\n",
+        request_id,
+        COMPRESSION_WAV,
+        wav.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&wav).await.unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await;
+    response
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_shutdown_drains_running_jobs_and_refuses_new_ones() {
+    let server = start_server(500, Duration::from_secs(10)).await;
+    let addr = server.local_addr();
+
+    let running = tokio::spawn(post_transcribe(addr, 1));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let shutdown = tokio::spawn(server.shutdown());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let refused = post_transcribe(addr, 2).await;
+    assert!(refused.starts_with("HTTP/1.1 503"), "{}", refused);
+    assert!(refused.ends_with("server is shutting down"));
+
+    let finished = running.await.unwrap();
+    assert!(finished.starts_with("HTTP/1.1 200"), "{}", finished);
+    tokio::time::timeout(Duration::from_secs(5), shutdown)
+        .await
+        .expect("shutdown finishes once the job is done")
+        .unwrap();
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_shutdown_cancels_jobs_past_the_deadline() {
+    let server = start_server(10_000, Duration::from_millis(200)).await;
+    let addr = server.local_addr();
+
+    let running = tokio::spawn(post_transcribe(addr, 1));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    tokio::time::timeout(Duration::from_secs(5), server.shutdown())
+        .await
+        .expect("shutdown is bounded by its timeout");
+
+    let cancelled = running.await.unwrap();
+    assert!(
+        cancelled.starts_with(&format!("HTTP/1.1 {}", STATUS_CANCELLED)),
+        "{}",
+        cancelled
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_delete_cancels_running_and_queued_requests() {
+    let runner_config = MockSttConfig {
+        delay_ms: 10_000,
+        ..Default::default()
+    };
+    let config = ServerConfig {
+        auth_secret: "secret".to_string(),
+        ..test_config()
+    };
+    let app = build_router(AppState::new(single_model(runner_config, 1, 1), &config));
+    let token = hex::encode(AuthToken::from_secret("secret").as_bytes());
+
+    let submit = |request_id: u64| {
+        let mut request = transcribe_request(request_id, tone_wav(500));
+        request
+            .headers_mut()
+            .insert("x-auth-token", HeaderValue::from_str(&token).unwrap());
+        tokio::spawn(app.clone().oneshot(request))
+    };
+    let cancel = |request_id: u64, token: &str| {
+        Request::delete(format!("/transcribe/{}", request_id))
+            .header("x-auth-token", token)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let running = submit(1);
+    let queued = submit(2);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let response = app.clone().oneshot(cancel(1, "00")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let start = Instant::now();
+    let response = app.clone().oneshot(cancel(2, &token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // The cancelled job left the queue, so it has room again.
+    let requeued = submit(3);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    for request_id in [3, 1] {
+        let response = app
+            .clone()
+            .oneshot(cancel(request_id, &token))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = app.clone().oneshot(cancel(4, &token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    for handle in [running, queued, requeued] {
+        let response = handle.await.unwrap().unwrap();
+        assert_eq!(response.status(), cancelled_status());
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_abandoned_requests_are_cancelled() {
+    let runner_config = MockSttConfig {
+        delay_ms: 10_000,
+        ..Default::default()
+    };
+    let state = AppState::new(single_model(runner_config, 1, 4), &test_config());
+    let app = build_router(state.clone());
+
+    let request = tokio::spawn(app.oneshot(transcribe_request(1, tone_wav(500))));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(state.models.snapshot().busy_workers, 1);
+    request.abort();
+
+    let start = Instant::now();
+    while !state.models.is_idle() {
+        assert!(start.elapsed() < Duration::from_secs(5), "job kept running");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_without_keys_only_the_cancel_token_may_cancel() {
+    let runner_config = MockSttConfig {
+        delay_ms: 10_000,
+        ..Default::default()
+    };
+    let app = build_router(AppState::new(
+        single_model(runner_config, 1, 4),
+        &test_config(),
+    ));
+    let mut request = transcribe_request(1, tone_wav(500));
+    request
+        .headers_mut()
+        .insert("x-cancel-token", HeaderValue::from_static("mine"));
+    let running = tokio::spawn(app.clone().oneshot(request));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let cancel = |cancel_token: Option<&'static str>| {
+        let mut request = Request::delete("/transcribe/1");
+        if let Some(cancel_token) = cancel_token {
+            request = request.header("x-cancel-token", cancel_token);
+        }
+        request.body(Body::empty()).unwrap()
+    };
+    let response = app.clone().oneshot(cancel(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.clone().oneshot(cancel(Some("theirs"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = app.clone().oneshot(cancel(Some("mine"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = running.await.unwrap().unwrap();
+    assert_eq!(response.status(), cancelled_status());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_only_the_submitting_key_or_admin_may_cancel() {
+    let keys = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        keys.path(),
+        r#"
+        [[keys]]
+        name = "team-a"
+        secret = "a-secret"
+
+        [[keys]]
+        name = "team-b"
+        secret = "b-secret"
+        "#,
+    )
+    .unwrap();
+    let runner_config = MockSttConfig {
+        delay_ms: 10_000,
+        ..Default::default()
+    };
+    let config = ServerConfig {
+        api_keys: Some(keys.path().to_path_buf()),
+        admin_secret: "admin".to_string(),
+        ..test_config()
+    };
+    let state = AppState::new(single_model(runner_config, 1, 4), &config);
+    state.keys.watch_file(keys.path()).unwrap();
+    let app = build_router(state);
+    let bearer = |secret: &str| HeaderValue::from_str(&format!("Bearer {}", secret)).unwrap();
+    let submit = |request_id: u64, secret: &str| {
+        let mut request = transcribe_request(request_id, tone_wav(500));
+        request.headers_mut().insert(AUTHORIZATION, bearer(secret));
+        tokio::spawn(app.clone().oneshot(request))
+    };
+    let cancel = |request_id: u64, secret: &str| {
+        Request::delete(format!("/transcribe/{}", request_id))
+            .header(AUTHORIZATION, bearer(secret))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let running = submit(1, "a-secret");
+    let queued = submit(2, "b-secret");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let response = app.clone().oneshot(cancel(1, "b-secret")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = app.clone().oneshot(cancel(1, "a-secret")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(cancel(2, "admin")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    for handle in [running, queued] {
+        let response = handle.await.unwrap().unwrap();
+        assert_eq!(response.status(), cancelled_status());
+    }
+
+    let response = submit(SERVER_REQUEST_ID_BASE, "a-secret")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_requests_past_their_deadline_are_dropped_or_aborted() {
+    let runner_config = MockSttConfig {
+        delay_ms: 10_000,
+        ..Default::default()
+    };
+    let config = test_config();
+    let app = build_router(AppState::new(single_model(runner_config, 1, 4), &config));
+    let submit = |request_id: u64, deadline_ms: &'static str| {
+        let mut request = transcribe_request(request_id, tone_wav(500));
+        request
+            .headers_mut()
+            .insert(HEADER_DEADLINE_MS, HeaderValue::from_static(deadline_ms));
+        tokio::spawn(app.clone().oneshot(request))
+    };
+
+    let start = Instant::now();
+    let running = submit(1, "300");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let queued = submit(2, "300");
+
+    for handle in [running, queued] {
+        let response = handle.await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(response.headers().contains_key(HEADER_QUEUE_MS));
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let response = submit(3, "soon").await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_uploads_in_other_formats_are_resampled() {
+    let app = test_router(1);
+    // One second of a 440 Hz tone, 44.1 kHz stereo.
+    let samples = (0..44_100)
+        .flat_map(|i| {
+            let phase = i as f32 * 440.0 * std::f32::consts::TAU / 44_100.0;
+            let sample = (phase.sin() * 8_000.0) as i16;
+            [sample, sample]
+        })
+        .collect();
+    let wav = PcmBuffer::new(samples, 44_100, 2).to_wav_bytes().unwrap();
+
+    let mut request = transcribe_request(1, wav);
+    request
+        .headers_mut()
+        .insert("x-compression", HeaderValue::from_static(COMPRESSION_AUTO));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let srt = SrtFile::parse_content(&body_text(response).await).unwrap();
+    let segments = srt.segments();
+    assert_eq!(segments.len(), 1);
+    assert!((900..=1_000).contains(&segments[0].end_ms));
+
+    let response = app
+        .oneshot(transcribe_request(2, b"not audio at all".to_vec()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_ogg_opus_upload_is_trimmed_to_its_granule_position() {
+    let app = test_router(1);
+    // One-byte packets (20 ms SILK frames without payload) decode to concealment audio.
+    let mut writer = OggOpusWriter::new(1, 16_000, 312, 42);
+    for _ in 0..51 {
+        writer.push_packet(&[0x08], 960);
+    }
+    let ogg = writer.finish(16_000);
+
+    let mut request = transcribe_request(1, ogg);
+    let headers = request.headers_mut();
+    headers.insert(
+        "x-compression",
+        HeaderValue::from_static(COMPRESSION_OGG_OPUS),
+    );
+    headers.insert(ACCEPT, HeaderValue::from_static(NDJSON_CONTENT_TYPE));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_text(response).await;
+    let summary: StreamEvent = serde_json::from_str(body.lines().last().unwrap()).unwrap();
+    assert!(matches!(
+        summary,
+        StreamEvent::Done {
+            duration_ms: 1_000,
+            ..
+        }
+    ));
+
+    let mut request = transcribe_request(2, tone_wav(100));
+    request.headers_mut().insert(
+        "x-compression",
+        HeaderValue::from_static(COMPRESSION_OGG_OPUS),
+    );
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_streamed_transcription_emits_segments_then_summary() {
+    let app = test_router(1);
+    let mut samples = Vec::new();
+    for amplitude in [8_000, 0, 8_000] {
+        samples.extend((0..8_000).map(|i| if i % 2 == 0 { amplitude } else { -amplitude }));
+    }
+    let wav = PcmBuffer::new(samples, 16_000, 1).to_wav_bytes().unwrap();
+
+    let mut request = transcribe_request(7, wav);
+    request
+        .headers_mut()
+        .insert(ACCEPT, HeaderValue::from_static(NDJSON_CONTENT_TYPE));
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], NDJSON_CONTENT_TYPE);
+
+    let body = body_text(response).await;
+    let events: Vec<StreamEvent> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events.len(), 3);
+    assert!(matches!(&events[0], StreamEvent::Segment(s) if s.start_ms == 0 && s.end_ms == 500));
+    assert!(matches!(&events[1], StreamEvent::Segment(s) if s.start_ms == 1_000));
+    assert!(matches!(
+        &events[2],
+        StreamEvent::Done {
+            duration_ms: 1_500,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_json_response_carries_segments_and_metrics() {
+    let app = test_router(1);
+    let mut request = transcribe_request(8, tone_wav(800));
+    request
+        .headers_mut()
+        .insert(ACCEPT, HeaderValue::from_static(JSON_CONTENT_TYPE));
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], JSON_CONTENT_TYPE);
+    let infer_ms: u64 = response.headers()[HEADER_INFER_MS]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let reply: TranscriptionResponse = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(reply.duration_ms, 800);
+    assert_eq!(reply.segments.len(), 1);
+    assert_eq!(reply.segments[0].end_ms, 800);
+    assert_eq!(reply.metrics.inference_ms, infer_ms);
+    assert!(reply.metrics.worker_total_ms >= reply.metrics.inference_ms);
+}
+
+#[tokio::test]
+async fn test_live_websocket_emits_partial_final_and_flushed() {
+    use futures::{SinkExt, StreamExt};
+    use mpv_stt_protocol::{LiveControl, LiveEvent};
+    use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::serve(listener, test_router(1)).into_future());
+
+    let mut request = format!("ws://{}/live", addr).into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("x-stream-start-ms", HeaderValue::from_static("5000"));
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    let mut events = Vec::new();
+    // 1.2 s of speech, then enough silence to end the utterance plus 100 ms spare. Partials
+    // run beside the stream and a final drops the one in flight, so the first second waits
+    // for its partial.
+    for amplitudes in [vec![8_000i16; 10], [vec![8_000; 2], vec![0; 7]].concat()] {
+        let waiting_for_partial = events.is_empty();
+        for amplitude in amplitudes {
+            let chunk: Vec<u8> = (0..1_600)
+                .flat_map(|i| (if i % 2 == 0 { amplitude } else { -amplitude }).to_le_bytes())
+                .collect();
+            socket
+                .send(tungstenite::Message::Binary(chunk))
+                .await
+                .unwrap();
+        }
+        let partial = if waiting_for_partial {
+            socket.next().await
+        } else {
+            None
+        };
+        if let Some(Ok(tungstenite::Message::Text(text))) = partial {
+            events.push(serde_json::from_str::<LiveEvent>(&text).unwrap());
+        }
+    }
+    let flush = serde_json::to_string(&LiveControl::Flush).unwrap();
+    socket
+        .send(tungstenite::Message::Text(flush))
+        .await
+        .unwrap();
+
+    while let Some(message) = socket.next().await {
+        if let tungstenite::Message::Text(text) = message.unwrap() {
+            let event: LiveEvent = serde_json::from_str(&text).unwrap();
+            let done = matches!(event, LiveEvent::Flushed { .. });
+            events.push(event);
+            if done {
+                break;
+            }
+        }
+    }
+
+    let segment = |start_ms, end_ms| TranscriptSegment {
+        start_ms,
+        end_ms,
+        text: String::new(),
+    };
+    let times = |segments: &[TranscriptSegment]| {
+        segments
+            .iter()
+            .map(|s| segment(s.start_ms, s.end_ms))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(events.len(), 3, "{:?}", events);
+    assert!(
+        matches!(&events[0], LiveEvent::Partial { segments } if times(segments) == [segment(5_000, 6_000)])
+    );
+    assert!(
+        matches!(&events[1], LiveEvent::Final { segments } if times(segments) == [segment(5_000, 6_200)])
+    );
+    assert_eq!(events[2], LiveEvent::Flushed { until_ms: 6_900 });
+}
+
+/// `multipart/form-data` request as sent by OpenAI SDKs.
+fn openai_request(path: &str, wav: Vec<u8>, fields: &[(&str, &str)]) -> Request<Body> {
+    const BOUNDARY: &str = "mpv-stt-test-boundary";
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                BOUNDARY, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"audio.wav\"\r\nContent-Type: audio/wav\r\n\r\n",
+            BOUNDARY
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&wav);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+    Request::post(path)
+        .header(
+            CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn body_text(response: Response) -> String {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_openai_transcriptions_response_formats() {
+    let app = test_router(2);
+
+    let request = openai_request(
+        "/v1/audio/transcriptions",
+        tone_wav(1_500),
+        &[
+            ("model", "whisper-1"),
+            ("language", "de"),
+            ("response_format", "verbose_json"),
+            ("timestamp_granularities[]", "segment"),
+        ],
+    );
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(json["task"], "transcribe");
+    assert_eq!(json["language"], "de");
+    assert_eq!(json["duration"], 1.5);
+    assert_eq!(json["text"], "mock speech 1");
+    assert_eq!(json["segments"][0]["end"], 1.5);
+
+    let request = openai_request(
+        "/v1/audio/translations",
+        tone_wav(1_000),
+        &[("response_format", "vtt")],
+    );
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        body_text(response).await,
+        "WEBVTT\n\n00:00:00.000 --> 00:00:01.000\nmock speech 1\n"
+    );
+
+    let request = openai_request("/v1/audio/transcriptions", tone_wav(1_000), &[]);
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body_text(response).await, r#"{"text":"mock speech 1"}"#);
+}
+
+#[tokio::test]
+async fn test_openai_errors_use_error_envelope() {
+    let config = ServerConfig {
+        auth_secret: "secret".to_string(),
+        ..test_config()
+    };
+    let app = build_router(AppState::new(
+        single_model(MockSttConfig::default(), 1, 4),
+        &config,
+    ));
+
+    let request = openai_request("/v1/audio/transcriptions", tone_wav(500), &[]);
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let mut request = openai_request(
+        "/v1/audio/transcriptions",
+        tone_wav(500),
+        &[
+            ("timestamp_granularities[]", "word"),
+            ("response_format", "verbose_json"),
+        ],
+    );
+    request
+        .headers_mut()
+        .insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let json: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(json["error"]["type"], "invalid_request_error");
+    assert_eq!(json["error"]["param"], "timestamp_granularities");
+
+    let mut request = openai_request(
+        "/v1/audio/transcriptions",
+        b"not a wav".to_vec(),
+        &[("model", "whisper-1")],
+    );
+    request
+        .headers_mut()
+        .insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let json: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(json["error"]["param"], "file");
+}
+
+#[tokio::test]
+async fn test_api_keys_are_rate_limited_and_metered() {
+    let keys = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        keys.path(),
+        r#"
+        [[keys]]
+        name = "team-a"
+        secret = "a-secret"
+        requests_per_minute = 1
+
+        [[keys]]
+        name = "team-b"
+        secret = "b-secret"
+        audio_minutes_per_day = 1
+        "#,
+    )
+    .unwrap();
+    let config = ServerConfig {
+        api_keys: Some(keys.path().to_path_buf()),
+        ..test_config()
+    };
+    let mut state = AppState::new(single_model(MockSttConfig::default(), 1, 4), &config);
+    state.keys.watch_file(keys.path()).unwrap();
+    state.cache = Some(Arc::new(
+        ResultCache::open(CacheConfig {
+            max_bytes: 1024 * 1024,
+            ttl: Duration::from_secs(60),
+            dir: None,
+        })
+        .unwrap(),
+    ));
+    let app = build_router(state);
+    let with_key = |request_id, ms, secret: &str| {
+        let mut request = transcribe_request(request_id, tone_wav(ms));
+        request.headers_mut().insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", secret)).unwrap(),
+        );
+        request
+    };
+
+    let response = app
+        .clone()
+        .oneshot(transcribe_request(1, tone_wav(500)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(with_key(2, 500, "a-secret"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app
+        .clone()
+        .oneshot(with_key(3, 500, "a-secret"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()[RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+
+    let response = app
+        .clone()
+        .oneshot(with_key(4, 45_000, "b-secret"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app
+        .clone()
+        .oneshot(with_key(5, 30_000, "b-secret"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(RETRY_AFTER));
+    let response = app
+        .clone()
+        .oneshot(with_key(6, 10_000, "b-secret"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // Cached results count against the quota as well.
+    let response = app
+        .clone()
+        .oneshot(with_key(7, 10_000, "b-secret"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let text = body_text(
+        app.oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert!(text.contains("mpv_stt_key_requests_total{key=\"team-a\"} 1\n"));
+    assert!(text.contains("mpv_stt_key_rejected_total{key=\"team-a\"} 1\n"));
+    assert!(text.contains("mpv_stt_key_audio_seconds_today{key=\"team-b\"} 55\n"));
+    assert!(text.contains("mpv_stt_errors_total{kind=\"rate_limited\"} 1\n"));
+    assert!(text.contains("mpv_stt_errors_total{kind=\"quota_exceeded\"} 2\n"));
+}
+
+#[tokio::test]
+async fn test_failed_jobs_give_back_their_audio() {
+    let keys = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        keys.path(),
+        r#"
+        [[keys]]
+        name = "team-b"
+        secret = "b-secret"
+        audio_minutes_per_day = 1
+        "#,
+    )
+    .unwrap();
+    let config = ServerConfig {
+        api_keys: Some(keys.path().to_path_buf()),
+        ..test_config()
+    };
+    let model = |name: &str, fail_every| {
+        HostedModel::start(ModelConfig {
+            runner: MockSttConfig {
+                fail_every,
+                ..Default::default()
+            },
+            name: name.to_string(),
+            ..mock_model(MockSttConfig::default(), 1, 4)
+        })
+    };
+    let models = ModelRegistry::new(vec![model("broken", 1), model("good", 0)], Some("good"));
+    let state = AppState::new(models, &config);
+    state.keys.watch_file(keys.path()).unwrap();
+    let app = build_router(state);
+    let request = |request_id, model: &'static str| {
+        let mut request = transcribe_request(request_id, tone_wav(45_000));
+        let headers = request.headers_mut();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer b-secret"));
+        headers.insert(HEADER_MODEL, HeaderValue::from_static(model));
+        request
+    };
+
+    // Neither the failed job nor its retry on a working model exceeds the quota.
+    for (request_id, model, status) in [
+        (1, "broken", StatusCode::INTERNAL_SERVER_ERROR),
+        (2, "broken", StatusCode::INTERNAL_SERVER_ERROR),
+        (3, "good", StatusCode::OK),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(request(request_id, model))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status);
+    }
+
+    let text = body_text(
+        app.oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert!(text.contains("mpv_stt_key_audio_seconds_today{key=\"team-b\"} 45\n"));
+}
+
+#[tokio::test]
+async fn test_requests_pick_their_model() {
+    let model = |name: &str, fail_every| {
+        HostedModel::start(ModelConfig {
+            name: name.to_string(),
+            runner: MockSttConfig {
+                fail_every,
+                ..Default::default()
+            },
+            workers: 1,
+            queue_capacity: 4,
+            languages: vec!["en".to_string()],
+        })
+    };
+    let config = ServerConfig {
+        default_model: Some("good".to_string()),
+        ..test_config()
+    };
+    let models = ModelRegistry::new(vec![model("broken", 1), model("good", 0)], Some("good"));
+    let app = build_router(AppState::new(models, &config));
+
+    let response = app
+        .clone()
+        .oneshot(transcribe_request(1, tone_wav(500)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[HEADER_MODEL], "good");
+
+    let mut request = transcribe_request(2, tone_wav(500));
+    request
+        .headers_mut()
+        .insert(HEADER_MODEL, HeaderValue::from_static("broken"));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let mut request = transcribe_request(3, tone_wav(500));
+    request
+        .headers_mut()
+        .insert(HEADER_MODEL, HeaderValue::from_static("missing"));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(
+        body_text(response)
+            .await
+            .contains("available: broken, good")
+    );
+
+    let request = openai_request(
+        "/v1/audio/transcriptions",
+        tone_wav(500),
+        &[("model", "broken")],
+    );
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let request = openai_request(
+        "/v1/audio/transcriptions",
+        tone_wav(500),
+        &[("model", "missing")],
+    );
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let json: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(json["error"]["param"], "model");
+
+    let request = Request::get("/v1/models").body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    let ids: Vec<_> = json["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| {
+            (
+                model["id"].as_str().unwrap(),
+                model["default"].as_bool().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(ids, [("broken", false), ("good", true)]);
+    assert_eq!(json["data"][0]["languages"][0], "en");
+}
+
+#[tokio::test]
+async fn test_identical_audio_is_served_from_cache() {
+    let config = ServerConfig {
+        cache: Some(CacheConfig {
+            max_bytes: 1024 * 1024,
+            ttl: Duration::from_secs(60),
+            dir: None,
+        }),
+        ..test_config()
+    };
+    let mut state = AppState::new(single_model(MockSttConfig::default(), 1, 4), &config);
+    state.cache = Some(Arc::new(
+        ResultCache::open(config.cache.clone().unwrap()).unwrap(),
+    ));
+    let app = build_router(state);
+
+    let first = app
+        .clone()
+        .oneshot(transcribe_request(1, tone_wav(800)))
+        .await
+        .unwrap();
+    assert_eq!(first.headers()[HEADER_CACHE], "miss");
+    let first = body_text(first).await;
+
+    let second = app
+        .clone()
+        .oneshot(transcribe_request(2, tone_wav(800)))
+        .await
+        .unwrap();
+    assert_eq!(second.headers()[HEADER_CACHE], "hit");
+    assert_eq!(second.headers()[HEADER_INFER_MS], "0");
+    assert_eq!(body_text(second).await, first);
+
+    let mut request = transcribe_request(3, tone_wav(800));
+    request
+        .headers_mut()
+        .insert(ACCEPT, HeaderValue::from_static(NDJSON_CONTENT_TYPE));
+    let streamed = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(streamed.headers()[HEADER_CACHE], "hit");
+    let events: Vec<StreamEvent> = body_text(streamed)
+        .await
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(matches!(&events[0], StreamEvent::Segment(s) if s.end_ms == 800));
+    assert!(matches!(
+        &events[1],
+        StreamEvent::Done { metrics, .. } if metrics.cache_hit
+    ));
+
+    let mut request = transcribe_request(4, tone_wav(800));
+    request
+        .headers_mut()
+        .insert(options::HEADER_LANGUAGE, HeaderValue::from_static("de"));
+    let other_options = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(other_options.headers()[HEADER_CACHE], "miss");
+
+    let text = body_text(
+        app.oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert!(text.contains("mpv_stt_cache_hits_total 2\n"));
+    assert!(text.contains("mpv_stt_cache_misses_total 2\n"));
+    assert!(text.contains("mpv_stt_cache_entries 2\n"));
+    assert!(text.contains("mpv_stt_inference_seconds_count 2\n"));
+}
+
+#[tokio::test]
+async fn test_batch_jobs_show_why_they_wait() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        keys.path(),
+        r#"
+        [[keys]]
+        name = "team-b"
+        secret = "b-secret"
+        audio_minutes_per_day = 1
+        "#,
+    )
+    .unwrap();
+    let config = ServerConfig {
+        api_keys: Some(keys.path().to_path_buf()),
+        jobs_dir: Some(dir.path().to_path_buf()),
+        ..test_config()
+    };
+    let mut state = AppState::new(single_model(MockSttConfig::default(), 1, 4), &config);
+    state.keys.watch_file(keys.path()).unwrap();
+    state.jobs = Some(Arc::new(JobStore::open(dir.path().to_path_buf()).unwrap()));
+    let app = build_router(state);
+    let with_key =
+        |request: axum::http::request::Builder| request.header(AUTHORIZATION, "Bearer b-secret");
+
+    // The last 5 s chunk no longer fits in the minute of daily quota.
+    let created = app
+        .clone()
+        .oneshot(
+            with_key(Request::post("/jobs"))
+                .body(Body::from(tone_wav(65_000)))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(created.status(), StatusCode::ACCEPTED);
+    let created: serde_json::Value = serde_json::from_str(&body_text(created).await).unwrap();
+    let id = created["id"].as_str().unwrap();
+
+    let mut status = serde_json::Value::Null;
+    for _ in 0..100 {
+        let response = app
+            .clone()
+            .oneshot(
+                with_key(Request::get(format!("/jobs/{}", id)))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        status = serde_json::from_str(&body_text(response).await).unwrap();
+        if status["status"] == "waiting" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(status["status"], "waiting", "{}", status);
+    assert_eq!(status["chunks_done"], 2);
+    assert!(status["waiting_for"].as_str().unwrap().contains("quota"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_batch_jobs_charge_retried_chunks_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        keys.path(),
+        r#"
+        [[keys]]
+        name = "team-b"
+        secret = "b-secret"
+        audio_minutes_per_day = 10
+        "#,
+    )
+    .unwrap();
+    let config = ServerConfig {
+        api_keys: Some(keys.path().to_path_buf()),
+        jobs_dir: Some(dir.path().to_path_buf()),
+        ..test_config()
+    };
+    // Every other call fails, so the second and third chunks each need a retry.
+    let runner = MockSttConfig {
+        fail_every: 2,
+        ..Default::default()
+    };
+    let mut state = AppState::new(single_model(runner, 1, 4), &config);
+    state.keys.watch_file(keys.path()).unwrap();
+    state.jobs = Some(Arc::new(JobStore::open(dir.path().to_path_buf()).unwrap()));
+    let app = build_router(state);
+    let with_key =
+        |request: axum::http::request::Builder| request.header(AUTHORIZATION, "Bearer b-secret");
+
+    let created = app
+        .clone()
+        .oneshot(
+            with_key(Request::post("/jobs"))
+                .body(Body::from(tone_wav(65_000)))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(created.status(), StatusCode::ACCEPTED);
+    let created: serde_json::Value = serde_json::from_str(&body_text(created).await).unwrap();
+    let id = created["id"].as_str().unwrap();
+
+    let mut status = serde_json::Value::Null;
+    for _ in 0..200 {
+        let response = app
+            .clone()
+            .oneshot(
+                with_key(Request::get(format!("/jobs/{}", id)))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        status = serde_json::from_str(&body_text(response).await).unwrap();
+        if status["status"] == "completed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(status["status"], "completed", "{}", status);
+
+    let text = body_text(
+        app.oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert!(text.contains("mpv_stt_key_audio_seconds_today{key=\"team-b\"} 65\n"));
+}
+
+/// Poll `GET /jobs/{id}` until the job completes; returns its last status.
+async fn wait_for_job(app: &Router, id: &str) -> serde_json::Value {
+    for _ in 0..100 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(format!("/jobs/{}", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        if status["status"] == "completed" {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("job {} did not complete", id);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_batch_jobs_are_chunked_persisted_and_resumed() {
+    let dir = tempfile::tempdir().unwrap();
+    let runner = MockSttConfig {
+        delay_ms: 100,
+        ..Default::default()
+    };
+    let jobs_state = |models: ModelRegistry| {
+        let config = ServerConfig {
+            jobs_dir: Some(dir.path().to_path_buf()),
+            ..test_config()
+        };
+        let mut state = AppState::new(models, &config);
+        state.jobs = Some(Arc::new(JobStore::open(dir.path().to_path_buf()).unwrap()));
+        state
+    };
+    let get = |uri: String| Request::get(uri).body(Body::empty()).unwrap();
+
+    let disabled = test_router(1)
+        .oneshot(
+            Request::post("/jobs")
+                .body(Body::from(tone_wav(500)))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(disabled.status(), StatusCode::NOT_FOUND);
+
+    // 65 s of audio is two full chunks and a short one.
+    let app = build_router(jobs_state(single_model(runner.clone(), 1, 4)));
+    let created = app
+        .clone()
+        .oneshot(
+            Request::post("/jobs")
+                .body(Body::from(tone_wav(65_000)))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(created.status(), StatusCode::ACCEPTED);
+    let location = created.headers()[axum::http::header::LOCATION]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let created: serde_json::Value = serde_json::from_str(&body_text(created).await).unwrap();
+    let id = created["id"].as_str().unwrap().to_string();
+    assert_eq!(location, format!("/jobs/{}", id));
+    // The upload is only decoded after the request is answered.
+    assert_eq!(created["chunks_total"], 0);
+
+    let pending = app
+        .clone()
+        .oneshot(get(format!("/jobs/{}/result", id)))
+        .await
+        .unwrap();
+    assert_eq!(pending.status(), StatusCode::CONFLICT);
+
+    let status = wait_for_job(&app, &id).await;
+    assert_eq!(status["chunks_done"], 3);
+    assert_eq!(status["chunks_total"], 3);
+    let srt = app
+        .clone()
+        .oneshot(get(format!("/jobs/{}/result", id)))
+        .await
+        .unwrap();
+    let srt = SrtFile::parse_content(&body_text(srt).await).unwrap();
+    let ends: Vec<u64> = srt.segments().iter().map(|s| s.end_ms).collect();
+    assert_eq!(ends, [30_000, 60_000, 65_000]);
+    let vtt = app
+        .clone()
+        .oneshot(get(format!("/jobs/{}/result?format=vtt", id)))
+        .await
+        .unwrap();
+    assert!(body_text(vtt).await.starts_with("WEBVTT"));
+    assert!(!dir.path().join(format!("{}.wav", id)).exists());
+    assert!(!dir.path().join(format!("{}.upload", id)).exists());
+
+    // Roll the job back to after its first chunk, as if the server had stopped there.
+    let record_path = dir.path().join(format!("{}.json", id));
+    let mut record: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&record_path).unwrap()).unwrap();
+    record["status"] = "running".into();
+    record["chunks_done"] = 1.into();
+    record["finished_at"] = serde_json::Value::Null;
+    record["segments"].as_array_mut().unwrap().truncate(1);
+    std::fs::write(&record_path, serde_json::to_vec(&record).unwrap()).unwrap();
+    std::fs::write(dir.path().join(format!("{}.wav", id)), tone_wav(65_000)).unwrap();
+
+    let state = jobs_state(single_model(runner, 1, 4));
+    let store = Arc::clone(state.jobs().unwrap());
+    assert_eq!(store.unfinished(), [id.clone()]);
+    tokio::spawn(jobs::run(state.clone(), store, id.clone()));
+    let app = build_router(state);
+    wait_for_job(&app, &id).await;
+    let json = app
+        .clone()
+        .oneshot(get(format!("/jobs/{}/result?format=json", id)))
+        .await
+        .unwrap();
+    assert_eq!(json.headers()[CONTENT_TYPE], "application/json");
+    let result: serde_json::Value = serde_json::from_str(&body_text(json).await).unwrap();
+    assert_eq!(result["duration_ms"], 65_000);
+    let ends: Vec<u64> = result["segments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["end_ms"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ends, [30_000, 60_000, 65_000]);
+
+    let unsupported = app
+        .oneshot(get(format!("/jobs/{}/result?format=txt", id)))
+        .await
+        .unwrap();
+    assert_eq!(unsupported.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_admin_endpoints_swap_worker_pools() {
+    let runner = MockSttConfig {
+        delay_ms: 300,
+        ..Default::default()
+    };
+    let config = ServerConfig {
+        admin_secret: "admin".to_string(),
+        ..test_config()
+    };
+    let state = AppState::new(single_model(runner, 1, 4), &config);
+    let app = build_router(state.clone());
+    let admin = |method: &str, uri: &str, body: &'static str| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(AUTHORIZATION, "Bearer admin")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let resize_uri = format!("/admin/models/{}/workers", DEFAULT_MODEL_NAME);
+
+    let disabled = test_router(1)
+        .oneshot(admin("POST", "/admin/warmup", ""))
+        .await
+        .unwrap();
+    assert_eq!(disabled.status(), StatusCode::NOT_FOUND);
+    let mut request = admin("PUT", &resize_uri, r#"{"workers": 2}"#);
+    request.headers_mut().remove(AUTHORIZATION);
+    let anonymous = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+    for (uri, body, status) in [
+        (
+            resize_uri.as_str(),
+            r#"{"workers": 0}"#,
+            StatusCode::BAD_REQUEST,
+        ),
+        (resize_uri.as_str(), "2", StatusCode::BAD_REQUEST),
+        (
+            "/admin/models/nope/workers",
+            r#"{"workers": 2}"#,
+            StatusCode::NOT_FOUND,
+        ),
+    ] {
+        let response = app.clone().oneshot(admin("PUT", uri, body)).await.unwrap();
+        assert_eq!(response.status(), status, "{} {}", uri, body);
+    }
+
+    // A request running on the old pool finishes there after the swap.
+    let running = tokio::spawn(app.clone().oneshot(transcribe_request(1, tone_wav(500))));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let resized = app
+        .clone()
+        .oneshot(admin("PUT", &resize_uri, r#"{"workers": 2}"#))
+        .await
+        .unwrap();
+    assert_eq!(resized.status(), StatusCode::OK);
+    let resized: serde_json::Value = serde_json::from_str(&body_text(resized).await).unwrap();
+    assert_eq!(resized["workers"], 2);
+    assert_eq!(state.models().default_model().pool.num_workers(), 2);
+    assert_eq!(state.models().snapshot().workers, 3);
+    assert_eq!(running.await.unwrap().unwrap().status(), StatusCode::OK);
+    let mut drained = false;
+    for _ in 0..40 {
+        if state.models().snapshot().workers == 2 {
+            drained = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(drained, "the old pool was not stopped");
+
+    let reload_uri = format!("/admin/models/{}/reload", DEFAULT_MODEL_NAME);
+    let reloaded = app
+        .clone()
+        .oneshot(admin("POST", &reload_uri, ""))
+        .await
+        .unwrap();
+    assert_eq!(reloaded.status(), StatusCode::OK);
+    let after_reload = app
+        .clone()
+        .oneshot(transcribe_request(2, tone_wav(500)))
+        .await
+        .unwrap();
+    assert_eq!(after_reload.status(), StatusCode::OK);
+    let warmed = app
+        .oneshot(admin("POST", "/admin/warmup", ""))
+        .await
+        .unwrap();
+    assert_eq!(body_text(warmed).await, "ready");
+}
+
+#[tokio::test]
+async fn test_request_options_are_applied_and_validated() {
+    let english_only = HostedModel::start(ModelConfig {
+        name: DEFAULT_MODEL_NAME.to_string(),
+        runner: MockSttConfig::default(),
+        workers: 1,
+        queue_capacity: 4,
+        languages: vec!["en".to_string()],
+    });
+    let config = test_config();
+    let app = build_router(AppState::new(
+        ModelRegistry::new(vec![english_only], None),
+        &config,
+    ));
+    let request_with = |request_id, headers: &[(&'static str, &'static str)]| {
+        let mut request = transcribe_request(request_id, tone_wav(600));
+        for (name, value) in headers {
+            request
+                .headers_mut()
+                .insert(*name, HeaderValue::from_static(value));
+        }
+        request
+    };
+
+    let request = request_with(
+        1,
+        &[
+            (options::HEADER_LANGUAGE, "EN"),
+            (options::HEADER_WORD_TIMESTAMPS, "true"),
+            (options::HEADER_TEMPERATURE, "0.4"),
+            (options::HEADER_PROMPT, "caf%C3%A9"),
+        ],
+    );
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[HEADER_LANGUAGE], "en");
+    let srt = SrtFile::parse_content(&body_text(response).await).unwrap();
+    let words: Vec<_> = srt
+        .segments()
+        .into_iter()
+        .map(|segment| (segment.start_ms, segment.end_ms, segment.text))
+        .collect();
+    assert_eq!(
+        words,
+        [
+            (0, 200, "mock".to_string()),
+            (200, 400, "speech".to_string()),
+            (400, 600, "1".to_string())
+        ]
+    );
+
+    for (request_id, header, rejected) in [
+        (2, (options::HEADER_TEMPERATURE, "1.5"), "temperature"),
+        (3, (options::HEADER_LANGUAGE, "de"), "language"),
+        (4, (options::HEADER_TASK, "translate"), "task"),
+        (5, (options::HEADER_TASK, "summarize"), options::HEADER_TASK),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(request_with(request_id, &[header]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(body_text(response).await.starts_with(rejected));
+    }
+}
+
+#[tokio::test]
+async fn test_https_requires_client_certificate_when_configured() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+    let fixture = |name: &str| {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/tls")
+            .join(name)
+    };
+    let acceptor = tls::acceptor(&TlsConfig {
+        cert_path: fixture("server.pem"),
+        key_path: fixture("server.key"),
+        client_ca_path: Some(fixture("ca.pem")),
+    })
+    .unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(tls::serve(
+        listener,
+        acceptor,
+        test_router(1),
+        CancellationToken::new(),
+    ));
+
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_file(fixture("ca.pem")).unwrap())
+        .unwrap();
+    let builder = ClientConfig::builder_with_provider(Arc::new(
+        tokio_rustls::rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots);
+    let with_cert = builder
+        .clone()
+        .with_client_auth_cert(
+            vec![CertificateDer::from_pem_file(fixture("client.pem")).unwrap()],
+            PrivateKeyDer::from_pem_file(fixture("client.key")).unwrap(),
+        )
+        .unwrap();
+
+    let healthz = |config: ClientConfig| async move {
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+        stream
+            .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        std::io::Result::Ok(response)
+    };
+
+    let response = healthz(with_cert).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    // TLS 1.3 reports the missing certificate after the client's handshake completes.
+    let rejected = healthz(builder.with_no_client_auth()).await;
+    assert!(
+        rejected
+            .as_ref()
+            .map_or(true, |response| response.is_empty()),
+        "{:?}",
+        rejected
+    );
+}
@@ -0,0 +1,238 @@
+//! Server settings, layered like the plugin's `Config::load`: built-in defaults, then
+//! the TOML config file, then `MPV_STT_SERVER_*` environment variables, then flags.
+
+use crate::cache::CacheConfig;
+use crate::tls::TlsConfig;
+use anyhow::{Context, Result, bail};
+use figment::{
+    Figment,
+    providers::{Env, Format, Serialized, Toml},
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const ENV_PREFIX: &str = "MPV_STT_SERVER_";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    pub bind: String,
+    pub model_path: String,
+    /// TOML file with `[[models]]` entries; replaces `model_path`.
+    pub models: Option<PathBuf>,
+    pub threads: u8,
+    pub language: String,
+    pub gpu_device: i32,
+    pub flash_attn: bool,
+    pub timeout_ms: u64,
+    /// Per model.
+    pub workers: usize,
+    /// Per model.
+    pub queue_capacity: usize,
+    pub warmup: bool,
+    /// Seconds in-flight jobs get to finish after Ctrl+C or SIGTERM.
+    pub shutdown_timeout_secs: u64,
+    pub max_temperature: f32,
+    pub max_prompt_chars: usize,
+    pub allow_word_timestamps: bool,
+    /// Size limit of the result cache; 0 disables it.
+    pub cache_size_mb: u64,
+    pub cache_ttl_secs: u64,
+    /// Keep cached results in this directory so they survive restarts.
+    pub cache_dir: Option<PathBuf>,
+    /// Keep batch jobs in this directory; batch jobs are disabled without one.
+    pub jobs_dir: Option<PathBuf>,
+    pub enable_encryption: bool,
+    pub encryption_key: String,
+    /// Read `encryption_key` from this file instead, so it stays out of `ps` and shell history.
+    pub encryption_key_file: Option<PathBuf>,
+    pub auth_secret: String,
+    /// Read `auth_secret` from this file instead.
+    pub auth_secret_file: Option<PathBuf>,
+    /// Secret for the `/admin` endpoints; they are disabled without one.
+    pub admin_secret: String,
+    pub admin_secret_file: Option<PathBuf>,
+    /// TOML file of named API keys with per-key quotas, re-read when it changes.
+    pub api_keys: Option<PathBuf>,
+    /// PEM certificate chain; the server speaks HTTPS when set.
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Require client certificates issued by this PEM CA (mTLS).
+    pub tls_client_ca: Option<PathBuf>,
+    #[cfg(feature = "stt_mock")]
+    pub mock_fixture: String,
+    #[cfg(feature = "stt_mock")]
+    pub mock_delay_ms: u64,
+    #[cfg(feature = "stt_mock")]
+    pub mock_fail_every: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0:9000".to_string(),
+            model_path: "ggml-base.bin".to_string(),
+            models: None,
+            threads: 8,
+            language: "auto".to_string(),
+            gpu_device: 0,
+            flash_attn: false,
+            timeout_ms: 120_000,
+            workers: 4,
+            queue_capacity: 64,
+            warmup: true,
+            shutdown_timeout_secs: 30,
+            max_temperature: 1.0,
+            max_prompt_chars: 1_000,
+            allow_word_timestamps: true,
+            cache_size_mb: 64,
+            cache_ttl_secs: 24 * 60 * 60,
+            cache_dir: None,
+            jobs_dir: None,
+            enable_encryption: false,
+            encryption_key: String::new(),
+            encryption_key_file: None,
+            auth_secret: String::new(),
+            auth_secret_file: None,
+            admin_secret: String::new(),
+            admin_secret_file: None,
+            api_keys: None,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            #[cfg(feature = "stt_mock")]
+            mock_fixture: String::new(),
+            #[cfg(feature = "stt_mock")]
+            mock_delay_ms: 0,
+            #[cfg(feature = "stt_mock")]
+            mock_fail_every: 0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn config_path_from_env() -> Option<PathBuf> {
+        std::env::var_os("MPV_STT_SERVER_CONFIG").map(PathBuf::from)
+    }
+
+    /// Merge every layer; `flags` holds only the options given on the command line.
+    pub fn load(config_path: Option<&Path>, flags: impl Serialize) -> Result<Self> {
+        let mut figment = Figment::from(Serialized::defaults(Settings::default()));
+        if let Some(path) = config_path {
+            figment = figment.merge(Toml::file_exact(path));
+        }
+        figment = figment
+            .merge(Env::prefixed(ENV_PREFIX).ignore(&["config"]))
+            .merge(Serialized::defaults(flags));
+
+        let mut settings: Settings = figment.extract().context("loading server settings")?;
+        settings.encryption_key = read_secret(
+            "encryption_key",
+            &settings.encryption_key,
+            settings.encryption_key_file.as_deref(),
+        )?;
+        settings.auth_secret = read_secret(
+            "auth_secret",
+            &settings.auth_secret,
+            settings.auth_secret_file.as_deref(),
+        )?;
+        settings.admin_secret = read_secret(
+            "admin_secret",
+            &settings.admin_secret,
+            settings.admin_secret_file.as_deref(),
+        )?;
+
+        if settings.workers == 0 {
+            bail!("workers must be at least 1");
+        }
+        if settings.queue_capacity == 0 {
+            bail!("queue_capacity must be at least 1");
+        }
+        if settings.enable_encryption && settings.encryption_key.is_empty() {
+            bail!("an encryption key is required when encryption is enabled");
+        }
+        if settings.tls_cert.is_some() != settings.tls_key.is_some() {
+            bail!("tls_cert and tls_key must be set together");
+        }
+        if settings.tls_client_ca.is_some() && settings.tls_cert.is_none() {
+            bail!("tls_client_ca requires tls_cert and tls_key");
+        }
+        Ok(settings)
+    }
+
+    pub fn cache_config(&self) -> Option<CacheConfig> {
+        (self.cache_size_mb > 0).then(|| CacheConfig {
+            max_bytes: self.cache_size_mb * 1024 * 1024,
+            ttl: Duration::from_secs(self.cache_ttl_secs),
+            dir: self.cache_dir.clone(),
+        })
+    }
+
+    pub fn tls_config(&self) -> Option<TlsConfig> {
+        Some(TlsConfig {
+            cert_path: self.tls_cert.clone()?,
+            key_path: self.tls_key.clone()?,
+            client_ca_path: self.tls_client_ca.clone(),
+        })
+    }
+}
+
+/// `value`, or the contents of `file` without the trailing newline editors add.
+fn read_secret(name: &str, value: &str, file: Option<&Path>) -> Result<String> {
+    let Some(file) = file else {
+        return Ok(value.to_string());
+    };
+    if !value.is_empty() {
+        bail!("set either {} or {}_file, not both", name, name);
+    }
+    let secret = std::fs::read_to_string(file)
+        .with_context(|| format!("reading {} from {}", name, file.display()))?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn write_file(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, contents.as_bytes()).unwrap();
+        file
+    }
+
+    fn no_flags() -> HashMap<&'static str, String> {
+        HashMap::new()
+    }
+
+    #[test]
+    fn test_flags_override_config_file() {
+        let config = write_file("bind = \"127.0.0.1:7000\"\nworkers = 2\nwarmup = false\n");
+        let flags = HashMap::from([("workers", 6)]);
+
+        let settings = Settings::load(Some(config.path()), flags).unwrap();
+        assert_eq!(settings.bind, "127.0.0.1:7000");
+        assert_eq!(settings.workers, 6);
+        assert!(!settings.warmup);
+        assert_eq!(settings.queue_capacity, 64);
+
+        let no_workers = HashMap::from([("workers", 0)]);
+        assert!(Settings::load(Some(config.path()), no_workers).is_err());
+    }
+
+    #[test]
+    fn test_secrets_are_read_from_files() {
+        let secret = write_file("s3cret\n");
+        let config = write_file(&format!(
+            "auth_secret_file = {:?}\n",
+            secret.path().display().to_string()
+        ));
+
+        let settings = Settings::load(Some(config.path()), no_flags()).unwrap();
+        assert_eq!(settings.auth_secret, "s3cret");
+
+        let both = HashMap::from([("auth_secret", "inline")]);
+        assert!(Settings::load(Some(config.path()), both).is_err());
+        assert!(Settings::load(Some(Path::new("/nonexistent/server.toml")), no_flags()).is_err());
+    }
+}
@@ -0,0 +1,445 @@
+//! `POST /transcribe` and `DELETE /transcribe/{request_id}`: the plugin's own endpoint.
+//!
+//! The body is one chunk of audio in the format named by `x-compression`, optionally
+//! encrypted. The reply is an SRT body, a [`TranscriptionResponse`] when JSON is accepted,
+//! or an NDJSON stream of [`StreamEvent`]s.
+
+use crate::audio::{decode_media, decompress_ogg_opus, decompress_opus, validate_pcm};
+use crate::cache::CacheSlot;
+use crate::keys::Caller;
+use crate::server::{
+    AppState, Billing, HEADER_MODEL, JobFailure, MAX_BODY_SIZE, Submitted, accept,
+    cache_hit_metrics, client_request_id, insert_metric_headers, record_failure, request_deadline,
+    request_priority, response_with_status, run_job, submit, wait_for_result,
+};
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{ACCEPT, CONTENT_TYPE},
+    },
+    response::Response,
+};
+use bytes::Bytes;
+use log::{debug, info, warn};
+use mpv_stt_common::{SegmentSink, Transcript, TranscriptSegment};
+use mpv_stt_protocol::{
+    JSON_CONTENT_TYPE, NDJSON_CONTENT_TYPE, StreamEvent, TranscriptionJob, TranscriptionResponse,
+};
+use mpv_stt_srt::SrtFile;
+use std::convert::Infallible;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+const COMPRESSION_PCM: &str = "pcm";
+pub(crate) const COMPRESSION_WAV: &str = "wav";
+const COMPRESSION_OPUS: &str = "opus";
+/// Standard Ogg Opus file (RFC 7845), as written by `opusenc` or `ffmpeg -c:a libopus`.
+pub(crate) const COMPRESSION_OGG_OPUS: &str = "ogg-opus";
+/// Any container and codec ffmpeg can read; `pcm` and `wav` payloads are decoded the same way.
+pub(crate) const COMPRESSION_AUTO: &str = "auto";
+const HEADER_BYTES_IN: &str = "x-bytes-in";
+const HEADER_BYTES_OUT: &str = "x-bytes-out";
+pub(crate) const HEADER_LANGUAGE: &str = "x-language";
+/// `hit` or `miss`; only sent when the result cache is enabled.
+pub(crate) const HEADER_CACHE: &str = "x-metric-cache";
+// Stream lines buffered ahead of a slow client.
+const STREAM_BUFFER: usize = 16;
+
+pub(crate) async fn handle_transcribe(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let response = transcribe(&state, &headers, body).await;
+    record_failure(&state, &response);
+    response
+}
+
+pub(crate) async fn handle_cancel(
+    State(state): State<AppState>,
+    Path(request_id): Path<u64>,
+    headers: HeaderMap,
+) -> Response {
+    // The admin may cancel any request; everyone else only those made with their own key
+    // or, on a server without keys, their own `x-cancel-token`.
+    let cancelled = if state.authorize_admin(&headers).is_ok() {
+        state.models().cancel_request(request_id)
+    } else {
+        let caller = state.authenticate(&headers).and_then(|caller| {
+            if caller.is_identified() {
+                Ok(caller)
+            } else {
+                Err(JobFailure::new(
+                    StatusCode::UNAUTHORIZED,
+                    "unauthorized",
+                    "cancelling needs an API key or the request's x-cancel-token",
+                ))
+            }
+        });
+        match caller {
+            Ok(caller) => state.models().cancel_request_of(request_id, &caller),
+            Err(failure) => {
+                let response = failure.into_response();
+                record_failure(&state, &response);
+                return response;
+            }
+        }
+    };
+    if cancelled {
+        info!("Cancelled request {}", request_id);
+        response_with_status(StatusCode::OK, b"cancelled")
+    } else {
+        response_with_status(StatusCode::NOT_FOUND, b"unknown request")
+    }
+}
+
+fn cache_status(hit: bool) -> HeaderValue {
+    HeaderValue::from_static(if hit { "hit" } else { "miss" })
+}
+
+async fn transcribe(state: &AppState, headers: &HeaderMap, body: Bytes) -> Response {
+    if body.len() > MAX_BODY_SIZE {
+        return response_with_status(StatusCode::PAYLOAD_TOO_LARGE, b"body too large");
+    }
+
+    let request_id = match client_request_id(headers) {
+        Ok(Some(id)) => id,
+        Ok(None) => return response_with_status(StatusCode::BAD_REQUEST, b"missing x-request-id"),
+        Err(msg) => return response_with_status(StatusCode::BAD_REQUEST, msg.as_bytes()),
+    };
+
+    let duration_ms = headers
+        .get("x-duration-ms")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let priority = match request_priority(headers) {
+        Ok(priority) => priority,
+        Err(msg) => return response_with_status(StatusCode::BAD_REQUEST, msg.as_bytes()),
+    };
+
+    let deadline = match request_deadline(headers) {
+        Ok(deadline) => deadline,
+        Err(msg) => return response_with_status(StatusCode::BAD_REQUEST, msg.as_bytes()),
+    };
+
+    let caller = match state.admit(headers) {
+        Ok(caller) => caller,
+        Err(failure) => return failure.into_response(),
+    };
+
+    let (model, options) = match state.request_target(headers) {
+        Ok(target) => target,
+        Err(failure) => return failure.into_response(),
+    };
+
+    let encrypted = headers
+        .get("x-encrypted")
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s == "1")
+        .unwrap_or(false);
+
+    let streaming = accepts(headers, NDJSON_CONTENT_TYPE);
+    if streaming && encrypted {
+        return response_with_status(
+            StatusCode::BAD_REQUEST,
+            b"streamed responses cannot be encrypted",
+        );
+    }
+
+    let compression = headers
+        .get("x-compression")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or(COMPRESSION_PCM);
+
+    let mut audio_bytes = body.to_vec();
+    if encrypted {
+        if let Some(key) = state.encryption_key() {
+            match key.decrypt(&audio_bytes) {
+                Ok(decrypted) => audio_bytes = decrypted,
+                Err(e) => {
+                    return response_with_status(
+                        StatusCode::BAD_REQUEST,
+                        format!("decrypt failed: {}", e).as_bytes(),
+                    );
+                }
+            }
+        } else {
+            return response_with_status(StatusCode::BAD_REQUEST, b"encryption not enabled");
+        }
+    }
+
+    if audio_bytes.is_empty() {
+        return response_with_status(StatusCode::BAD_REQUEST, b"empty audio data");
+    }
+
+    let decoded = match compression {
+        COMPRESSION_PCM | COMPRESSION_WAV | COMPRESSION_AUTO => decode_media(audio_bytes).await,
+        COMPRESSION_OPUS => match decompress_opus(&audio_bytes) {
+            Ok(pcm) => Ok(pcm),
+            Err(e) => {
+                // Backward compatibility: some clients mislabeled WAV as OPUS.
+                if audio_bytes.starts_with(b"RIFF") {
+                    warn!("compression=opus but payload looks like WAV; bypassing opus decode");
+                    decode_media(audio_bytes).await
+                } else {
+                    return response_with_status(StatusCode::BAD_REQUEST, e.to_string().as_bytes());
+                }
+            }
+        },
+        COMPRESSION_OGG_OPUS => decompress_ogg_opus(&audio_bytes).map_err(|e| e.to_string()),
+        _ => return response_with_status(StatusCode::BAD_REQUEST, b"unsupported compression"),
+    };
+
+    let audio = match decoded.and_then(validate_pcm) {
+        Ok(pcm) => pcm,
+        Err(msg) => return response_with_status(StatusCode::BAD_REQUEST, msg.as_bytes()),
+    };
+
+    let job = TranscriptionJob {
+        request_id,
+        model: model.clone(),
+        audio,
+        duration_ms,
+        options,
+        priority,
+        on_segment: None,
+        enqueue_at: Instant::now(),
+        deadline,
+    };
+    if streaming {
+        return stream_transcription(state, &caller, job, body.len()).await;
+    }
+
+    let (transcript, metrics) = match run_job(state, &caller, job, Billing::Charged).await {
+        Ok(done) => done,
+        Err(failure) => return failure.into_response(),
+    };
+
+    let json = accepts(headers, JSON_CONTENT_TYPE);
+    let mut resp_body = if json {
+        serde_json::to_vec(&TranscriptionResponse {
+            segments: transcript.segments.clone(),
+            language: transcript.language.clone(),
+            duration_ms: transcript.duration_ms,
+            metrics,
+        })
+        .unwrap_or_default()
+    } else {
+        render_srt(&transcript)
+    };
+    if encrypted {
+        if let Some(key) = state.encryption_key() {
+            match key.encrypt(&resp_body) {
+                Ok(enc) => resp_body = enc,
+                Err(e) => {
+                    return response_with_status(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        e.to_string().as_bytes(),
+                    );
+                }
+            }
+        }
+    }
+
+    let resp_body_len = resp_body.len();
+    state.metrics.record_bytes(body.len(), resp_body_len);
+    state.metrics.record_job(&metrics, transcript.duration_ms);
+    let mut response = Response::new(resp_body.into());
+    *response.status_mut() = StatusCode::OK;
+    let headers = response.headers_mut();
+    if json {
+        let _ = headers.insert(CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+    }
+    insert_metric_headers(headers, &metrics);
+    let _ = headers.insert(
+        HEADER_BYTES_IN,
+        HeaderValue::from_str(&body.len().to_string())
+            .unwrap_or_else(|_| HeaderValue::from_static("0")),
+    );
+    let _ = headers.insert(
+        HEADER_BYTES_OUT,
+        HeaderValue::from_str(&resp_body_len.to_string())
+            .unwrap_or_else(|_| HeaderValue::from_static("0")),
+    );
+    if let Some(language) = transcript
+        .language
+        .as_deref()
+        .and_then(|lang| HeaderValue::from_str(lang).ok())
+    {
+        let _ = headers.insert(HEADER_LANGUAGE, language);
+    }
+    if let Ok(model) = HeaderValue::from_str(&model) {
+        let _ = headers.insert(HEADER_MODEL, model);
+    }
+    if state.caches_results() {
+        let _ = headers.insert(HEADER_CACHE, cache_status(metrics.cache_hit));
+    }
+
+    response
+}
+
+/// Whether the `Accept` header lists `content_type`.
+fn accepts(headers: &HeaderMap, content_type: &str) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|accept| {
+            accept
+                .split(',')
+                .any(|media| media.trim().starts_with(content_type))
+        })
+}
+
+/// Streamed `/transcribe` reply: one [`StreamEvent`] line per segment, then a summary.
+async fn stream_transcription(
+    state: &AppState,
+    caller: &Caller,
+    mut job: TranscriptionJob,
+    bytes_in: usize,
+) -> Response {
+    let model = match accept(state, caller, &job, Billing::Charged) {
+        Ok(model) => model,
+        Err(failure) => return failure.into_response(),
+    };
+    let cache_slot = state.cache_slot(&model, &job);
+    if let Some(transcript) = state.cached_result(cache_slot.as_ref()).await {
+        return cached_stream(state, transcript, bytes_in);
+    }
+
+    let (segment_tx, segment_rx) = mpsc::unbounded_channel();
+    job.on_segment = Some(SegmentSink::new(move |segment| {
+        let _ = segment_tx.send(segment.clone());
+    }));
+    let submitted = match submit(state, &model, caller, job, Billing::Charged) {
+        Ok(submitted) => submitted,
+        Err(failure) => return failure.into_response(),
+    };
+
+    let (line_tx, line_rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(forward_stream(
+        state.clone(),
+        submitted,
+        segment_rx,
+        line_tx,
+        bytes_in,
+        cache_slot,
+    ));
+    let lines = futures::stream::unfold(line_rx, |mut rx| async move {
+        rx.recv().await.map(|line| (Ok::<_, Infallible>(line), rx))
+    });
+
+    let mut response = Response::new(Body::from_stream(lines));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(NDJSON_CONTENT_TYPE));
+    if state.caches_results() {
+        response
+            .headers_mut()
+            .insert(HEADER_CACHE, cache_status(false));
+    }
+    response
+}
+
+/// Streamed reply for a cached transcript, written in one go.
+fn cached_stream(state: &AppState, transcript: Transcript, bytes_in: usize) -> Response {
+    let metrics = cache_hit_metrics();
+    state.metrics.record_job(&metrics, transcript.duration_ms);
+    let mut body = Vec::new();
+    for segment in transcript.segments {
+        body.extend_from_slice(&encode_event(&StreamEvent::Segment(segment)));
+    }
+    body.extend_from_slice(&encode_event(&StreamEvent::Done {
+        language: transcript.language,
+        duration_ms: transcript.duration_ms,
+        metrics,
+    }));
+    state.metrics.record_bytes(bytes_in, body.len());
+
+    let mut response = Response::new(Body::from(body));
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(NDJSON_CONTENT_TYPE));
+    headers.insert(HEADER_CACHE, cache_status(true));
+    response
+}
+
+/// Write segments as they arrive, then the job's summary or error.
+async fn forward_stream(
+    state: AppState,
+    submitted: Submitted,
+    mut segments: mpsc::UnboundedReceiver<TranscriptSegment>,
+    lines: mpsc::Sender<Bytes>,
+    bytes_in: usize,
+    cache_slot: Option<CacheSlot>,
+) {
+    let mut bytes_out = 0;
+    let request_id = submitted.request_id;
+    let result = wait_for_result(&state, submitted);
+    tokio::pin!(result);
+    let outcome = loop {
+        tokio::select! {
+            outcome = &mut result => break outcome,
+            Some(segment) = segments.recv() => {
+                let event = StreamEvent::Segment(segment);
+                if !send_event(&lines, &event, &mut bytes_out).await {
+                    // Dropping `result` cancels the job.
+                    debug!("Client of request {} disconnected; cancelling", request_id);
+                    return;
+                }
+            }
+        }
+    };
+    // Segments emitted just before the result may still be buffered.
+    while let Ok(segment) = segments.try_recv() {
+        send_event(&lines, &StreamEvent::Segment(segment), &mut bytes_out).await;
+    }
+
+    let summary = match outcome {
+        Ok((transcript, metrics)) => {
+            state.metrics.record_job(&metrics, transcript.duration_ms);
+            state.cache_result(cache_slot, &transcript).await;
+            StreamEvent::Done {
+                language: transcript.language,
+                duration_ms: transcript.duration_ms,
+                metrics,
+            }
+        }
+        Err(failure) => {
+            state.metrics.record_error(failure.kind);
+            StreamEvent::Error {
+                code: failure.kind.to_string(),
+                message: failure.message,
+            }
+        }
+    };
+    send_event(&lines, &summary, &mut bytes_out).await;
+    state.metrics.record_bytes(bytes_in, bytes_out);
+}
+
+/// Queue one NDJSON line; `false` once the client has gone away.
+async fn send_event(
+    lines: &mpsc::Sender<Bytes>,
+    event: &StreamEvent,
+    bytes_out: &mut usize,
+) -> bool {
+    let line = encode_event(event);
+    *bytes_out += line.len();
+    lines.send(line).await.is_ok()
+}
+
+fn encode_event(event: &StreamEvent) -> Bytes {
+    let mut line = serde_json::to_vec(event).expect("stream events serialize to JSON");
+    line.push(b'\n');
+    Bytes::from(line)
+}
+
+/// SRT body for the legacy `/transcribe` response; empty when nothing was recognised.
+fn render_srt(transcript: &Transcript) -> Vec<u8> {
+    if transcript.is_empty() {
+        return Vec::new();
+    }
+    SrtFile::from(transcript).to_string().into_bytes()
+}
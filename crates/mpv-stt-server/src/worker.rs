@@ -1,92 +1,452 @@
+use crate::keys::Caller;
 use anyhow::Result;
-use log::{debug, error, info, warn};
-use mpv_stt_plugin::{LocalModelConfig, SttBackend, SttRunner};
-use mpv_stt_protocol::{JobMetrics, JobResult, TranscriptionJob};
-use std::collections::HashSet;
-use std::io::Write;
+use log::{debug, info, warn};
+use mpv_stt_common::{MpvSttError, Transcript};
+use mpv_stt_plugin::{SttBackend, SttCancelHandle, SttRunner, SttRunnerConfig};
+use mpv_stt_protocol::{JobMetrics, JobPriority, JobResult, TranscriptionJob};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use tempfile::NamedTempFile;
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, oneshot};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 pub struct WorkerPool {
-    job_tx: mpsc::UnboundedSender<TranscriptionJob>,
-    result_rx: mpsc::UnboundedReceiver<JobResult>,
-    cancelled: Arc<Mutex<HashSet<u64>>>,
+    queue: Arc<JobQueue>,
+    pending: PendingResults,
+    inflight: Arc<Mutex<Inflight>>,
+    stats: Arc<QueueStats>,
+    /// Tells idle workers to exit.
+    stop: CancellationToken,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+    num_workers: usize,
+    queue_capacity: usize,
 }
 
-impl WorkerPool {
-    pub fn new(config: LocalModelConfig, num_workers: usize) -> Self {
-        let (job_tx, job_rx) = mpsc::unbounded_channel();
-        let (result_tx, result_rx) = mpsc::unbounded_channel();
-        let cancelled = Arc::new(Mutex::new(HashSet::new()));
+/// Why a job could not be queued.
+#[derive(Debug)]
+pub enum SubmitError {
+    /// Another request with the same id is still waiting for its result.
+    DuplicateRequest(u64),
+    /// The queue is at capacity; `retry_after` estimates when a slot frees up.
+    QueueFull { depth: usize, retry_after: Duration },
+    /// All workers have stopped.
+    Closed,
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmitError::DuplicateRequest(id) => write!(f, "request {} already in flight", id),
+            SubmitError::QueueFull { depth, .. } => {
+                write!(f, "job queue full ({} waiting)", depth)
+            }
+            SubmitError::Closed => write!(f, "worker pool closed"),
+        }
+    }
+}
+
+impl std::error::Error for SubmitError {}
+
+/// Cancellation bookkeeping shared by the pool and its workers.
+#[derive(Default)]
+struct Inflight {
+    /// Jobs whose result must be reported as cancelled, or that their worker must skip
+    /// when they were cancelled between leaving the queue and starting to run.
+    cancelled: HashSet<u64>,
+    /// Jobs being transcribed, with their class and the handle that aborts their worker's
+    /// backend.
+    running: HashMap<u64, (JobPriority, SttCancelHandle)>,
+    /// Running jobs aborted to make room for a realtime job; they go back to the queue.
+    preempted: HashSet<u64>,
+    /// Running jobs aborted because their deadline passed.
+    expired: HashSet<u64>,
+}
+
+/// Jobs waiting for a worker, one FIFO per priority class.
+#[derive(Default)]
+struct JobQueue {
+    classes: Mutex<[VecDeque<TranscriptionJob>; JobPriority::ALL.len()]>,
+    /// Signalled once per job pushed.
+    available: Notify,
+}
+
+impl JobQueue {
+    fn class(priority: JobPriority) -> usize {
+        JobPriority::ALL
+            .iter()
+            .position(|class| *class == priority)
+            .expect("every priority has a class")
+    }
+
+    fn len(&self) -> usize {
+        let classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+        classes.iter().map(VecDeque::len).sum()
+    }
+
+    /// Queue `job` behind others of its class, unless `capacity` jobs are already waiting.
+    fn push(
+        &self,
+        job: TranscriptionJob,
+        capacity: usize,
+    ) -> std::result::Result<(), TranscriptionJob> {
+        let mut classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+        if classes.iter().map(VecDeque::len).sum::<usize>() >= capacity {
+            return Err(job);
+        }
+        classes[Self::class(job.priority)].push_back(job);
+        drop(classes);
+        self.available.notify_one();
+        Ok(())
+    }
 
-        let job_rx = Arc::new(tokio::sync::Mutex::new(job_rx));
+    /// Put a preempted job back at the head of its class; it was already admitted.
+    fn requeue(&self, job: TranscriptionJob) {
+        let mut classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+        classes[Self::class(job.priority)].push_front(job);
+        drop(classes);
+        self.available.notify_one();
+    }
 
-        for id in 0..num_workers {
-            let job_rx = Arc::clone(&job_rx);
-            let result_tx = result_tx.clone();
-            let config = config.clone();
-            let cancelled = Arc::clone(&cancelled);
+    /// Take `request_id` out of the queue if it is still waiting.
+    fn remove(&self, request_id: u64) -> Option<TranscriptionJob> {
+        let mut classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+        classes.iter_mut().find_map(|class| {
+            let index = class.iter().position(|job| job.request_id == request_id)?;
+            class.remove(index)
+        })
+    }
 
-            tokio::task::spawn_blocking(move || {
-                worker_thread(id, config, job_rx, result_tx, cancelled);
+    /// The oldest job of the highest class waiting, once there is one.
+    async fn pop(&self) -> TranscriptionJob {
+        loop {
+            let notified = self.available.notified();
+            let job = {
+                let mut classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+                classes.iter_mut().find_map(VecDeque::pop_front)
+            };
+            if let Some(job) = job {
+                return job;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Worker activity and a running average of inference time, used to size `Retry-After`.
+#[derive(Default)]
+struct QueueStats {
+    busy: AtomicUsize,
+    avg_inference_ms: AtomicU64,
+}
+
+impl QueueStats {
+    fn record_inference(&self, inference_ms: u64) {
+        // Exponential moving average weighted 1/8 towards the newest sample.
+        let _ = self
+            .avg_inference_ms
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |avg| {
+                Some(if avg == 0 {
+                    inference_ms.max(1)
+                } else {
+                    (avg * 7 + inference_ms) / 8
+                })
             });
+    }
+}
+
+/// Where a submitted job's result goes, and who may cancel it.
+struct Waiter {
+    tx: oneshot::Sender<JobResult>,
+    owner: Caller,
+}
+
+/// One-shot result channels keyed by request id, registered when a job is submitted.
+#[derive(Clone, Default)]
+struct PendingResults(Arc<Mutex<HashMap<u64, Waiter>>>);
+
+impl PendingResults {
+    fn register(&self, request_id: u64, owner: Caller) -> Option<oneshot::Receiver<JobResult>> {
+        let mut pending = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.contains_key(&request_id) {
+            return None;
+        }
+        let (tx, rx) = oneshot::channel();
+        pending.insert(request_id, Waiter { tx, owner });
+        Some(rx)
+    }
+
+    fn is_owned_by(&self, request_id: u64, caller: &Caller) -> bool {
+        let pending = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        pending
+            .get(&request_id)
+            .is_some_and(|waiter| waiter.owner == *caller)
+    }
+
+    fn ids(&self) -> Vec<u64> {
+        let pending = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        pending.keys().copied().collect()
+    }
+
+    fn remove(&self, request_id: u64) -> Option<oneshot::Sender<JobResult>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&request_id)
+            .map(|waiter| waiter.tx)
+    }
+
+    fn deliver(&self, result: JobResult) {
+        let request_id = result.request_id();
+        match self.remove(request_id) {
+            Some(tx) => {
+                if tx.send(result).is_err() {
+                    debug!(
+                        "Request {} was abandoned before its result arrived",
+                        request_id
+                    );
+                }
+            }
+            None => debug!("No waiter registered for request {}", request_id),
         }
+    }
+}
+
+impl WorkerPool {
+    pub fn new(config: SttRunnerConfig, num_workers: usize, queue_capacity: usize) -> Self {
+        let queue_capacity = queue_capacity.max(1);
+        let queue = Arc::new(JobQueue::default());
+        let pending = PendingResults::default();
+        let inflight = Arc::new(Mutex::new(Inflight::default()));
+        let stats = Arc::new(QueueStats::default());
+        let stop = CancellationToken::new();
+
+        let workers = (0..num_workers)
+            .map(|id| {
+                let queue = Arc::clone(&queue);
+                let pending = pending.clone();
+                let config = config.clone();
+                let inflight = Arc::clone(&inflight);
+                let stats = Arc::clone(&stats);
+                let stop = stop.clone();
+
+                tokio::task::spawn_blocking(move || {
+                    worker_thread(id, config, queue, pending, inflight, stats, stop);
+                })
+            })
+            .collect();
 
         Self {
-            job_tx,
-            result_rx,
-            cancelled,
+            queue,
+            pending,
+            inflight,
+            stats,
+            stop,
+            workers: Mutex::new(workers),
+            num_workers: num_workers.max(1),
+            queue_capacity,
         }
     }
 
-    #[allow(dead_code)]
-    pub fn submit_job(&self, job: TranscriptionJob) -> Result<()> {
-        self.job_tx.send(job)?;
-        Ok(())
+    pub fn num_workers(&self) -> usize {
+        self.num_workers
+    }
+
+    pub fn queue_capacity(&self) -> usize {
+        self.queue_capacity
+    }
+
+    /// Workers currently running a job.
+    pub fn busy_workers(&self) -> usize {
+        self.stats.busy.load(Ordering::Relaxed)
+    }
+
+    /// True once every worker has exited.
+    pub fn is_closed(&self) -> bool {
+        // Each worker holds a reference to the queue until it exits.
+        Arc::strong_count(&self.queue) == 1
     }
 
-    #[allow(dead_code)]
-    pub fn cancel_request(&self, request_id: u64) {
-        if let Ok(mut set) = self.cancelled.lock() {
-            set.insert(request_id);
+    /// Jobs accepted but not yet picked up by a worker.
+    pub fn queue_depth(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Rough time until the current queue drains, never less than one second.
+    pub fn retry_after(&self) -> Duration {
+        const FALLBACK_INFERENCE_MS: u64 = 1_000;
+
+        let avg_ms = match self.stats.avg_inference_ms.load(Ordering::Relaxed) {
+            0 => FALLBACK_INFERENCE_MS,
+            avg => avg,
+        };
+        let drain_ms =
+            (self.queue_depth().max(1) as u64).saturating_mul(avg_ms) / self.num_workers as u64;
+        Duration::from_secs(drain_ms.div_ceil(1_000).max(1))
+    }
+
+    /// Queue `job` for `owner` and return the channel its result will be delivered on.
+    ///
+    /// A realtime job that finds every worker busy preempts a running prefetch job, which
+    /// goes back to the head of the prefetch queue.
+    pub fn submit_job(
+        &self,
+        job: TranscriptionJob,
+        owner: Caller,
+    ) -> std::result::Result<oneshot::Receiver<JobResult>, SubmitError> {
+        if self.stop.is_cancelled() || self.is_closed() {
+            return Err(SubmitError::Closed);
+        }
+        let request_id = job.request_id;
+        let priority = job.priority;
+        let rx = self
+            .pending
+            .register(request_id, owner)
+            .ok_or(SubmitError::DuplicateRequest(request_id))?;
+        if self.queue.push(job, self.queue_capacity).is_err() {
+            self.pending.remove(request_id);
+            return Err(SubmitError::QueueFull {
+                depth: self.queue_depth(),
+                retry_after: self.retry_after(),
+            });
+        }
+        if priority == JobPriority::Realtime && self.busy_workers() >= self.num_workers {
+            self.preempt_one();
+        }
+        Ok(rx)
+    }
+
+    /// Abort one running preemptible job that is not already being preempted.
+    fn preempt_one(&self) {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        let victim = inflight
+            .running
+            .iter()
+            .filter(|(request_id, (priority, _))| {
+                priority.is_preemptible() && !inflight.preempted.contains(request_id)
+            })
+            .map(|(request_id, (_, handle))| (*request_id, handle.clone()))
+            .next();
+        if let Some((request_id, handle)) = victim {
+            debug!("Preempting request {} for a realtime job", request_id);
+            inflight.preempted.insert(request_id);
+            handle.cancel();
+        }
+    }
+
+    /// Cancel `request_id`: a queued job is removed from the queue, a running one is
+    /// aborted.
+    ///
+    /// The waiter receives [`JobResult::Cancelled`]. Returns false if the request is
+    /// unknown or already finished.
+    pub fn cancel_request(&self, request_id: u64) -> bool {
+        self.cancel(request_id, None)
+    }
+
+    /// Like [`Self::cancel_request`], but only for a job submitted by `caller`; other
+    /// callers' jobs are reported as unknown.
+    pub fn cancel_request_of(&self, request_id: u64, caller: &Caller) -> bool {
+        self.cancel(request_id, Some(caller))
+    }
+
+    fn cancel(&self, request_id: u64, caller: Option<&Caller>) -> bool {
+        // Workers deliver results under this lock, so the owner checked below is still the
+        // owner of the job that gets cancelled.
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        if caller.is_some_and(|caller| !self.pending.is_owned_by(request_id, caller)) {
+            return false;
+        }
+        if let Some((_, handle)) = inflight.running.get(&request_id) {
+            handle.cancel();
+            inflight.cancelled.insert(request_id);
+            return true;
+        }
+        let Some(tx) = self.pending.remove(request_id) else {
+            return false;
+        };
+        // Only a job already taken by a worker, which checks this set before running it,
+        // can be missing from the queue here.
+        if self.queue.remove(request_id).is_none() {
+            inflight.cancelled.insert(request_id);
         }
+        let _ = tx.send(JobResult::Cancelled { request_id });
+        true
     }
 
-    /// Cloneable sender for submitting jobs from external tasks.
-    pub fn job_sender(&self) -> mpsc::UnboundedSender<TranscriptionJob> {
-        self.job_tx.clone()
+    /// Give up on `request_id` because its deadline passed: a queued job is dropped, a
+    /// running one is aborted.
+    ///
+    /// The waiter receives [`JobResult::Expired`]. Returns false if the request is unknown
+    /// or already finished.
+    pub fn expire_request(&self, request_id: u64) -> bool {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, handle)) = inflight.running.get(&request_id) {
+            handle.cancel();
+            inflight.expired.insert(request_id);
+            return true;
+        }
+        match self.queue.remove(request_id) {
+            Some(job) => {
+                self.pending.deliver(expired(&job, Instant::now(), None));
+                true
+            }
+            None => false,
+        }
     }
 
-    pub async fn next_result(&mut self) -> Option<JobResult> {
-        self.result_rx.recv().await
+    /// Cancel every queued and running job; returns how many there were.
+    pub fn cancel_all(&self) -> usize {
+        self.pending
+            .ids()
+            .into_iter()
+            .filter(|request_id| self.cancel_request(*request_id))
+            .count()
+    }
+
+    /// Stop the workers once they are idle and wait for them to exit.
+    ///
+    /// Jobs still queued are left unprocessed; drain or cancel them first.
+    pub async fn shutdown(&self) {
+        self.stop.cancel();
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap_or_else(|e| e.into_inner()));
+        for worker in workers {
+            if let Err(e) = worker.await {
+                warn!("Worker exited abnormally: {}", e);
+            }
+        }
     }
 }
 
 fn worker_thread(
     worker_id: usize,
-    config: LocalModelConfig,
-    job_rx: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<TranscriptionJob>>>,
-    result_tx: mpsc::UnboundedSender<JobResult>,
-    cancelled: Arc<Mutex<HashSet<u64>>>,
+    config: SttRunnerConfig,
+    queue: Arc<JobQueue>,
+    pending: PendingResults,
+    inflight: Arc<Mutex<Inflight>>,
+    stats: Arc<QueueStats>,
+    stop: CancellationToken,
 ) {
     info!("Worker {} started", worker_id);
 
     let mut runner = SttRunner::new(config);
+    let cancel_handle = runner.cancel_handle();
 
     loop {
         let worker_start = Instant::now();
         let job = {
             let rt = tokio::runtime::Handle::current();
             rt.block_on(async {
-                let mut rx = job_rx.lock().await;
-                rx.recv().await
+                tokio::select! {
+                    job = queue.pop() => Some(job),
+                    _ = stop.cancelled() => None,
+                }
             })
         };
 
         let Some(job) = job else {
-            debug!("Worker {} shutting down (channel closed)", worker_id);
+            debug!("Worker {} shutting down", worker_id);
             break;
         };
 
@@ -97,23 +457,80 @@ fn worker_thread(
             .unwrap_or(u64::MAX);
 
         debug!(
-            "Worker {} processing request {} ({} bytes)",
+            "Worker {} processing {} request {} ({} samples)",
             worker_id,
+            job.priority.as_str(),
             job.request_id,
-            job.audio_data.len()
+            job.audio.samples.len()
         );
 
-        // Drop job early if it was cancelled after submission but before processing
-        if is_cancelled(job.request_id, &cancelled) {
+        {
+            let mut tracked = inflight.lock().unwrap_or_else(|e| e.into_inner());
+            // Drop a job cancelled after it left the queue but before it started running.
+            if tracked.cancelled.remove(&job.request_id) {
+                debug!(
+                    "Worker {} skipping cancelled request {}",
+                    worker_id, job.request_id
+                );
+                continue;
+            }
+            if job
+                .deadline
+                .is_some_and(|deadline| deadline <= worker_start)
+            {
+                debug!(
+                    "Worker {} dropping request {} past its deadline",
+                    worker_id, job.request_id
+                );
+                pending.deliver(expired(&job, worker_start, None));
+                continue;
+            }
+            tracked
+                .running
+                .insert(job.request_id, (job.priority, cancel_handle.clone()));
+        }
+
+        stats.busy.fetch_add(1, Ordering::Relaxed);
+        let outcome = process_job(&mut runner, &job);
+        stats.busy.fetch_sub(1, Ordering::Relaxed);
+
+        // Hold the lock until the result is delivered so a concurrent cancel either aborts
+        // this job or finds it finished, never a half-removed state.
+        let mut tracked = inflight.lock().unwrap_or_else(|e| e.into_inner());
+        tracked.running.remove(&job.request_id);
+        let cancelled = tracked.cancelled.remove(&job.request_id);
+        let preempted = tracked.preempted.remove(&job.request_id);
+        let expired_while_running = tracked.expired.remove(&job.request_id);
+
+        let aborted = outcome
+            .as_ref()
+            .is_err_and(|e| matches!(e.downcast_ref(), Some(MpvSttError::SttCancelled)));
+        if preempted && aborted && !cancelled && !expired_while_running {
             debug!(
-                "Worker {} skipping cancelled request {}",
+                "Worker {} requeueing preempted request {}",
                 worker_id, job.request_id
             );
+            drop(tracked);
+            queue.requeue(job);
             continue;
         }
 
-        let result = match process_job(&mut runner, &job) {
-            Ok((srt_data, inference_ms)) => {
+        let result = match outcome {
+            _ if cancelled => {
+                debug!("Worker {} cancelled request {}", worker_id, job.request_id);
+                JobResult::Cancelled {
+                    request_id: job.request_id,
+                }
+            }
+            _ if expired_while_running => {
+                debug!(
+                    "Worker {} aborted request {} past its deadline",
+                    worker_id, job.request_id
+                );
+                expired(&job, worker_start, Some(worker_start.elapsed()))
+            }
+            Ok((transcript, inference_ms)) => {
+                stats.record_inference(inference_ms);
                 let worker_total_ms = worker_start
                     .elapsed()
                     .as_millis()
@@ -125,76 +542,72 @@ fn worker_thread(
                 );
                 JobResult::Success {
                     request_id: job.request_id,
-                    srt_data,
+                    transcript,
                     metrics: JobMetrics {
                         queue_wait_ms,
                         inference_ms,
                         worker_total_ms,
+                        cache_hit: false,
                     },
                 }
             }
+            Err(e) if matches!(e.downcast_ref(), Some(MpvSttError::SttCancelled)) => {
+                JobResult::Cancelled {
+                    request_id: job.request_id,
+                }
+            }
             Err(e) => JobResult::Error {
                 request_id: job.request_id,
                 message: e.to_string(),
             },
         };
 
-        if result_tx.send(result).is_err() {
-            error!("Worker {}: result channel closed", worker_id);
-            break;
-        }
+        pending.deliver(result);
     }
 
     info!("Worker {} stopped", worker_id);
 }
 
-fn is_cancelled(request_id: u64, cancelled: &Arc<Mutex<HashSet<u64>>>) -> bool {
-    cancelled
-        .lock()
-        .map(|set| set.contains(&request_id))
-        .unwrap_or(false)
+/// Result for a job given up on at its deadline, after waiting in the queue until
+/// `picked_up` and running for `ran`.
+fn expired(job: &TranscriptionJob, picked_up: Instant, ran: Option<Duration>) -> JobResult {
+    let millis = |duration: Duration| duration.as_millis().try_into().unwrap_or(u64::MAX);
+    let ran = ran.map(millis).unwrap_or(0);
+    JobResult::Expired {
+        request_id: job.request_id,
+        metrics: JobMetrics {
+            queue_wait_ms: millis(picked_up.saturating_duration_since(job.enqueue_at)),
+            inference_ms: ran,
+            worker_total_ms: ran,
+            cache_hit: false,
+        },
+    }
 }
 
-fn process_job(runner: &mut SttRunner, job: &TranscriptionJob) -> Result<(Vec<u8>, u64)> {
-    let mut audio_file = NamedTempFile::new()?;
-    audio_file.write_all(&job.audio_data)?;
-    audio_file.flush()?;
-
+fn process_job(runner: &mut SttRunner, job: &TranscriptionJob) -> Result<(Transcript, u64)> {
     let duration_ms = if job.duration_ms > 0 {
         job.duration_ms
     } else {
-        derive_duration_ms(audio_file.path()).unwrap_or(0)
+        job.audio.duration_ms()
     };
 
     let infer_start = Instant::now();
-    runner.transcribe(audio_file.path(), audio_file.path(), duration_ms)?;
+    let transcript = match &job.on_segment {
+        Some(sink) => runner.transcribe_streaming(&job.audio, duration_ms, &job.options, sink)?,
+        None => runner.transcribe_with_options(&job.audio, duration_ms, &job.options)?,
+    };
     let inference_ms = infer_start
         .elapsed()
         .as_millis()
         .try_into()
         .unwrap_or(u64::MAX);
 
-    let srt_path = audio_file.path().with_extension("srt");
-    let srt_data = std::fs::read(&srt_path)?;
-
-    if srt_data.is_empty() {
+    if transcript.is_empty() {
         warn!(
             "No subtitles produced for request {} (empty transcription output)",
             job.request_id
         );
-        return Ok((Vec::new(), inference_ms));
     }
 
-    Ok((srt_data, inference_ms))
-}
-
-fn derive_duration_ms(path: &std::path::Path) -> Option<u64> {
-    let reader = hound::WavReader::open(path).ok()?;
-    let spec = reader.spec();
-    let samples = reader.len() as u64;
-    let rate = spec.sample_rate as u64;
-    if rate == 0 {
-        return None;
-    }
-    Some(samples.saturating_mul(1000) / rate)
+    Ok((transcript, inference_ms))
 }
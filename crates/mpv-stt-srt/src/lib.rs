@@ -1,5 +1,5 @@
 use log::{debug, trace};
-use mpv_stt_common::{MpvSttError, Result};
+use mpv_stt_common::{MpvSttError, Result, Transcript, TranscriptSegment};
 use srtlib::{Subtitle, Subtitles};
 use std::fmt;
 use std::fs;
@@ -98,6 +98,45 @@ impl SrtFile {
         self.entries.push(entry);
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Shift every entry by `offset_ms` in place.
+    pub fn offset(&mut self, offset_ms: i64) {
+        trace!("Offsetting SRT timestamps by {}ms", offset_ms);
+        for entry in &mut self.entries {
+            entry.start_time.add_milliseconds(offset_ms);
+            entry.end_time.add_milliseconds(offset_ms);
+        }
+    }
+
+    /// Entries as transcript segments, in file order.
+    pub fn segments(&self) -> Vec<TranscriptSegment> {
+        self.entries
+            .iter()
+            .map(|entry| TranscriptSegment {
+                start_ms: timestamp_to_millis(entry.start_time) as u64,
+                end_ms: timestamp_to_millis(entry.end_time) as u64,
+                text: entry.text.clone(),
+            })
+            .collect()
+    }
+
+    /// Render as WebVTT; cue timings use `.` before the milliseconds.
+    pub fn to_vtt(&self) -> String {
+        let mut out = String::from("WEBVTT\n");
+        for entry in &self.entries {
+            out.push_str(&format!(
+                "\n{} --> {}\n{}\n",
+                vtt_timestamp(entry.start_time),
+                vtt_timestamp(entry.end_time),
+                entry.text
+            ));
+        }
+        out
+    }
+
     pub fn merge_bilingual(&mut self, translations: &[String]) {
         for (i, entry) in self.entries.iter_mut().enumerate() {
             if i < translations.len() && !translations[i].is_empty() {
@@ -107,6 +146,37 @@ impl SrtFile {
     }
 }
 
+impl From<&Transcript> for SrtFile {
+    fn from(transcript: &Transcript) -> Self {
+        let entries = transcript
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(idx, segment)| SubtitleEntry {
+                index: (idx + 1) as u32,
+                start_time: millis_to_timestamp(segment.start_ms),
+                end_time: millis_to_timestamp(segment.end_ms),
+                text: segment.text.clone(),
+            })
+            .collect();
+        Self { entries }
+    }
+}
+
+fn timestamp_to_millis(ts: Timestamp) -> u32 {
+    let (h, m, s, ms) = ts.get();
+    Timestamp::convert_to_milliseconds(h, m, s, ms)
+}
+
+fn vtt_timestamp(ts: Timestamp) -> String {
+    let (h, m, s, ms) = ts.get();
+    format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms)
+}
+
+fn millis_to_timestamp(ms: u64) -> Timestamp {
+    Timestamp::from_milliseconds(u32::try_from(ms).unwrap_or(u32::MAX))
+}
+
 impl fmt::Display for SrtFile {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for (i, entry) in self.entries.iter().enumerate() {
@@ -170,4 +240,43 @@ mod tests {
         assert_eq!(converted_back.end_time.to_string(), "00:00:15,500");
         assert_eq!(converted_back.text, "Test subtitle");
     }
+
+    #[test]
+    fn test_transcript_roundtrip() {
+        let transcript = Transcript {
+            segments: vec![TranscriptSegment {
+                start_ms: 61_250,
+                end_ms: 63_000,
+                text: "Hello there".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let srt = SrtFile::from(&transcript);
+        assert_eq!(
+            srt.to_string(),
+            "1\n00:01:01,250 --> 00:01:03,000\nHello there"
+        );
+        assert_eq!(srt.segments(), transcript.segments);
+    }
+
+    #[test]
+    fn test_to_vtt() {
+        let srt = SrtFile::parse_content(
+            "1\n00:00:01,000 --> 00:00:02,500\nHello\n\n2\n01:00:03,040 --> 01:00:04,000\nWorld\n",
+        )
+        .unwrap();
+        assert_eq!(
+            srt.to_vtt(),
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.500\nHello\n\n01:00:03.040 --> 01:00:04.000\nWorld\n"
+        );
+    }
+
+    #[test]
+    fn test_offset_in_memory() {
+        let mut srt = SrtFile::parse_content("1\n00:00:01,000 --> 00:00:02,500\nHello\n").unwrap();
+        srt.offset(60_000);
+        assert_eq!(srt.entries[0].start_time.to_string(), "00:01:01,000");
+        assert_eq!(srt.entries[0].end_time.to_string(), "00:01:02,500");
+    }
 }